@@ -0,0 +1,44 @@
+use serde::{Deserialize, Serialize};
+
+/// Identifies the exact binary which produced a report or trace, so that bug reports can be reproduced
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct BuildInfo {
+    pub version: String,
+    /// The commit the binary was built from, if it was built from a git checkout
+    pub git_hash: Option<String>,
+    pub target: String,
+    /// The cargo profile the binary was built with, e.g. `release` or `debug`
+    pub profile: String,
+    pub full_moon_version: Option<String>,
+    pub features: Vec<String>,
+}
+
+fn non_empty(value: &str) -> Option<String> {
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+impl BuildInfo {
+    /// The build information of the running binary, captured by the build script
+    pub fn current() -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_hash: non_empty(env!("BUILD_GIT_HASH")),
+            target: env!("BUILD_TARGET").to_string(),
+            profile: env!("BUILD_PROFILE").to_string(),
+            full_moon_version: non_empty(env!("BUILD_FULL_MOON_VERSION")),
+            features: env!("BUILD_FEATURES")
+                .split(',')
+                .filter_map(non_empty)
+                .collect(),
+        }
+    }
+
+    /// A single line identifying the binary, e.g. `1.4.2 (a1b2c3d, x86_64-unknown-linux-musl)`
+    pub fn summary(&self) -> String {
+        match &self.git_hash {
+            Some(git_hash) => format!("{} ({git_hash}, {})", self.version, self.target),
+            None => format!("{} ({})", self.version, self.target),
+        }
+    }
+}
@@ -1,47 +1,368 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::bail;
 use anyhow::Context;
 use anyhow::Result;
-use clap::Parser;
-use full_moon::ast::LastStmt;
+use clap::{Args, Parser, Subcommand};
+use full_moon::{
+    ast::{luau::ExportedTypeDeclaration, Ast, Expression, LastStmt},
+    node::Node as _,
+};
 use log::error;
 use log::info;
 use log::warn;
+use log::LevelFilter;
+use rayon::prelude::*;
 
+use crate::build_info::BuildInfo;
+use crate::config::{Config, CONFIG_FILE_NAME};
+use crate::declaration_cache::DeclarationCache;
+use crate::diagnostics::{Diagnostics, Warning, WarningCategory};
+use crate::failure::{json_error, ClassifiedError, ErrorFormat, FailureClass, LuauParseError};
+use crate::file_access::{FileAccess, FileSystem, STDIN_PATH};
+use crate::formatting::{LineEndings, OutputStyle};
+use crate::html_report;
+use crate::incremental::{IncrementalCache, CACHE_FILE_NAME};
 use crate::link_mutator::*;
+use crate::lockfile::{
+    containing_index_folder, index_folder_matches, index_folder_name, is_index_folder,
+    is_inside_index_folder, package_folder_depth, parse_index_folder_name, Lockfile,
+};
+use crate::luaurc::{package_aliases, update_luaurc};
+use crate::progress::Progress;
+use crate::report::{OutputFormat, PackageReport, Report, ThunkReport, ThunkStatus};
 use crate::require_parser::*;
+use crate::resolution_error::{ErrorCode, ResolutionError};
 use crate::sourcemap::*;
+use crate::sourcemap_command::{GeneratedSourcemap, DEFAULT_SOURCEMAP_COMMAND};
+use crate::strictness::{self, analyse_packages};
+use crate::string_require::{
+    is_project_file, module_require_path, project_module_file, relative_string_require,
+    resolve_module_file, resolve_string_require,
+};
+use crate::suggestions;
+use crate::summary;
+use crate::timings::Timings;
+use crate::trace::{hash_contents, PathKind, Trace};
+use crate::transaction::{self, JOURNAL_FILE_NAME};
+use crate::watchdog::run_with_timeout;
+use crate::workspace_edit::WorkspaceEdits;
 
 #[derive(Parser, Debug)]
-#[clap(author, version, about)]
+#[clap(author, version, about, args_conflicts_with_subcommands = true)]
 pub struct Command {
-    /// Path to sourcemap
-    #[clap(short, long, value_parser)]
-    pub sourcemap: PathBuf,
+    #[clap(subcommand)]
+    subcommand: Option<Operation>,
+
+    /// Print the version, commit, target and dependencies the binary was built with as JSON, for bug reports
+    #[clap(long)]
+    buildinfo: bool,
+
+    /// Running without a subcommand behaves like `fix`
+    #[clap(flatten)]
+    options: Options,
+}
+
+#[derive(Subcommand, Debug)]
+enum Operation {
+    /// Rewrite link files to re-export the types of the modules they link to
+    Fix(Options),
+    /// Check that no link files need to be rewritten, without writing anything
+    Check(Options),
+    /// Restore link files generated by a previous run back to the plain links they were generated from
+    Clean(Options),
+    /// Print the types each link file would re-export, without writing anything
+    List(Options),
+    /// Print every type a package exports, with its generics and the file and line it is declared on, e.g. to
+    /// review the types of a dependency before upgrading it
+    Types(TypesQuery),
+    /// Restore the files written by the last run which wrote any to their previous contents
+    Rollback(Rollback),
+    /// Tools for investigating why packages fail to resolve
+    #[clap(subcommand)]
+    Debug(DebugOperation),
+}
+
+#[derive(Subcommand, Debug)]
+enum DebugOperation {
+    /// Print the nodes added, removed or moved between two sourcemaps, e.g. after regenerating the sourcemap
+    DiffSourcemaps(DiffSourcemaps),
+}
+
+#[derive(Args, Debug)]
+struct TypesQuery {
+    /// The package to print the types of, e.g. `sleitnick/signal`, `sleitnick_signal` or `sleitnick_signal@1.5.0`
+    #[clap(value_name = "PACKAGE")]
+    name: String,
+
+    #[clap(flatten)]
+    options: Options,
+}
+
+#[derive(Args, Debug)]
+struct Rollback {
+    /// Path to the journal written by the run to roll back, which is kept next to the packages folder
+    #[clap(long, value_parser, default_value = JOURNAL_FILE_NAME)]
+    journal: PathBuf,
+}
+
+impl Rollback {
+    fn run(&self) -> Result<()> {
+        transaction::rollback(&FileAccess::new(1), &self.journal)
+    }
+}
 
-    /// Path to packages
+#[derive(Args, Debug)]
+struct DiffSourcemaps {
+    /// Path to the old sourcemap
     #[clap(value_parser)]
-    pub packages_folder: PathBuf,
+    old: PathBuf,
+
+    /// Path to the new sourcemap
+    #[clap(value_parser)]
+    new: PathBuf,
+
+    /// Only compare nodes under this name path, e.g. `Packages` or `ReplicatedStorage/Packages`
+    #[clap(long, value_name = "NAME_PATH", default_value = "")]
+    under: String,
 }
 
-fn find_node(root: &SourcemapNode, path: PathBuf) -> Option<Vec<&SourcemapNode>> {
-    let mut stack = vec![vec![root]];
+impl DiffSourcemaps {
+    fn read_sourcemap(path: &Path) -> Result<SourcemapNode> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read sourcemap '{}'", path.display()))?;
+        serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse sourcemap '{}'", path.display()))
+    }
+
+    fn run(self) -> Result<()> {
+        let diff = diff_sourcemaps(
+            &Self::read_sourcemap(&self.old)?,
+            &Self::read_sourcemap(&self.new)?,
+            &self.under,
+        );
 
-    while let Some(node_path) = stack.pop() {
-        let node = node_path.last().unwrap();
-        if node.file_paths.contains(&path.to_path_buf()) {
-            return Some(node_path);
+        for path in &diff.removed {
+            println!("- {path}");
         }
-
-        for child in &node.children {
-            let mut path = node_path.clone();
-            path.push(child);
-            stack.push(path);
+        for path in &diff.added {
+            println!("+ {path}");
+        }
+        for (old, new) in &diff.moved {
+            println!("~ {old} -> {new}");
         }
+        println!(
+            "{} added, {} removed, {} moved",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.moved.len()
+        );
+        Ok(())
     }
+}
+
+/// What a run does with the link files it finds
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    #[default]
+    Fix,
+    Check,
+    Clean,
+    List,
+    Types,
+}
+
+#[derive(Args, Debug)]
+pub struct Options {
+    #[clap(skip)]
+    pub mode: Mode,
+
+    /// Changes to the types re-exported from each package, which can only be set in the configuration
+    #[clap(skip)]
+    pub types: BTreeMap<String, TypeOverrides>,
+
+    /// Path to sourcemap, or `-` to read it from stdin
+    #[clap(short, long, value_parser)]
+    pub sourcemap: Option<PathBuf>,
+
+    /// Generate a fresh sourcemap before processing instead of reading --sourcemap, removing it afterwards
+    #[clap(long, conflicts_with = "replay")]
+    pub generate_sourcemap: bool,
+
+    /// Command --generate-sourcemap runs, where `{output}` is replaced with the path to write the sourcemap to.
+    /// Without `{output}`, the sourcemap is read from its stdout
+    #[clap(long, value_name = "COMMAND", default_value = DEFAULT_SOURCEMAP_COMMAND)]
+    pub sourcemap_command: String,
+
+    /// Paths to packages folders, which may contain glob patterns such as `places/*/Packages`.
+    /// May also be the path to a single link file
+    #[clap(value_parser)]
+    pub packages_folders: Vec<PathBuf>,
+
+    /// Where `game`-rooted require paths actually are in the sourcemap, as `from=to`, e.g.
+    /// `game/ReplicatedStorage/Packages=game/ReplicatedStorage/Shared/Packages`. Can be repeated
+    #[clap(long, value_parser, value_name = "FROM=TO")]
+    pub root_map: Vec<RootMap>,
+
+    /// Only process link files with this name, or belonging to this package (e.g. `sleitnick/signal`,
+    /// `sleitnick_signal` or `sleitnick_signal@1.5.0`). Can be repeated
+    #[clap(long, value_name = "NAME")]
+    pub package: Vec<String>,
+
+    /// Skip link files with this name, or belonging to this package (e.g. `sleitnick/signal`,
+    /// `sleitnick_signal` or `sleitnick_signal@1.5.0`). Can be repeated
+    #[clap(long, visible_alias = "exclude", value_name = "NAME")]
+    pub skip: Vec<String>,
+
+    /// Number of threads to use when mutating thunks. Defaults to the number of logical CPUs
+    #[clap(short, long, value_parser)]
+    pub threads: Option<usize>,
+
+    /// Maximum number of files that may be open simultaneously whilst mutating thunks
+    #[clap(long, value_parser, default_value_t = 64)]
+    pub max_open_files: usize,
+
+    /// Maximum size of a module in megabytes, above which its link file fails rather than reading it.
+    /// Guards against corrupt downloads. 0 disables the limit
+    #[clap(long, value_parser, value_name = "MEGABYTES", default_value_t = 32)]
+    pub max_file_size: u64,
+
+    /// Match files by their lexically normalized paths rather than canonicalizing them, for symlinked workspaces and
+    /// network drives. Symlinks are then not followed when matching link files to the sourcemap
+    #[clap(long)]
+    pub no_canonicalize: bool,
+
+    /// Maximum number of seconds parsing a single module may take before its link file fails.
+    /// 0 disables the timeout
+    #[clap(long, value_parser, value_name = "SECONDS", default_value_t = 30)]
+    pub parse_timeout: u64,
+
+    /// What to do with exported types whose names shadow builtin Luau or Roblox types
+    #[clap(long, value_enum)]
+    pub shadowed_builtins: Option<ShadowedBuiltinBehaviour>,
+
+    /// Also export a type named after each link file holding the type of the whole required module,
+    /// e.g. `export type React = typeof(REQUIRED_MODULE)`
+    #[clap(long)]
+    pub namespace_types: bool,
+
+    /// Add an alias for each package to the `.luaurc` next to the packages folder, pointing to the module it links to
+    #[clap(long)]
+    pub luaurc: bool,
+
+    /// When a linked module only re-exports the types of another module, e.g. those of a peer dependency,
+    /// re-export the types from the module they are declared in instead
+    #[clap(long)]
+    pub follow_re_exports: bool,
+
+    /// Skip link files which, along with the modules they link to, are unchanged since the last run.
+    /// Keeps a cache file next to the packages folder
+    #[clap(long)]
+    pub incremental: bool,
+
+    /// Stop at the first link file which fails to be mutated, rather than continuing with the rest
+    #[clap(long)]
+    pub strict: bool,
+
+    /// Record every file read and resolution decision into a trace file, for reproducing bugs
+    #[clap(long, value_parser, conflicts_with = "replay")]
+    pub record: Option<PathBuf>,
+
+    /// Re-run against the file contents embedded in a recorded trace file, without writing anything
+    #[clap(long, value_parser)]
+    pub replay: Option<PathBuf>,
+
+    /// Format to output the results of the run in
+    #[clap(long, value_enum)]
+    pub output_format: Option<OutputFormat>,
+
+    /// Format to output errors in. Each failure class exits with its own code
+    #[clap(long, value_enum)]
+    pub error_format: Option<ErrorFormat>,
+
+    /// Compute the new link files without writing them
+    #[clap(long)]
+    pub dry_run: bool,
+
+    /// Keep a `.bak` copy of the previous contents of each file written, next to it
+    #[clap(long)]
+    pub backup: bool,
+
+    /// Write the changes to an LSP WorkspaceEdit JSON file rather than to the link files, so an editor can apply them
+    #[clap(long, value_name = "PATH")]
+    pub emit_workspace_edit: Option<PathBuf>,
+
+    /// Print the new contents of a single link file to stdout rather than writing it, e.g. to use the tool in a pipe.
+    /// Any other output is suppressed
+    #[clap(long, conflicts_with_all = &["record", "replay", "dry-run", "emit-workspace-edit"])]
+    pub stdout: bool,
+
+    /// Also write a `.d.luau` stub for each package into this directory, re-exporting its types from the module
+    /// the package links to
+    #[clap(long, value_name = "DIR")]
+    pub emit_declarations: Option<PathBuf>,
+
+    /// Print a unified diff of the changes made to each link file
+    #[clap(long)]
+    pub diff: bool,
+
+    /// Write a report of the run to a file, e.g. `--report html report.html`. `strictness` writes a report of
+    /// the packages whose types use `any`, most severe first. Can be repeated
+    #[clap(long, number_of_values = 2, value_names = &["FORMAT", "PATH"])]
+    pub report: Vec<String>,
+
+    /// Print how long each phase of the run took to stderr
+    #[clap(long)]
+    pub timings: bool,
+
+    /// Suppress all logging, and print a single summary line once the run completes
+    #[clap(long)]
+    pub summary_only: bool,
 
-    None
+    /// Only log warnings and errors rather than the progress of each link file, e.g. in CI
+    #[clap(short, long)]
+    pub quiet: bool,
+
+    /// List every warning in the end of run summary, rather than only counting them per package
+    #[clap(long)]
+    pub verbose: bool,
+
+    /// Path to the wally.lock file used to verify root links point to locked versions.
+    /// Defaults to the wally.lock next to the packages folder, if present
+    #[clap(long, value_parser)]
+    pub lockfile: Option<PathBuf>,
+
+    /// Rewrite root links pointing to a version which is not locked in wally.lock to point to the locked version
+    #[clap(long)]
+    pub repair_links: bool,
+
+    /// Line endings of generated link files. Defaults to those of the StyLua configuration if given,
+    /// otherwise to the line endings each link file already uses
+    #[clap(long, value_enum)]
+    pub line_endings: Option<LineEndings>,
+
+    /// Path to a `stylua.toml`, whose line endings generated link files follow, with trailing whitespace removed
+    #[clap(long, value_parser)]
+    pub stylua_config: Option<PathBuf>,
+
+    /// Promote all warnings to errors, causing the run to fail if any are emitted
+    #[clap(long)]
+    pub fail_on_warning: bool,
+
+    /// Promote a category of warnings to errors. Can be repeated
+    #[clap(long, value_enum)]
+    pub deny: Vec<WarningCategory>,
+
+    /// Fail the run if more than this many warnings are emitted
+    #[clap(long, value_name = "N")]
+    pub max_warnings: Option<usize>,
+
+    /// Keep a category of warnings as warnings, even with --fail-on-warning. Can be repeated
+    #[clap(long, value_enum)]
+    pub allow: Vec<WarningCategory>,
 }
 
 fn lua_files_filter(path: &&PathBuf) -> bool {
@@ -51,186 +372,2376 @@ fn lua_files_filter(path: &&PathBuf) -> bool {
     }
 }
 
+/// Whether a file is a `.d.luau` declaration file, which only declares types rather than being a runtime module
+fn is_declaration_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(".d.luau"))
+}
+
+/// Finds the Luau file of a node, preferring its runtime module over any declaration file
+fn module_file_path(node: &Node) -> Option<&PathBuf> {
+    let mut lua_files = node.file_paths.iter().filter(lua_files_filter);
+    lua_files
+        .clone()
+        .find(|path| !is_declaration_file(path))
+        .or_else(|| lua_files.next())
+}
+
+/// Finds the declaration files belonging to the same sourcemap node as the given module
+fn declaration_file_paths<'a>(sourcemap: &'a Sourcemap, module_path: &Path) -> Vec<&'a PathBuf> {
+    sourcemap.find_by_path(module_path).map_or(vec![], |id| {
+        sourcemap
+            .node(id)
+            .file_paths
+            .iter()
+            .filter(|path| is_declaration_file(path) && path.as_path() != module_path)
+            .collect()
+    })
+}
+
+/// What the components of a require path resolved to
+enum Resolved {
+    Node(NodeId),
+    /// A module found on disk after the require left the sourcemap, which is not in the sourcemap itself
+    File(PathBuf),
+}
+
+/// Where a node is on disk, i.e. its folder, or its module without an extension
+fn node_disk_path(node: &Node, files: &FileAccess) -> Option<PathBuf> {
+    if let Some(module) = module_file_path(node) {
+        return Some(module_require_path(module));
+    }
+    node.file_paths
+        .iter()
+        .find(|path| files.path_kind(path) == PathKind::Directory)
+        .cloned()
+}
+
+/// Resolves the rest of a require path against the directory structure on disk, once a `Parent` has walked off
+/// the top of the sourcemap. The module found is resolved back into a node if it is in the sourcemap after all
+fn resolve_on_disk<'a>(
+    sourcemap: &Sourcemap,
+    files: &FileAccess,
+    start: &Path,
+    components: impl Iterator<Item = &'a String>,
+) -> Result<Resolved> {
+    let mut disk_path = start.to_path_buf();
+    for component in components {
+        if component == "Parent" {
+            disk_path = disk_path
+                .parent()
+                .with_context(|| format!("'{}' has no parent directory", disk_path.display()))?
+                .to_path_buf();
+        } else {
+            disk_path.push(component);
+        }
+    }
+
+    let file_path = resolve_module_file(&disk_path, files)?;
+    let file_path = files
+        .canonicalize(&file_path)
+        .with_context(|| format!("Failed to canonicalize '{}'", file_path.display()))?;
+    Ok(match sourcemap.find_by_path(&file_path) {
+        Some(id) => Resolved::Node(id),
+        None => {
+            info!(
+                "Link require points to '{}', which is not in the sourcemap",
+                file_path.display()
+            );
+            Resolved::File(file_path)
+        }
+    })
+}
+
 /// Given a list of components (e.g., ['script', 'Parent', 'Example']), converts it to a file path
-fn file_path_from_components(
+fn node_from_components(
     path: &Path,
-    root: &SourcemapNode,
+    sourcemap: &Sourcemap,
+    files: &FileAccess,
     path_components: Vec<String>,
-) -> Result<PathBuf> {
+    root_maps: &[RootMap],
+) -> Result<Resolved> {
+    let path_components = RootMap::apply(root_maps, path_components);
     let mut iter = path_components.iter();
-    let first_in_chain = iter.next().context("No path components")?;
+    let first_in_chain = iter.next().map(String::as_str);
 
-    if !(first_in_chain == "script" || first_in_chain == "game") {
-        bail!("require expression does not start with 'script' or 'game', cannot determine starting point");
+    if !matches!(first_in_chain, Some("script" | "game")) {
+        bail!(ResolutionError::new(
+            ErrorCode::UnsupportedRoot,
+            "require expression does not start with 'script' or 'game', cannot determine starting point"
+        ));
     }
 
-    let mut node_path = if first_in_chain == "script" {
-        find_node(root, path.canonicalize()?)
-            .with_context(|| format!("Linker node '{}' not found in sourcemap", path.display()))?
+    let mut current = if first_in_chain == Some("script") {
+        sourcemap
+            .find_by_path(&files.canonicalize(path)?)
+            .ok_or_else(|| {
+                ResolutionError::new(
+                    ErrorCode::LinkNotInSourcemap,
+                    format!("Linker node '{}' not found in sourcemap", path.display()),
+                )
+            })?
     } else {
-        vec![root]
+        sourcemap.root()
     };
+    // Where the current node is on disk, following the require from the last node which has a path on disk
+    let mut disk_path = node_disk_path(sourcemap.node(current), files);
 
-    for component in iter {
+    while let Some(component) = iter.next() {
         if component == "Parent" {
-            node_path
-                .pop()
-                .context("No parent found in linked components")?;
-        } else {
-            node_path.push(
-                node_path
-                    .last()
-                    .unwrap()
-                    .find_child(component.to_string())
-                    .with_context(|| {
+            let Some(parent) = sourcemap.node(current).parent else {
+                let no_parent = |detail: String| {
+                    ResolutionError::new(
+                        ErrorCode::NoParent,
                         format!(
-                            "Child '{component}' not found in '{}'",
-                            node_path
-                                .iter()
-                                .map(|node| node.name.as_str())
-                                .collect::<Vec<_>>()
-                                .join("/")
-                        )
-                    })?,
-            );
+                            "'{}' has no parent in the sourcemap{detail}",
+                            sourcemap.name_path(current)
+                        ),
+                    )
+                    .with_resolved(sourcemap.name_path(current))
+                };
+                let Some(disk_path) = disk_path else {
+                    bail!(no_parent(String::new()));
+                };
+                info!(
+                    "Require leaves the sourcemap at '{}', resolving the rest of it on disk from '{}'",
+                    sourcemap.name_path(current),
+                    disk_path.display()
+                );
+                return resolve_on_disk(
+                    sourcemap,
+                    files,
+                    &disk_path,
+                    std::iter::once(component).chain(iter),
+                )
+                .map_err(|err| {
+                    no_parent(format!(
+                        ", nor could the rest of it be resolved on disk from '{}': {err:#}",
+                        disk_path.display()
+                    ))
+                    .into()
+                });
+            };
+            current = parent;
+            disk_path = node_disk_path(sourcemap.node(current), files)
+                .or_else(|| Some(disk_path?.parent()?.to_path_buf()));
+        } else {
+            current = sourcemap.find_child(current, component).ok_or_else(|| {
+                ResolutionError::new(
+                    ErrorCode::ChildNotFound,
+                    format!(
+                        "Child '{component}' not found in '{}'",
+                        sourcemap.name_path(current)
+                    ),
+                )
+                .with_resolved(sourcemap.name_path(current))
+            })?;
+            disk_path = node_disk_path(sourcemap.node(current), files)
+                .or_else(|| Some(disk_path?.join(component)));
         }
     }
 
-    let current = node_path.last().unwrap();
-    let file_path = current
-        .file_paths
-        .iter()
-        .find(lua_files_filter)
-        .context("No .lua/.luau file found for linked node")?
-        .clone();
+    Ok(Resolved::Node(current))
+}
+
+/// The project file a package may ship in its folder, which maps the package's root to its entry module
+const NESTED_PROJECT_FILE_NAME: &str = "default.project.json";
+
+/// Finds the Luau file of the node a require points to. Packages which ship their own Rojo project file have it
+/// alongside or instead of their Luau files, in which case the module the project maps its root to is preferred.
+/// A require may also land on the package's folder, in which case a project file inside of it is used
+fn node_file_path(
+    sourcemap: &Sourcemap,
+    id: NodeId,
+    files: &FileAccess,
+) -> Result<Option<PathBuf>> {
+    let node = sourcemap.node(id);
+    let mut project_module = match node.file_paths.iter().find(|path| is_project_file(path)) {
+        Some(project_path) => project_module_file(project_path, files)?,
+        None => None,
+    };
+    if project_module.is_none() && module_file_path(node).is_none() {
+        let nested_project = node
+            .file_paths
+            .iter()
+            .map(|path| path.join(NESTED_PROJECT_FILE_NAME))
+            .find(|project_path| files.path_kind(project_path) == PathKind::File);
+        if let Some(project_path) = nested_project {
+            info!(
+                "Linked node is a folder, using the project file '{}' inside of it",
+                project_path.display()
+            );
+            project_module = project_module_file(&project_path, files)?;
+        }
+    }
+    let Some(file_path) = project_module.or_else(|| module_file_path(node).cloned()) else {
+        return Ok(None);
+    };
     info!(
         "Link require points to {} [{}] @ '{}'",
-        current.name,
-        current.class_name,
+        node.name,
+        node.class_name,
         file_path.display()
     );
 
-    Ok(file_path)
+    Ok(Some(file_path))
 }
 
-enum MutateResult {
-    Successful,
-    FailedToParseReturnStmt,
+fn file_path_from_components(
+    path: &Path,
+    sourcemap: &Sourcemap,
+    files: &FileAccess,
+    path_components: Vec<String>,
+    root_maps: &[RootMap],
+) -> Result<PathBuf> {
+    match node_from_components(path, sourcemap, files, path_components, root_maps)? {
+        Resolved::Node(id) => node_file_path(sourcemap, id, files)?
+            .ok_or_else(|| no_module_file(sourcemap, id).into()),
+        Resolved::File(file_path) => Ok(file_path),
+    }
 }
 
-fn mutate_thunk(path: &Path, root: &SourcemapNode) -> Result<MutateResult> {
-    info!("Found link file '{}'", path.display());
+fn no_module_file(sourcemap: &Sourcemap, id: NodeId) -> ResolutionError {
+    ResolutionError::new(
+        ErrorCode::NoModuleFile,
+        format!(
+            "No .lua/.luau file found for linked node '{}'",
+            sourcemap.name_path(id)
+        ),
+    )
+}
 
-    // The entry should be a thunk
-    let parsed_code = match full_moon::parse(&std::fs::read_to_string(path)?) {
-        Ok(parsed_code) => parsed_code,
-        Err(errors) => bail!(errors
+/// Attributes an error resolving a require to the require expression in the link file it came from
+fn in_require(err: anyhow::Error, path: &Path, expression: &Expression) -> anyhow::Error {
+    match err.downcast::<ResolutionError>() {
+        Ok(err) => err.in_require(path, expression).into(),
+        Err(err) => err,
+    }
+}
+
+/// Extensions of files Rojo can turn into instances other than Luau modules, which types cannot be extracted from
+const NON_LUAU_EXTENSIONS: &[&str] = &["rbxm", "rbxmx", "json", "txt"];
+
+/// Finds a file of a node which is not a Luau module, such as a `.rbxm` model or a `.json` file
+fn non_luau_file_path(node: &Node) -> Option<&PathBuf> {
+    node.file_paths.iter().find(|path| {
+        path.extension().is_some_and(|extension| {
+            NON_LUAU_EXTENSIONS
+                .iter()
+                .any(|non_luau| extension == *non_luau)
+        })
+    })
+}
+
+/// Indexes instances by name, e.g. `.Example["name with spaces"]`
+fn instance_indexes(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|name| {
+            if is_identifier(name) {
+                format!(".{name}")
+            } else {
+                format!("[\"{name}\"]")
+            }
+        })
+        .collect()
+}
+
+/// Parses a single expression, such as a require call created for a new link file
+fn parse_expression(code: &str) -> Result<Expression> {
+    let parsed_code = full_moon::parse(&format!("return {code}\n"))
+        .map_err(|_| anyhow::anyhow!("Failed to parse expression '{code}'"))?;
+    match parsed_code.nodes().last_stmt() {
+        Some(LastStmt::Return(r#return)) => r#return
+            .returns()
             .iter()
-            .map(|err| err.to_string())
-            .collect::<Vec<_>>()
-            .join("\n")),
-    };
+            .next()
+            .cloned()
+            .with_context(|| format!("Failed to parse expression '{code}'")),
+        _ => bail!("Failed to parse expression '{code}'"),
+    }
+}
+
+/// Follows the members indexed on a required module (e.g. `require(x).SubModule`) to the module they refer to,
+/// either a child of the required module or a module it re-exports as a field. Returns its file path, alongside
+/// a require pointing directly at it which its types can be referenced through
+fn resolve_member_access(
+    path: &Path,
+    require: &MatchedRequire,
+    mut file_path: PathBuf,
+    context: &MutationContext,
+) -> Result<(PathBuf, Expression)> {
+    let sourcemap = context.sourcemap;
 
-    if let Some(LastStmt::Return(r#return)) = parsed_code.nodes().last_stmt() {
-        let returned_expression = r#return.returns().iter().next().unwrap();
+    let types_require = match &require.path {
+        RequirePath::Instance(_) => {
+            let mut appended = Vec::new();
+            for member in &require.members {
+                let node = sourcemap
+                    .find_by_path(&file_path)
+                    .with_context(|| format!("'{}' not found in sourcemap", file_path.display()))?;
 
-        let path_components = match match_require(returned_expression) {
-            Ok(components) => components,
-            Err(err) => {
-                warn!("Malformed link file, could not parse return expression, skipping. Run `wally install` to regenerate link files");
-                error!("{:#}", err);
-                return Ok(MutateResult::FailedToParseReturnStmt);
+                let member_node = match sourcemap.find_child(node, member) {
+                    Some(child) => {
+                        appended.push(member.clone());
+                        child
+                    }
+                    None => {
+                        let contents = context.files.read_module(&file_path)?;
+                        let Some(RequirePath::Instance(components)) =
+                            find_table_field_require(&contents, member)?
+                        else {
+                            bail!(
+                                "'{member}' is not a child of, or re-exported by, '{}'",
+                                sourcemap.name_path(node)
+                            );
+                        };
+                        let re_exported = file_path_from_components(
+                            &file_path,
+                            sourcemap,
+                            context.files,
+                            components,
+                            &context.root_maps,
+                        )?;
+                        let re_exported_node =
+                            sourcemap.find_by_path(&re_exported).with_context(|| {
+                                format!("'{}' not found in sourcemap", re_exported.display())
+                            })?;
+                        let names = sourcemap
+                            .relative_names(node, re_exported_node)
+                            .with_context(|| {
+                                format!(
+                                    "'{member}' is re-exported from outside of '{}', so cannot be required directly",
+                                    sourcemap.name_path(node)
+                                )
+                            })?;
+                        appended.extend(names);
+                        re_exported_node
+                    }
+                };
+
+                file_path = node_file_path(sourcemap, member_node, context.files)?
+                    .with_context(|| format!("No .lua/.luau file found for '{member}'"))?;
             }
-        };
 
-        info!(
-            "Require expression converted to path: '{}'",
-            path_components.join("/")
-        );
+            format!(
+                "require({}{})",
+                require.argument.to_string().trim(),
+                instance_indexes(&appended)
+            )
+        }
+        RequirePath::String(string_require) => {
+            let member_require = format!("{string_require}/{}", require.members.join("/"));
+            file_path = resolve_string_require(path, &member_require, context.files)?;
+            format!("require(\"{member_require}\")")
+        }
+    };
 
-        let file_path = file_path_from_components(path, root, path_components)
-            .context("Could not convert require expression to file path")?;
-        let pass_through_contents =
-            std::fs::read_to_string(file_path).context("Failed to read linked file")?;
-        let returns = r#return.returns().clone();
-        let new_link_contents = mutate_link(parsed_code, returns, &pass_through_contents)
-            .context("Failed to create new link contents")?;
-
-        match new_link_contents {
-            MutateLinkResult::Changed(new_ast) => {
-                info!("Exported types found, writing new linker file");
-                std::fs::write(path, new_ast.to_string())?
+    info!(
+        "Member access resolved to '{}', re-exporting its types through '{types_require}'",
+        file_path.display()
+    );
+    Ok((file_path, parse_expression(&types_require)?))
+}
+
+/// Follows a module which only re-exports the types of another module, and any link files in between, to the
+/// module the types are declared in. Returns its file path alongside the names of the types re-exported all the
+/// way through, or `None` if the module declares its own types
+fn follow_re_exports(
+    file_path: &Path,
+    context: &MutationContext,
+) -> Result<Option<(PathBuf, Vec<String>)>> {
+    let mut current = file_path.to_path_buf();
+    let mut visited = HashSet::from([current.clone()]);
+    let mut type_names: Option<Vec<String>> = None;
+
+    loop {
+        let contents = context.files.read_module(&current)?;
+        let require_path = match find_re_exported_require(&contents)? {
+            Some((require_path, names)) => {
+                type_names = Some(match type_names {
+                    Some(type_names) => type_names
+                        .into_iter()
+                        .filter(|name| names.contains(name))
+                        .collect(),
+                    None => names,
+                });
+                require_path
+            }
+            // Link files between the modules are followed through, e.g. a package's links to its dependencies
+            None if type_names.is_some() => {
+                let Ok(parsed_code) = full_moon::parse(&contents) else {
+                    break;
+                };
+                match find_link_require(&parsed_code)
+                    .and_then(|link_require| match_require(link_require.expression()).ok())
+                {
+                    Some(require) if require.members.is_empty() => require.path,
+                    _ => break,
+                }
             }
-            MutateLinkResult::Unchanged => {
-                info!("No exported types, leaving unchanged");
+            None => break,
+        };
+
+        current = match require_path {
+            RequirePath::Instance(components) => file_path_from_components(
+                &current,
+                context.sourcemap,
+                context.files,
+                components,
+                &context.root_maps,
+            )?,
+            RequirePath::String(require) => {
+                resolve_string_require(&current, &require, context.files)?
             }
         };
-    } else {
-        warn!("Malformed link file, no return statement found, skipping. Run `wally install` to regenerate link files");
-        return Ok(MutateResult::FailedToParseReturnStmt);
+        if !visited.insert(current.clone()) {
+            bail!(
+                "Types re-exported by '{}' are re-exported in a cycle",
+                file_path.display()
+            );
+        }
     }
 
-    Ok(MutateResult::Successful)
+    Ok(type_names.map(|type_names| (current, type_names)))
 }
 
-// Mutate thunk with error handled, to allow continuing
-fn handled_mutate_thunk(path: &Path, root: &SourcemapNode) -> bool {
-    match mutate_thunk(path, root) {
-        Ok(result) => matches!(result, MutateResult::Successful),
-        Err(err) => {
-            error!("{:#}", err);
-            false
+/// Creates a require from a link file to any module, of the same kind as the link's own require
+fn require_from_link(
+    path: &Path,
+    link_require: &RequirePath,
+    target: &Path,
+    context: &MutationContext,
+) -> Result<Expression> {
+    let link_path = context.files.canonicalize(path)?;
+    let require = match link_require {
+        RequirePath::Instance(_) => {
+            let sourcemap = context.sourcemap;
+            let link_node = sourcemap.find_by_path(&link_path).with_context(|| {
+                format!("Linker node '{}' not found in sourcemap", path.display())
+            })?;
+            let target_node = sourcemap
+                .find_by_path(target)
+                .with_context(|| format!("'{}' not found in sourcemap", target.display()))?;
+            let mut components = sourcemap
+                .relative_instance_path(link_node, target_node)
+                .into_iter();
+            let script = components.next().unwrap_or_default();
+            format!(
+                "require({script}{})",
+                instance_indexes(&components.collect::<Vec<_>>())
+            )
         }
-    }
+        RequirePath::String(_) => {
+            let directory = link_path
+                .parent()
+                .context("Link file has no parent directory")?;
+            let require = relative_string_require(directory, target).with_context(|| {
+                format!(
+                    "Could not require '{}' from the link file",
+                    target.display()
+                )
+            })?;
+            format!("require(\"{require}\")")
+        }
+    };
+    parse_expression(&require)
 }
 
-fn handle_index_directory(path: &Path, root: &SourcemapNode) -> Result<bool> {
-    let mut success = true;
-    for package_entry in std::fs::read_dir(path)?.flatten() {
-        for thunk in std::fs::read_dir(package_entry.path())?.flatten() {
-            if thunk.file_type().unwrap().is_file() {
-                success &= handled_mutate_thunk(&thunk.path(), root);
-            }
+/// State shared between all thunks whilst they are being mutated
+pub struct MutationContext<'a> {
+    pub sourcemap: &'a Sourcemap,
+    pub files: &'a FileAccess,
+    pub declarations: DeclarationCache,
+    pub options: MutateLinkOptions,
+    pub dry_run: bool,
+    pub diff: bool,
+    /// Restore generated link files rather than generating them
+    pub clean: bool,
+    /// Collects changes rather than writing them, if a workspace edit is being emitted
+    pub workspace_edits: Option<WorkspaceEdits>,
+    /// The canonical directory declaration stubs are written to, if they are being emitted
+    pub declarations_dir: Option<PathBuf>,
+    pub lockfile: Option<Lockfile>,
+    pub repair_links: bool,
+    pub diagnostics: Diagnostics,
+    pub output_style: OutputStyle,
+    /// Changes to the types re-exported from each package, keyed by package name
+    pub type_overrides: BTreeMap<String, TypeOverrides>,
+    pub root_maps: Vec<RootMap>,
+    /// How long parsing a single file may take before it is abandoned, if limited
+    pub parse_timeout: Option<Duration>,
+}
+
+impl<'a> MutationContext<'a> {
+    /// A context which writes link files with the given options, and nothing else
+    pub fn new(
+        sourcemap: &'a Sourcemap,
+        files: &'a FileAccess,
+        options: MutateLinkOptions,
+    ) -> Self {
+        Self {
+            sourcemap,
+            files,
+            declarations: DeclarationCache::default(),
+            options,
+            dry_run: false,
+            diff: false,
+            clean: false,
+            workspace_edits: None,
+            declarations_dir: None,
+            lockfile: None,
+            repair_links: false,
+            diagnostics: Diagnostics::default(),
+            output_style: OutputStyle::default(),
+            type_overrides: BTreeMap::new(),
+            root_maps: Vec::new(),
+            parse_timeout: None,
         }
     }
+}
 
-    Ok(success)
+enum MutateResult {
+    Successful {
+        target: PathBuf,
+        exported_types: Option<Vec<ReExportedType>>,
+        diff: Option<String>,
+    },
+    FailedToParseReturnStmt(String),
+    /// The link points to a file which is not a Luau module, such as a `.rbxm` model, so was left untouched
+    UnsupportedTarget,
+    /// The file is a module rather than a link, so was left untouched
+    NotALink,
+    /// A generated link file was restored to a plain link
+    Cleaned {
+        diff: Option<String>,
+    },
+    /// The link file was not generated, so there was nothing to restore
+    AlreadyClean,
+    /// The link file was generated by a previous run, and regenerating it made no changes
+    UpToDate {
+        target: PathBuf,
+        exported_types: Vec<ReExportedType>,
+    },
 }
 
-impl Command {
-    pub fn run(&self) -> Result<()> {
-        let sourcemap_contents =
-            std::fs::read_to_string(&self.sourcemap).context("Failed to read sourcemap file")?;
-        let mut sourcemap: SourcemapNode =
-            serde_json::from_str(&sourcemap_contents).context("Failed to parse sourcemap file")?;
+/// Checks that a root link's require points into the `_Index` folder of a version locked in wally.lock.
+/// Returns the folder it currently points to, and the folder it should point to if it can be determined
+fn find_version_mismatch(
+    require_path: &RequirePath,
+    lockfile: &Lockfile,
+) -> Option<(String, Option<String>)> {
+    let components = match require_path {
+        RequirePath::Instance(components) => components.clone(),
+        RequirePath::String(require) => require.split('/').map(String::from).collect(),
+    };
 
-        // Mutate the sourcemap so that all file paths are canonicalized for simplicity
-        // And that they contain pointers to their parent
-        mutate_sourcemap(&mut sourcemap)?;
+    let folder_name = components
+        .windows(2)
+        .find(|window| window[0] == "_Index")
+        .map(|window| window[1].clone())?;
+    let (name, version) = parse_index_folder_name(&folder_name)?;
 
-        let mut success = true;
-        for entry in std::fs::read_dir(&self.packages_folder)
-            .context("Failed to read packages folder")?
-            .flatten()
-        {
-            if entry.file_name() == "_Index" {
-                match handle_index_directory(&entry.path(), &sourcemap) {
-                    Ok(index_success) => success &= index_success,
-                    Err(err) => {
-                        error!("{:#}", err);
-                        success = false;
+    if lockfile.is_locked(&name, &version) {
+        return None;
+    }
+
+    let expected = match lockfile.locked_versions(&name).as_slice() {
+        [locked_version] => Some(index_folder_name(&name, locked_version)),
+        _ => None,
+    };
+    Some((folder_name, expected))
+}
+
+/// Points the require argument of a link file at another index folder, leaving the rest of the file,
+/// such as comments mentioning the folder, untouched
+fn repair_require_argument(
+    contents: &str,
+    argument: &Expression,
+    current: &str,
+    expected: &str,
+) -> String {
+    let Some((start, end)) = argument.range() else {
+        return contents.to_string();
+    };
+    let (start, end) = (start.bytes(), end.bytes());
+    format!(
+        "{}{}{}",
+        &contents[..start],
+        contents[start..end].replace(current, expected),
+        &contents[end..]
+    )
+}
+
+fn mutate_thunk(path: &Path, context: &MutationContext) -> Result<MutateResult> {
+    info!("Found link file '{}'", path.display());
+
+    let original_contents = context.files.read_module(path)?;
+    if context.clean {
+        return clean_thunk(path, &original_contents, context);
+    }
+    mutate_thunk_contents(path, &original_contents, &original_contents, context)
+}
+
+/// Restores a link file generated by a previous run back to the plain link it was generated from
+fn clean_thunk(path: &Path, contents: &str, context: &MutationContext) -> Result<MutateResult> {
+    let parsed_code = match full_moon::parse(contents) {
+        Ok(parsed_code) => parsed_code,
+        Err(errors) => bail!(LuauParseError::new(&errors)),
+    };
+
+    if find_link_require(&parsed_code).is_some() {
+        info!("Link file was not generated, leaving unchanged");
+        return Ok(MutateResult::AlreadyClean);
+    }
+    let Some(restored) = restore_link(&parsed_code) else {
+        info!(
+            "'{}' is not a generated link file, leaving it untouched",
+            path.display()
+        );
+        return Ok(MutateResult::NotALink);
+    };
+
+    let new_contents = context.output_style.apply(&restored.to_string(), contents);
+    if let Some(workspace_edits) = &context.workspace_edits {
+        info!("Generated link file found, adding its restoration to the workspace edit");
+        workspace_edits.record(path, contents, &new_contents);
+    } else if context.dry_run {
+        info!("Generated link file found, not restoring it due to --dry-run");
+    } else {
+        info!("Generated link file found, restoring it");
+        context.files.write(path, &new_contents)?;
+    }
+
+    let diff = context
+        .diff
+        .then(|| unified_diff(path, contents, &new_contents));
+    Ok(MutateResult::Cleaned { diff })
+}
+
+/// Mutates a thunk given its contents, which may differ from its original contents on disk if it has been repaired
+fn mutate_thunk_contents(
+    path: &Path,
+    original_contents: &str,
+    contents: &str,
+    context: &MutationContext,
+) -> Result<MutateResult> {
+    // The entry should be a thunk
+    let owned_contents = contents.to_string();
+    let parsed_code = match run_with_timeout(
+        context.parse_timeout,
+        &format!("Parsing '{}'", path.display()),
+        move || full_moon::parse(&owned_contents),
+    )? {
+        Ok(parsed_code) => parsed_code,
+        Err(errors) => bail!(LuauParseError::new(&errors)),
+    };
+
+    let Some(link_require) = find_link_require(&parsed_code) else {
+        // Links generated by a previous run are regenerated from the plain link they were created from,
+        // so that they reflect the current exports of the linked module
+        if let Some(restored) = restore_link(&parsed_code) {
+            info!("Link file was generated by a previous run, regenerating it");
+            return mutate_thunk_contents(path, original_contents, &restored.to_string(), context);
+        }
+        info!(
+            "'{}' is not a link file of the form `return require(...)` or `local module = require(...) return module`, leaving it untouched",
+            path.display()
+        );
+        return Ok(MutateResult::NotALink);
+    };
+
+    let require = match match_require(link_require.expression()) {
+        Ok(require) => require,
+        Err(err) => {
+            context.diagnostics.emit(
+                path,
+                Warning::new(WarningCategory::SkippedThunks, "Malformed link file, could not parse return expression, skipping. Run `wally install` to regenerate link files"),
+            );
+            return Ok(MutateResult::FailedToParseReturnStmt(format!(
+                "Malformed link file, could not parse return expression: {:#}",
+                err
+            )));
+        }
+    };
+
+    if let (Some(lockfile), false) = (&context.lockfile, is_inside_index_folder(path)) {
+        match find_version_mismatch(&require.path, lockfile) {
+            Some((current, Some(expected))) if context.repair_links => {
+                context.diagnostics.emit(
+                path,
+                Warning::new(WarningCategory::VersionMismatches, format!("Link points to '{current}', which is not locked in wally.lock, repairing to point to '{expected}'")),
+            );
+                let repaired_contents =
+                    repair_require_argument(contents, &require.argument, &current, &expected);
+                return mutate_thunk_contents(path, original_contents, &repaired_contents, context);
+            }
+            Some((current, Some(expected))) => {
+                context.diagnostics.emit(
+                path,
+                Warning::new(WarningCategory::VersionMismatches, format!("Link points to '{current}', which is not locked in wally.lock, expected '{expected}'. Run with `--repair-links` to fix, or run `wally install`")),
+            );
+            }
+            Some((current, None)) => {
+                context.diagnostics.emit(
+                path,
+                Warning::new(WarningCategory::VersionMismatches, format!("Link points to '{current}', which is not locked in wally.lock. Run `wally install` to regenerate link files")),
+            );
+            }
+            None => {}
+        }
+    }
+
+    let file_path = match require.path.clone() {
+        RequirePath::Instance(path_components) => {
+            info!(
+                "Require expression converted to path: '{}'",
+                path_components.join("/")
+            );
+
+            let resolved = node_from_components(
+                path,
+                context.sourcemap,
+                context.files,
+                path_components,
+                &context.root_maps,
+            )
+            .map_err(|err| in_require(err, path, &require.argument))
+            .context("Could not convert require expression to file path")?;
+            match resolved {
+                Resolved::File(file_path) => file_path,
+                Resolved::Node(id) => {
+                    let file_path = node_file_path(context.sourcemap, id, context.files)
+                        .context("Could not convert require expression to file path")?;
+                    if let (None, Some(non_luau)) =
+                        (&file_path, non_luau_file_path(context.sourcemap.node(id)))
+                    {
+                        context.diagnostics.emit(
+                        path,
+                        Warning::new(
+                            WarningCategory::SkippedThunks,
+                            format!(
+                                "Link points to '{}', which is not a Luau module, so its types cannot be extracted. Leaving it untouched",
+                                non_luau.display()
+                            ),
+                        ),
+                    );
+                        return Ok(MutateResult::UnsupportedTarget);
                     }
+                    file_path
+                        .ok_or_else(|| {
+                            in_require(
+                                no_module_file(context.sourcemap, id).into(),
+                                path,
+                                &require.argument,
+                            )
+                        })
+                        .context("Could not convert require expression to file path")?
                 }
-                continue;
             }
+        }
+        RequirePath::String(require) => {
+            info!("Found string require '{require}'");
 
-            success &= handled_mutate_thunk(&entry.path(), &sourcemap)
+            let file_path = resolve_string_require(path, &require, context.files)
+                .context("Could not resolve string require to file path")?;
+            info!("Link require points to '{}'", file_path.display());
+            file_path
         }
+    };
+    let (file_path, types_require) = if require.members.is_empty() {
+        (file_path, None)
+    } else {
+        let (file_path, types_require) = resolve_member_access(path, &require, file_path, context)
+            .context("Could not resolve member accessed on required module")?;
+        (file_path, Some(types_require))
+    };
+    context.files.record_resolution(path, &file_path);
 
-        if success {
-            Ok(())
-        } else {
-            bail!("Mutation did not complete successfully");
+    let followed = if context.options.follow_re_exports {
+        follow_re_exports(&file_path, context).unwrap_or_else(|err| {
+            info!("Could not follow re-exported types, re-exporting them from the linked module: {err:#}");
+            None
+        })
+    } else {
+        None
+    };
+    let (type_declarations, types_require) = match followed {
+        Some((source_path, type_names)) => {
+            info!(
+                "Linked module only re-exports types, re-exporting them from '{}' instead",
+                source_path.display()
+            );
+            let type_declarations = linked_type_declarations(&source_path, context)?
+                .iter()
+                .filter(|stmt| {
+                    type_names.contains(&stmt.type_declaration().type_name().token().to_string())
+                })
+                .cloned()
+                .collect();
+            let types_require = require_from_link(path, &require.path, &source_path, context)
+                .context("Could not require the module re-exported types are declared in")?;
+            (Arc::new(type_declarations), Some(types_require))
+        }
+        None => (
+            linked_type_declarations(&file_path, context)?,
+            types_require,
+        ),
+    };
+    let namespace = context
+        .options
+        .namespace_types
+        .then(|| path.file_stem())
+        .flatten()
+        .map(|stem| stem.to_string_lossy())
+        .filter(|stem| {
+            let valid = is_identifier(stem);
+            if !valid {
+                info!("'{stem}' is not a valid type name, not creating a namespace type");
+            }
+            valid
+        });
+    let body = match link_require {
+        LinkRequire::Returned(r#return) => LinkBody::Return(r#return.returns().clone()),
+        LinkRequire::Local(local) => {
+            info!(
+                "Link file assigns its require to '{}', keeping its statements",
+                local.local_name
+            );
+            LinkBody::Local {
+                require: Box::new(local.expression.clone()),
+                local_name: local.local_name,
+            }
         }
+    };
+    let options = MutateLinkOptions {
+        type_overrides: type_overrides(&file_path, context),
+        ..context.options.clone()
+    };
+    let new_link_contents = mutate_link(
+        parsed_code,
+        body,
+        types_require,
+        &type_declarations,
+        namespace.as_deref(),
+        &options,
+    )
+    .context("Failed to create new link contents")?;
+
+    let (exported_types, diff) = match new_link_contents {
+        MutateLinkResult::Changed {
+            ast,
+            exported_types,
+            warnings,
+        } => {
+            for warning in warnings {
+                context.diagnostics.emit(path, warning);
+            }
+
+            let new_contents = context
+                .output_style
+                .apply(&ast.to_string(), original_contents);
+            let up_to_date = new_contents == original_contents;
+            if up_to_date {
+                info!("Link file already re-exports the current types, leaving unchanged");
+            } else if let Some(workspace_edits) = &context.workspace_edits {
+                info!("Exported types found, adding new linker file to the workspace edit");
+                workspace_edits.record(path, original_contents, &new_contents);
+            } else if context.dry_run {
+                info!("Exported types found, not writing new linker file due to --dry-run");
+            } else {
+                info!("Exported types found, writing new linker file");
+                context.files.write(path, &new_contents)?;
+            }
+
+            if let (Some(declarations_dir), false) =
+                (&context.declarations_dir, is_inside_index_folder(path))
+            {
+                write_declaration_stub(path, &file_path, &ast, declarations_dir, context)?;
+            }
+
+            if up_to_date {
+                return Ok(MutateResult::UpToDate {
+                    target: file_path,
+                    exported_types,
+                });
+            }
+
+            let diff = context
+                .diff
+                .then(|| unified_diff(path, original_contents, &new_contents));
+            (Some(exported_types), diff)
+        }
+        MutateLinkResult::Unchanged if contents != original_contents => {
+            if let Some(workspace_edits) = &context.workspace_edits {
+                info!("No exported types, adding repaired linker file to the workspace edit");
+                workspace_edits.record(path, original_contents, contents);
+            } else if context.dry_run {
+                info!("No exported types, not writing repaired linker file due to --dry-run");
+            } else {
+                info!("No exported types, writing repaired linker file");
+                context.files.write(path, contents)?;
+            }
+
+            let diff = context
+                .diff
+                .then(|| unified_diff(path, original_contents, contents));
+            // The link file still changed, e.g. a generated link whose module no longer exports any types
+            (Some(Vec::new()), diff)
+        }
+        MutateLinkResult::Unchanged => {
+            info!("No exported types, leaving unchanged");
+            (None, None)
+        }
+    };
+
+    Ok(MutateResult::Successful {
+        target: file_path,
+        exported_types,
+        diff,
+    })
+}
+
+/// The type overrides configured for the package a linked module belongs to
+fn type_overrides(file_path: &Path, context: &MutationContext) -> TypeOverrides {
+    let Some(folder) = containing_index_folder(file_path) else {
+        return TypeOverrides::default();
+    };
+    context
+        .type_overrides
+        .iter()
+        .find(|(package, _)| index_folder_matches(&folder, package))
+        .map(|(_, overrides)| overrides.clone())
+        .unwrap_or_default()
+}
+
+/// Retrieves the exported type declarations of a linked module, falling back to its bundled declaration file
+fn linked_type_declarations(
+    file_path: &Path,
+    context: &MutationContext,
+) -> Result<Arc<Vec<ExportedTypeDeclaration>>> {
+    Ok(declaring_file(file_path, context)?.1)
+}
+
+/// Retrieves the exported type declarations of a linked module alongside the file they were declared in, which
+/// is its bundled declaration file if the module itself declares no types
+fn declaring_file(
+    file_path: &Path,
+    context: &MutationContext,
+) -> Result<(PathBuf, Arc<Vec<ExportedTypeDeclaration>>)> {
+    let type_declarations =
+        context
+            .declarations
+            .get_or_parse(file_path, context.files, context.parse_timeout)?;
+    if !type_declarations.is_empty() {
+        return Ok((file_path.to_path_buf(), type_declarations));
+    }
+
+    // Some packages only declare their types in a bundled declaration file
+    for declaration_file in declaration_file_paths(context.sourcemap, file_path) {
+        let declarations = context.declarations.get_or_parse(
+            declaration_file,
+            context.files,
+            context.parse_timeout,
+        )?;
+        if !declarations.is_empty() {
+            info!(
+                "No exported types in linked module, using declaration file '{}'",
+                declaration_file.display()
+            );
+            return Ok((declaration_file.clone(), declarations));
+        }
+    }
+    Ok((file_path.to_path_buf(), type_declarations))
+}
+
+/// The file and line each type re-exported from a linked module is declared on, keyed by the name it is
+/// re-exported as
+fn type_locations(
+    file_path: &Path,
+    context: &MutationContext,
+) -> Result<BTreeMap<String, (PathBuf, usize)>> {
+    let followed = match context.options.follow_re_exports {
+        true => follow_re_exports(file_path, context).unwrap_or(None),
+        false => None,
+    };
+    let source_path =
+        followed.map_or_else(|| file_path.to_path_buf(), |(source_path, _)| source_path);
+    let (declared_in, type_declarations) = declaring_file(&source_path, context)?;
+    let overrides = type_overrides(file_path, context);
+
+    Ok(type_declarations
+        .iter()
+        .map(|stmt| {
+            let name = stmt.type_declaration().type_name().token().to_string();
+            let line = stmt.export_token().token().start_position().line();
+            let name = overrides.rename.get(&name).cloned().unwrap_or(name);
+            (name, (declared_in.clone(), line))
+        })
+        .collect())
+}
+
+/// Writes a `.d.luau` stub for a root link into the declarations directory, named after the link
+fn write_declaration_stub(
+    path: &Path,
+    target: &Path,
+    generated: &Ast,
+    declarations_dir: &Path,
+    context: &MutationContext,
+) -> Result<()> {
+    let require = relative_string_require(declarations_dir, target).with_context(|| {
+        format!(
+            "Could not require '{}' from the declarations directory",
+            target.display()
+        )
+    })?;
+    let stub =
+        declaration_stub(generated, &require).context("Failed to create declaration stub")?;
+    let name = path.file_stem().context("Link file has no name")?;
+    let stub_path = declarations_dir.join(format!("{}.d.luau", name.to_string_lossy()));
+
+    info!("Writing declaration stub '{}'", stub_path.display());
+    context.files.write(&stub_path, &stub.to_string())?;
+    Ok(())
+}
+
+/// Creates a unified diff between the old and new contents of a link file
+fn unified_diff(path: &Path, old: &str, new: &str) -> String {
+    let path = path.display().to_string();
+    similar::TextDiff::from_lines(old, new)
+        .unified_diff()
+        .header(&path, &path)
+        .to_string()
+}
+
+// Mutate thunk with error handled, to allow continuing
+pub fn handled_mutate_thunk(path: &Path, context: &MutationContext) -> ThunkReport {
+    let (target, status, diff) = match mutate_thunk(path, context) {
+        Ok(MutateResult::Successful {
+            target,
+            exported_types: Some(exported_types),
+            diff,
+        }) => (Some(target), ThunkStatus::Mutated { exported_types }, diff),
+        Ok(MutateResult::Successful {
+            target,
+            exported_types: None,
+            diff,
+        }) => (Some(target), ThunkStatus::Unchanged, diff),
+        Ok(MutateResult::FailedToParseReturnStmt(reason)) => {
+            (None, ThunkStatus::Skipped { reason }, None)
+        }
+        Ok(MutateResult::NotALink) => (None, ThunkStatus::NotALink, None),
+        Ok(MutateResult::Cleaned { diff }) => (None, ThunkStatus::Cleaned, diff),
+        Ok(MutateResult::AlreadyClean | MutateResult::UnsupportedTarget) => {
+            (None, ThunkStatus::Unchanged, None)
+        }
+        Ok(MutateResult::UpToDate {
+            target,
+            exported_types,
+        }) => (Some(target), ThunkStatus::UpToDate { exported_types }, None),
+        Err(err) => (
+            None,
+            ThunkStatus::Failed {
+                error: format!("{:#}", err),
+                class: FailureClass::of(&err),
+            },
+            None,
+        ),
+    };
+
+    ThunkReport {
+        path: path.to_path_buf(),
+        target,
+        status,
+        diff,
+    }
+}
+
+/// Rewrites every link file in the packages folders, reading and writing through the given filesystem rather
+/// than the real one, e.g. to generate link files held in memory by an editor extension. Link files are mutated one
+/// at a time, and written as they are mutated
+pub fn mutate_file_system(
+    file_system: impl FileSystem + 'static,
+    sourcemap_path: &Path,
+    packages_folders: &[PathBuf],
+    options: MutateLinkOptions,
+) -> Result<Report> {
+    let files = FileAccess::new(1).with_file_system(file_system);
+
+    let sourcemap_contents = files
+        .read_to_string(sourcemap_path)
+        .context(ClassifiedError::new(
+            FailureClass::Sourcemap,
+            "Failed to read sourcemap file",
+        ))?;
+    let sourcemap: SourcemapNode = serde_json::from_str(&sourcemap_contents).context(
+        ClassifiedError::new(FailureClass::Sourcemap, "Failed to parse sourcemap file"),
+    )?;
+    let sourcemap = mutate_sourcemap(sourcemap, &files).context(ClassifiedError::new(
+        FailureClass::Sourcemap,
+        "Failed to read files in sourcemap",
+    ))?;
+
+    let mut report = Report::default();
+    let packages_folders = expand_packages_folders(packages_folders, &files)?;
+    let thunks = discover_thunks(&packages_folders, &files, &mut report)?;
+
+    let context = MutationContext::new(&sourcemap, &files, options);
+    report.thunks.extend(
+        thunks
+            .iter()
+            .map(|thunk| handled_mutate_thunk(thunk, &context)),
+    );
+    report.warnings = context.diagnostics.emitted();
+    Ok(report)
+}
+
+/// Whether a file's contents are a link, i.e. `return require(...)` or a require assigned to a local which is
+/// returned, or a link file generated by a previous run
+fn is_link_source(contents: &str) -> bool {
+    contents.contains("require")
+        && full_moon::parse(contents)
+            .is_ok_and(|ast| find_link_require(&ast).is_some() || restore_link(&ast).is_some())
+}
+
+/// The index folder of the package a link file belongs to. For a link at the root of a packages folder,
+/// this is the index folder of the package it links to, e.g. `sleitnick_signal@1.5.0` for `Signal.lua`
+fn linked_package(path: &Path, files: &FileAccess) -> Option<String> {
+    if let Some(folder) = containing_index_folder(path) {
+        return Some(folder);
+    }
+
+    let contents = files.read_to_string(path).ok()?;
+    let parsed_code = full_moon::parse(&contents).ok()?;
+    let parsed_code = restore_link(&parsed_code).unwrap_or(parsed_code);
+    let RequirePath::Instance(components) =
+        match_require(find_link_require(&parsed_code)?.expression())
+            .ok()?
+            .path
+    else {
+        return None;
+    };
+    containing_index_folder(&components.iter().collect::<PathBuf>())
+}
+
+/// The directory containing the first packages folder, where project files such as `.luaurc` are kept
+fn project_root(packages_folders: &[PathBuf]) -> &Path {
+    match packages_folders.first().and_then(|folder| folder.parent()) {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    }
+}
+
+/// The packages in `_Index` folders a link file belongs to or links into, as pairs of name and version.
+/// Root links only contribute the package they point to
+fn thunk_packages(thunk: &ThunkReport) -> impl Iterator<Item = (String, String)> + '_ {
+    [Some(thunk.path.as_path()), thunk.target.as_deref()]
+        .into_iter()
+        .flatten()
+        .filter_map(containing_index_folder)
+        .filter_map(|folder| parse_index_folder_name(&folder))
+}
+
+/// Lists the packages link files belong to or link into, noting whether each is locked
+fn installed_packages(report: &Report, lockfile: Option<&Lockfile>) -> Vec<PackageReport> {
+    let packages: BTreeSet<_> = report.thunks.iter().flat_map(thunk_packages).collect();
+    packages
+        .into_iter()
+        .map(|(name, version)| PackageReport {
+            locked: lockfile.map(|lockfile| lockfile.is_locked(&name, &version)),
+            name,
+            version,
+        })
+        .collect()
+}
+
+/// Warns about packages installed in an index folder which are not locked, which are left behind by an earlier
+/// install, and about locked packages which are not installed in any packages folder, as after an install
+/// which did not finish
+fn check_lockfile_consistency(
+    lockfile: &Lockfile,
+    lockfile_path: &Path,
+    report: &Report,
+    packages_folders: &[PathBuf],
+    context: &MutationContext,
+) {
+    let mut unlocked = BTreeSet::new();
+    // Root links pointing to unlocked versions are already warned about as version mismatches
+    for thunk in report
+        .thunks
+        .iter()
+        .filter(|thunk| is_inside_index_folder(&thunk.path))
+    {
+        for (name, version) in thunk_packages(thunk) {
+            if !lockfile.is_locked(&name, &version)
+                && unlocked.insert((name.clone(), version.clone()))
+            {
+                context.diagnostics.emit(
+                    &thunk.path,
+                    Warning::new(
+                        WarningCategory::LockfileMismatches,
+                        format!(
+                            "'{name}@{version}' is installed, but is not locked in '{}'. Run `wally install` to \
+                             remove packages left behind by an earlier install",
+                            lockfile_path.display()
+                        ),
+                    ),
+                );
+            }
+        }
+    }
+
+    // Realm specific packages folders may not be processed, so every packages folder next to them is searched
+    let files = context.files;
+    let index_folders: Vec<PathBuf> = files
+        .read_dir(project_root(packages_folders))
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|entry| !entry.is_file)
+        .map(|entry| entry.path)
+        .chain(packages_folders.iter().cloned())
+        .map(|packages_folder| packages_folder.join("_Index"))
+        .filter(|index_folder| files.path_kind(index_folder) == PathKind::Directory)
+        .collect();
+    for package in lockfile.dependencies() {
+        let folder_name = index_folder_name(&package.name, &package.version);
+        let installed = index_folders.iter().any(|index_folder| {
+            files.path_kind(&index_folder.join(&folder_name)) == PathKind::Directory
+        });
+        if !installed {
+            // Attributed to where wally installs the package, so it is grouped with the package's other problems
+            context.diagnostics.emit(
+                &project_root(packages_folders)
+                    .join("Packages")
+                    .join("_Index")
+                    .join(&folder_name),
+                Warning::new(
+                    WarningCategory::LockfileMismatches,
+                    format!(
+                        "'{}@{}' is locked, but is not installed in any packages folder. Run `wally install` \
+                         to finish installing your packages",
+                        package.name, package.version
+                    ),
+                ),
+            );
+        }
+    }
+}
+
+/// The class of failure most of the failed link files failed with, which the run exits with
+fn most_common_failure_class(failures: &[&ThunkReport]) -> FailureClass {
+    let mut counts: BTreeMap<FailureClass, usize> = BTreeMap::new();
+    for class in failures.iter().filter_map(|thunk| thunk.failure_class()) {
+        *counts.entry(class).or_default() += 1;
+    }
+    // `max_by_key` returns the last maximum, so classes are reversed to prefer the first on a tie
+    counts
+        .into_iter()
+        .rev()
+        .max_by_key(|(_, count)| *count)
+        .map_or(FailureClass::Other, |(class, _)| class)
+}
+
+/// The formats `--report` can write
+const REPORT_FORMATS: &[&str] = &["html", "strictness"];
+
+/// The realm specific packages folders wally installs alongside the shared `Packages` folder
+const REALM_PACKAGES_FOLDERS: &[&str] = &["ServerPackages", "DevPackages"];
+
+/// Whether a file name matches a glob pattern, where `*` matches any run of characters and `?` any single one
+fn matches_wildcard(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let (mut p, mut n) = (0, 0);
+    // Where to resume from when a mismatch occurs after the last `*`
+    let mut backtrack = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, n));
+                p += 1;
+            }
+            Some(&char) if char == '?' || char == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((star, matched)) => {
+                    p = star + 1;
+                    n = matched + 1;
+                    backtrack = Some((star, matched + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|char| *char == '*')
+}
+
+/// Expands a packages folder containing glob patterns (e.g. `Packages*` or `places/*/Packages`) to every matching
+/// directory. Folders without patterns are returned as is
+fn expand_glob(folder: &Path, files: &FileAccess) -> Result<Vec<PathBuf>> {
+    let is_pattern = |component: &str| component.contains(['*', '?']);
+    if !folder
+        .components()
+        .any(|component| is_pattern(&component.as_os_str().to_string_lossy()))
+    {
+        return Ok(vec![folder.to_path_buf()]);
+    }
+
+    let mut matches = vec![PathBuf::new()];
+    for component in folder.components() {
+        let name = component.as_os_str().to_string_lossy();
+        if !is_pattern(&name) {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        }
+
+        let mut expanded = Vec::new();
+        for path in matches {
+            let directory = if path.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                path.as_path()
+            };
+            let entries = files
+                .read_dir(directory)
+                .with_context(|| format!("Failed to read '{}'", directory.display()))?;
+            expanded.extend(
+                entries
+                    .into_iter()
+                    .filter(|entry| !entry.is_file)
+                    .filter_map(|entry| {
+                        let entry_name = entry.path.file_name()?.to_string_lossy().to_string();
+                        matches_wildcard(&name, &entry_name).then(|| path.join(entry_name))
+                    }),
+            );
+        }
+        matches = expanded;
+    }
+
+    // Components after a pattern are only joined on, so may not exist
+    matches.retain(|path| files.path_kind(path) == PathKind::Directory);
+    if matches.is_empty() {
+        bail!("No packages folders match '{}'", folder.display());
+    }
+    Ok(matches)
+}
+
+/// Expands packages folders containing glob patterns (e.g. `Packages*` or `places/*/Packages`) to all matching
+/// directories, and adds the shared `Packages` folder when only realm specific folders are given, as their links
+/// may point into it. The folders are sorted, so that every realm is processed in one deterministic pass
+fn expand_packages_folders(folders: &[PathBuf], files: &FileAccess) -> Result<Vec<PathBuf>> {
+    let mut expanded = Vec::new();
+    for folder in folders {
+        expanded.extend(expand_glob(folder, files)?);
+    }
+
+    for folder in expanded.clone() {
+        let is_realm_folder = folder
+            .file_name()
+            .is_some_and(|name| REALM_PACKAGES_FOLDERS.iter().any(|realm| name == *realm));
+        let shared = folder.with_file_name("Packages");
+        if is_realm_folder
+            && !expanded.contains(&shared)
+            && files.path_kind(&shared) == PathKind::Directory
+        {
+            info!(
+                "Also processing the shared packages folder '{}', which '{}' may link into",
+                shared.display(),
+                folder.display()
+            );
+            expanded.push(shared);
+        }
+    }
+
+    expanded.sort();
+    expanded.dedup();
+    Ok(expanded)
+}
+
+/// Finds the link files in each packages folder, including those of packages in its index folder and any nested
+/// inside of them. Index folders which cannot be read are reported as failures
+pub(crate) fn discover_thunks(
+    packages_folders: &[PathBuf],
+    files: &FileAccess,
+    report: &mut Report,
+) -> Result<Vec<PathBuf>> {
+    let mut thunks = Vec::new();
+    for packages_folder in packages_folders {
+        match files.path_kind(packages_folder) {
+            PathKind::Missing => bail!(
+                "Packages folder '{}' does not exist. Run `wally install` to install your packages",
+                packages_folder.display()
+            ),
+            PathKind::File => {
+                info!(
+                "'{}' is a file rather than a packages folder, treating it as a single link file",
+                packages_folder.display()
+            );
+                thunks.push(packages_folder.to_path_buf());
+            }
+            PathKind::Directory => {
+                for entry in files
+                    .read_dir(packages_folder)
+                    .context("Failed to read packages folder")?
+                {
+                    if entry.path.file_name().is_some_and(is_index_folder) {
+                        match collect_index_thunks(&entry.path, files) {
+                            Ok(index_thunks) => thunks.extend(index_thunks),
+                            Err(err) => {
+                                error!("{:#}", err);
+                                report.thunks.push(ThunkReport {
+                                    path: entry.path,
+                                    target: None,
+                                    diff: None,
+                                    status: ThunkStatus::Failed {
+                                        error: format!("Failed to read index directory: {:#}", err),
+                                        class: FailureClass::Filesystem,
+                                    },
+                                });
+                            }
+                        }
+                        continue;
+                    }
+
+                    if entry.is_file {
+                        thunks.push(entry.path);
+                    }
+                }
+            }
+        }
+    }
+    Ok(thunks)
+}
+
+/// The folders packages are installed into inside of an index directory, e.g. `_Index/sleitnick_signal@1.5.0`
+fn index_package_folders(path: &Path, files: &FileAccess) -> Result<Vec<PathBuf>> {
+    let mut folders = vec![path.to_path_buf()];
+    for _ in 0..package_folder_depth(path.file_name().unwrap_or_default()) {
+        let mut children = Vec::new();
+        for folder in folders {
+            children.extend(
+                files
+                    .read_dir(&folder)?
+                    .into_iter()
+                    .filter(|entry| !entry.is_file)
+                    .map(|entry| entry.path),
+            );
+        }
+        folders = children;
+    }
+    Ok(folders)
+}
+
+/// Collects all the thunks found inside of an index directory, such as wally's `_Index` or pesde's `.pesde`.
+/// Link files sit directly inside of each package's folder, next to any nested index directory holding the links
+/// of conflicting transitive versions, which is collected from in the same way. The packages' own modules are not
+/// walked, as they may have the shape of a link too
+fn collect_index_thunks(path: &Path, files: &FileAccess) -> Result<Vec<PathBuf>> {
+    let mut candidates = Vec::new();
+    let mut package_folders = index_package_folders(path, files)?
+        .into_iter()
+        .map(|folder| (folder, false))
+        .collect::<Vec<_>>();
+
+    while let Some((package_folder, nested)) = package_folders.pop() {
+        let entries = match files.read_dir(&package_folder) {
+            Ok(entries) => entries,
+            Err(err) => {
+                warn!("Failed to read '{}': {err}", package_folder.display());
+                continue;
+            }
+        };
+
+        for entry in entries {
+            if entry.is_file {
+                if lua_files_filter(&&entry.path) {
+                    candidates.push((entry.path, nested));
+                }
+            } else if entry.path.file_name().is_some_and(is_index_folder) {
+                match index_package_folders(&entry.path, files) {
+                    Ok(folders) => {
+                        package_folders.extend(folders.into_iter().map(|folder| (folder, true)))
+                    }
+                    Err(err) => warn!("Failed to read '{}': {err}", entry.path.display()),
+                }
+            }
+        }
+    }
+
+    Ok(candidates
+        .into_par_iter()
+        .filter(
+            |(candidate, nested)| match files.read_to_string(candidate) {
+                Ok(contents) => is_link_source(&contents),
+                // Unreadable files directly inside of a package are reported when mutating them, but those inside of
+                // nested index directories may not be links at all
+                Err(err) if *nested => {
+                    warn!("Failed to read '{}': {err}", candidate.display());
+                    false
+                }
+                Err(_) => true,
+            },
+        )
+        .map(|(candidate, _)| candidate)
+        .collect())
+}
+
+impl Command {
+    pub fn run(self) -> Result<()> {
+        if self.buildinfo {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&BuildInfo::current())
+                    .context("Failed to serialize build info")?
+            );
+            return Ok(());
+        }
+
+        let (mode, mut options) = match self.subcommand {
+            None => (Mode::Fix, self.options),
+            Some(Operation::Fix(options)) => (Mode::Fix, options),
+            Some(Operation::Check(options)) => (Mode::Check, options),
+            Some(Operation::Clean(options)) => (Mode::Clean, options),
+            Some(Operation::List(options)) => (Mode::List, options),
+            Some(Operation::Types(TypesQuery { name, mut options })) => {
+                // The package is resolved through its root link, which --package selects
+                options.package = vec![name];
+                (Mode::Types, options)
+            }
+            Some(Operation::Rollback(rollback)) => return rollback.run(),
+            Some(Operation::Debug(DebugOperation::DiffSourcemaps(diff))) => return diff.run(),
+        };
+        options.mode = mode;
+        if let Some(config) = Config::discover(Path::new(""))? {
+            options.apply_config(config);
+        }
+        let result = options.run();
+        if let (Err(err), ErrorFormat::Json) = (&result, options.error_format.unwrap_or_default()) {
+            eprintln!(
+                "{}",
+                json_error(FailureClass::of(err), None, &format!("{err:#}"))
+            );
+        }
+        result
+    }
+}
+
+impl Options {
+    /// Fills in any options not given on the command line from the configuration
+    fn apply_config(&mut self, config: Config) {
+        self.sourcemap = self.sourcemap.take().or(config.sourcemap);
+        self.generate_sourcemap |= config.generate_sourcemap;
+        if self.sourcemap_command == DEFAULT_SOURCEMAP_COMMAND {
+            if let Some(sourcemap_command) = config.sourcemap_command {
+                self.sourcemap_command = sourcemap_command;
+            }
+        }
+        self.types = config.types;
+        if self.packages_folders.is_empty() {
+            self.packages_folders = config.packages;
+        }
+        if self.skip.is_empty() {
+            self.skip = config.skip;
+        }
+        if self.root_map.is_empty() {
+            self.root_map = config.root_map;
+        }
+        self.lockfile = self.lockfile.take().or(config.lockfile);
+        self.line_endings = self.line_endings.or(config.line_endings);
+        self.stylua_config = self.stylua_config.take().or(config.stylua_config);
+        self.output_format = self.output_format.or(config.output_format);
+        self.error_format = self.error_format.or(config.error_format);
+        self.diff |= config.diff;
+        self.backup |= config.backup;
+        self.no_canonicalize |= config.no_canonicalize;
+        self.summary_only |= config.summary_only;
+        self.quiet |= config.quiet;
+        self.shadowed_builtins = self.shadowed_builtins.or(config.shadowed_builtins);
+        self.namespace_types |= config.namespace_types;
+        self.luaurc |= config.luaurc;
+        self.follow_re_exports |= config.follow_re_exports;
+        self.incremental |= config.incremental;
+        self.fail_on_warning |= config.fail_on_warning;
+        if self.deny.is_empty() {
+            self.deny = config.deny;
+        }
+        if self.allow.is_empty() {
+            self.allow = config.allow;
+        }
+        self.max_warnings = self.max_warnings.or(config.max_warnings);
+    }
+
+    /// Whether a link file matches any of the given names, either by its own name, a folder it is inside of,
+    /// or the package it belongs to
+    fn matches_any(path: &Path, names: &[String], files: &FileAccess) -> bool {
+        if names.is_empty() {
+            return false;
+        }
+        let file_stem = path.file_stem().map(|stem| stem.to_string_lossy());
+        let package = linked_package(path, files);
+        names.iter().any(|name| {
+            file_stem.as_deref() == Some(name.as_str())
+                || path
+                    .components()
+                    .any(|component| component.as_os_str().to_string_lossy() == *name)
+                || package
+                    .as_deref()
+                    .is_some_and(|package| index_folder_matches(package, name))
+        })
+    }
+
+    pub fn run(&self) -> Result<()> {
+        let start = Instant::now();
+        if self.summary_only || self.error_format == Some(ErrorFormat::Json) {
+            log::set_max_level(LevelFilter::Off);
+        } else if self.quiet {
+            log::set_max_level(LevelFilter::Warn);
+        }
+
+        if let Some(threads) = self.threads {
+            rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build_global()
+                .context("Failed to initialise thread pool")?;
+        }
+
+        let mut files = FileAccess::new(self.max_open_files);
+        if self.max_file_size > 0 {
+            files = files.with_max_file_size(self.max_file_size * 1024 * 1024);
+        }
+        if self.no_canonicalize {
+            files = files.without_canonicalizing();
+        }
+        // Kept until the end of the run, as the generated sourcemap is removed once dropped
+        let mut generated_sourcemap = None;
+        let (files, sourcemap_path, packages_folders) = match &self.replay {
+            Some(replay) => {
+                let trace = Trace::load(replay)?;
+                info!("Replaying trace '{}'", replay.display());
+                let build_info = BuildInfo::current();
+                match &trace.build_info {
+                    Some(recorded) if *recorded != build_info => warn!(
+                        "Trace was recorded by wally-package-types {}, but is being replayed by {}",
+                        recorded.summary(),
+                        build_info.summary()
+                    ),
+                    Some(_) => {}
+                    None => {
+                        warn!("Trace was recorded by an unknown version of wally-package-types")
+                    }
+                }
+                let sourcemap_path = trace.sourcemap.clone();
+                let packages_folders = trace.packages_folders.clone();
+                (files.replaying(trace), sourcemap_path, packages_folders)
+            }
+            None => {
+                if self.packages_folders.is_empty() {
+                    bail!("No packages folder provided, either as an argument or in {CONFIG_FILE_NAME}");
+                }
+                let sourcemap_path = match self.generate_sourcemap {
+                    true => generated_sourcemap
+                        .insert(GeneratedSourcemap::generate(&self.sourcemap_command).context(
+                            ClassifiedError::new(
+                                FailureClass::Sourcemap,
+                                "Failed to generate sourcemap",
+                            ),
+                        )?)
+                        .path()
+                        .to_path_buf(),
+                    false => self.sourcemap.clone().with_context(|| {
+                        ClassifiedError::new(
+                            FailureClass::Sourcemap,
+                            format!(
+                                "No sourcemap provided, either as an argument or in {CONFIG_FILE_NAME}. \
+                                 Pass --generate-sourcemap to generate one"
+                            ),
+                        )
+                    })?,
+                };
+                let packages_folders = self.packages_folders.clone();
+                let files = match &self.record {
+                    Some(_) => files.recording(Trace {
+                        sourcemap: sourcemap_path.clone(),
+                        packages_folders: packages_folders.clone(),
+                        build_info: Some(BuildInfo::current()),
+                        ..Default::default()
+                    }),
+                    // The new contents are printed once the link file has been mutated
+                    None if self.stdout => files.capturing_writes(),
+                    None => files,
+                };
+                // Writes are committed together once every link file has been mutated
+                let files = match self.stdout {
+                    true => files,
+                    false => files.staging(),
+                };
+                (files, sourcemap_path, packages_folders)
+            }
+        };
+
+        let result = self.mutate(&files, &sourcemap_path, &packages_folders, start);
+
+        if let (Some(record), Some(trace)) = (&self.record, files.recorded_trace()) {
+            trace.save(record)?;
+            info!("Recorded trace to '{}'", record.display());
+        }
+
+        result
+    }
+
+    /// Validates the `--report` arguments, returning the path the report of the given format should be written to
+    fn report_path(&self, format: &str) -> Result<Option<&Path>> {
+        let mut path = None;
+        for report in self.report.chunks(2) {
+            match report {
+                [report_format, report_path]
+                    if REPORT_FORMATS.contains(&report_format.as_str()) =>
+                {
+                    if report_format == format {
+                        path = Some(Path::new(report_path));
+                    }
+                }
+                [report_format, _] => bail!(
+                    "Unsupported report format '{report_format}', expected 'html' or 'strictness'"
+                ),
+                _ => bail!("--report expects a format and a path"),
+            }
+        }
+        Ok(path)
+    }
+
+    /// Prints the types exported by the queried package, read through the root links which resolve into its
+    /// index folder, each with the file and line it is declared on
+    fn print_types(&self, report: &Report, context: &MutationContext) -> Result<()> {
+        let current_dir = std::env::current_dir().unwrap_or_default();
+        let mut printed = HashSet::new();
+        for thunk in &report.thunks {
+            let Some(target) = &thunk.target else {
+                continue;
+            };
+            let queried = containing_index_folder(target).filter(|folder| {
+                self.package
+                    .iter()
+                    .any(|package| index_folder_matches(folder, package))
+            });
+            let Some(folder) = queried else {
+                continue;
+            };
+            if is_inside_index_folder(&thunk.path) || !printed.insert(target.clone()) {
+                continue;
+            }
+
+            let exported_types = match &thunk.status {
+                ThunkStatus::Mutated { exported_types }
+                | ThunkStatus::UpToDate { exported_types } => exported_types.as_slice(),
+                _ => &[],
+            };
+            let locations = type_locations(target, context)?;
+            println!("{folder} ({})", thunk.path.display());
+            for exported_type in exported_types {
+                match locations.get(&exported_type.name) {
+                    Some((file, line)) => println!(
+                        "  {}  {}:{line}",
+                        exported_type.signature,
+                        file.strip_prefix(&current_dir).unwrap_or(file).display()
+                    ),
+                    None => println!("  {}", exported_type.signature),
+                }
+            }
+        }
+
+        // Link files which failed to resolve are reported with the other failures instead
+        if printed.is_empty() && report.failures().next().is_none() {
+            bail!(ClassifiedError::new(
+                FailureClass::Resolution,
+                format!(
+                    "No link file in the packages folders resolves to package '{}'",
+                    self.package.join("', '")
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Prints the failures and warnings of the run to stderr, grouped by severity and package,
+    /// followed by suggested next steps if any link files failed
+    fn print_problems(&self, report: &Report) {
+        if self.error_format == Some(ErrorFormat::Json) {
+            for thunk in report.failures() {
+                eprintln!(
+                    "{}",
+                    json_error(
+                        thunk.failure_class().unwrap_or(FailureClass::Other),
+                        Some(&thunk.path),
+                        thunk.failure_reason().unwrap_or_default()
+                    )
+                );
+            }
+        } else if log::max_level() >= LevelFilter::Warn {
+            eprint!("{}", summary::render(report, self.verbose));
+            eprint!("{}", suggestions::render(report));
+        }
+    }
+
+    /// Adds an alias for each package to the `.luaurc` in the directory containing the packages folder
+    fn write_luaurc_aliases(
+        &self,
+        files: &FileAccess,
+        packages_folders: &[PathBuf],
+        report: &Report,
+        dry_run: bool,
+    ) -> Result<()> {
+        let project_root = project_root(packages_folders);
+        let luaurc_path = project_root.join(".luaurc");
+        let canonical_root = files
+            .canonicalize(project_root)
+            .context("Failed to find project root")?;
+
+        let aliases = package_aliases(report, &canonical_root);
+        let contents = match files.path_kind(&luaurc_path) {
+            PathKind::File => Some(
+                files
+                    .read_to_string(&luaurc_path)
+                    .context("Failed to read .luaurc")?,
+            ),
+            _ => None,
+        };
+        let new_contents = update_luaurc(contents.as_deref(), &aliases)?;
+        if contents.as_deref() == Some(new_contents.as_str()) {
+            info!(
+                "'{}' already has aliases for all packages",
+                luaurc_path.display()
+            );
+        } else if dry_run {
+            info!(
+                "Not writing {} package alias(es) to '{}' due to --dry-run",
+                aliases.len(),
+                luaurc_path.display()
+            );
+        } else {
+            info!(
+                "Writing {} package alias(es) to '{}'",
+                aliases.len(),
+                luaurc_path.display()
+            );
+            files
+                .write(&luaurc_path, &new_contents)
+                .context("Failed to write .luaurc")?;
+        }
+        Ok(())
+    }
+
+    fn mutate(
+        &self,
+        files: &FileAccess,
+        sourcemap_path: &Path,
+        packages_folders: &[PathBuf],
+        start: Instant,
+    ) -> Result<()> {
+        let html_report_path = self.report_path("html")?;
+        let strictness_report_path = self.report_path("strictness")?;
+        if self.stdout
+            && !matches!(packages_folders, [link] if files.path_kind(link) == PathKind::File)
+        {
+            bail!("--stdout expects the path to a single link file");
+        }
+        // Checking and listing only need to know what would change
+        let dry_run = self.dry_run || matches!(self.mode, Mode::Check | Mode::List | Mode::Types);
+        let mut timings = Timings::new(self.timings);
+
+        let sourcemap_contents = if sourcemap_path == Path::new(STDIN_PATH) {
+            files.read_stdin().context(ClassifiedError::new(
+                FailureClass::Sourcemap,
+                "Failed to read sourcemap from stdin",
+            ))?
+        } else {
+            files
+                .read_to_string(sourcemap_path)
+                .context(ClassifiedError::new(
+                    FailureClass::Sourcemap,
+                    "Failed to read sourcemap file",
+                ))?
+        };
+        let sourcemap: SourcemapNode = serde_json::from_str(&sourcemap_contents).context(
+            ClassifiedError::new(FailureClass::Sourcemap, "Failed to parse sourcemap file"),
+        )?;
+        timings.phase("read sourcemap");
+
+        // Mutate the sourcemap so that all file paths are canonicalized for simplicity
+        // And that they contain pointers to their parent
+        let sourcemap = mutate_sourcemap(sourcemap, files).context(ClassifiedError::new(
+            FailureClass::Sourcemap,
+            "Failed to read files in sourcemap",
+        ))?;
+        timings.phase("canonicalize sourcemap");
+
+        let mut report = Report {
+            // Emitting a workspace edit leaves writing the changes to the editor
+            dry_run: dry_run || self.emit_workspace_edit.is_some(),
+            build_info: Some(BuildInfo::current()),
+            ..Default::default()
+        };
+        let packages_folders = expand_packages_folders(packages_folders, files)?;
+        let mut thunks = discover_thunks(&packages_folders, files, &mut report)?;
+        thunks.retain(|thunk| {
+            if !self.package.is_empty() && !Self::matches_any(thunk, &self.package, files) {
+                info!(
+                    "Skipping '{}' as it is not selected by --package",
+                    thunk.display()
+                );
+                return false;
+            }
+            let skipped = Self::matches_any(thunk, &self.skip, files);
+            if skipped {
+                info!("Skipping '{}' due to --skip", thunk.display());
+            }
+            !skipped
+        });
+
+        timings.phase("discover links");
+
+        let lockfile_path = match &self.lockfile {
+            Some(lockfile) => Some(lockfile.clone()),
+            None => packages_folders
+                .first()
+                .and_then(|packages_folder| packages_folder.parent())
+                .map(|parent| parent.join("wally.lock"))
+                .filter(|lockfile| files.path_kind(lockfile) == PathKind::File),
+        };
+        let (lockfile, lockfile_hash) = match &lockfile_path {
+            Some(lockfile_path) => {
+                info!("Verifying links against '{}'", lockfile_path.display());
+                let contents = files
+                    .read_to_string(lockfile_path)
+                    .context("Failed to read lockfile")?;
+                (
+                    Some(Lockfile::parse(&contents)?),
+                    Some(hash_contents(&contents)),
+                )
+            }
+            None => (None, None),
+        };
+
+        timings.phase("read lockfile");
+
+        let declarations_dir = match &self.emit_declarations {
+            Some(_) if dry_run || self.emit_workspace_edit.is_some() || self.stdout => {
+                info!("Not writing declaration stubs, as link files are not being written");
+                None
+            }
+            Some(declarations_dir) => {
+                files.create_dir_all(declarations_dir).with_context(|| {
+                    format!("Failed to create '{}'", declarations_dir.display())
+                })?;
+                Some(files.canonicalize(declarations_dir).with_context(|| {
+                    format!("Failed to canonicalize '{}'", declarations_dir.display())
+                })?)
+            }
+            None => None,
+        };
+
+        let mut output_style = match &self.stylua_config {
+            Some(stylua_config) => OutputStyle::from_stylua_config(
+                &files.read_to_string(stylua_config).with_context(|| {
+                    format!(
+                        "Failed to read StyLua configuration '{}'",
+                        stylua_config.display()
+                    )
+                })?,
+            )?,
+            None => OutputStyle::default(),
+        };
+        if let Some(line_endings) = self.line_endings {
+            output_style.line_endings = line_endings;
+        }
+
+        let incremental_cache_path = self
+            .incremental
+            .then(|| project_root(&packages_folders).join(CACHE_FILE_NAME));
+        let mut incremental_cache = match &incremental_cache_path {
+            Some(_) if self.mode == Mode::Clean => {
+                info!("Not using the incremental cache whilst cleaning");
+                None
+            }
+            Some(incremental_cache_path) => {
+                // Anything which changes the generated links invalidates the whole cache
+                let fingerprint = hash_contents(&format!(
+                    "{} {:?} {} {} {} {:?} {:?} {:?} {:?} {:?}",
+                    env!("CARGO_PKG_VERSION"),
+                    self.shadowed_builtins.unwrap_or_default(),
+                    self.namespace_types,
+                    self.repair_links,
+                    self.follow_re_exports,
+                    lockfile_hash,
+                    declarations_dir,
+                    self.types,
+                    self.root_map,
+                    output_style,
+                ));
+                Some(IncrementalCache::load(
+                    incremental_cache_path,
+                    &fingerprint,
+                    files,
+                ))
+            }
+            None => None,
+        };
+        if let Some(incremental_cache) = &incremental_cache {
+            let discovered = thunks.len();
+            thunks.retain(
+                |thunk| match incremental_cache.unchanged_link(thunk, files) {
+                    Some(thunk_report) => {
+                        report.thunks.push(thunk_report);
+                        false
+                    }
+                    None => true,
+                },
+            );
+            info!(
+                "Skipping {} link file(s) unchanged since the last run",
+                discovered - thunks.len()
+            );
+        }
+
+        timings.phase("check incremental cache");
+
+        // The sourcemap is read-only from this point on, so thunks can be mutated in parallel
+        let context = MutationContext {
+            sourcemap: &sourcemap,
+            files,
+            declarations: DeclarationCache::default(),
+            options: MutateLinkOptions {
+                shadowed_builtins: self.shadowed_builtins.unwrap_or_default(),
+                namespace_types: self.namespace_types,
+                follow_re_exports: self.follow_re_exports,
+                ..Default::default()
+            },
+            dry_run,
+            clean: self.mode == Mode::Clean,
+            workspace_edits: self
+                .emit_workspace_edit
+                .is_some()
+                .then(WorkspaceEdits::default),
+            declarations_dir,
+            // The HTML report includes diffs, so they are computed even if not printed
+            diff: self.diff || html_report_path.is_some(),
+            lockfile,
+            repair_links: self.repair_links,
+            type_overrides: self.types.clone(),
+            root_maps: self.root_map.clone(),
+            output_style,
+            parse_timeout: (self.parse_timeout > 0)
+                .then(|| Duration::from_secs(self.parse_timeout)),
+            diagnostics: Diagnostics::new(
+                self.fail_on_warning,
+                self.deny.clone(),
+                self.allow.clone(),
+            ),
+        };
+        let progress = Progress::new(
+            &packages_folders,
+            &thunks,
+            log::max_level() >= LevelFilter::Warn,
+        );
+        let mutate = |thunk: &PathBuf| {
+            let package = containing_index_folder(thunk).unwrap_or_else(|| {
+                thunk
+                    .file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .to_string()
+            });
+            progress.start(thunk, &package);
+            let thunk_report = handled_mutate_thunk(thunk, &context);
+            progress.finish(thunk, &package);
+            thunk_report
+        };
+        let thunk_reports = thunks.par_iter().map(mutate).collect::<Vec<_>>();
+        if self.strict {
+            if let Some(failure) = thunk_reports.iter().find(|thunk| thunk.is_failure()) {
+                progress.clear();
+                let error = ClassifiedError::new(
+                    failure.failure_class().unwrap_or(FailureClass::Other),
+                    format!(
+                        "Failed to mutate '{}', stopping due to --strict: {}",
+                        failure.path.display(),
+                        failure.failure_reason().unwrap_or_default()
+                    ),
+                );
+                report.thunks.extend(thunk_reports);
+                report.warnings = context.diagnostics.emitted();
+                self.print_problems(&report);
+                bail!(error);
+            }
+        }
+        report.thunks.extend(thunk_reports);
+        progress.clear();
+
+        timings.phase("mutate links");
+
+        transaction::commit(
+            files,
+            files.take_staged(),
+            &project_root(&packages_folders).join(JOURNAL_FILE_NAME),
+            self.backup,
+        )?;
+        timings.phase("commit writes");
+        report.packages = installed_packages(&report, context.lockfile.as_ref());
+        if let (Some(lockfile), Some(lockfile_path)) = (&context.lockfile, &lockfile_path) {
+            check_lockfile_consistency(
+                lockfile,
+                lockfile_path,
+                &report,
+                &packages_folders,
+                &context,
+            );
+        }
+        report.warnings = context.diagnostics.emitted();
+
+        if let (Some(incremental_cache), Some(incremental_cache_path), false) = (
+            &mut incremental_cache,
+            &incremental_cache_path,
+            report.dry_run || self.stdout,
+        ) {
+            incremental_cache.update(&report, files);
+            incremental_cache.save(incremental_cache_path, files)?;
+        }
+
+        if self.luaurc {
+            self.write_luaurc_aliases(files, &packages_folders, &report, dry_run)?;
+        }
+
+        match self.output_format.unwrap_or_default() {
+            _ if self.stdout => {
+                if report.failures().next().is_none() {
+                    let link = &packages_folders[0];
+                    let contents = match files
+                        .written_files()
+                        .and_then(|mut written| written.remove(link))
+                    {
+                        Some(contents) => contents,
+                        None => files
+                            .read_to_string(link)
+                            .with_context(|| format!("Failed to read '{}'", link.display()))?,
+                    };
+                    print!("{contents}");
+                }
+            }
+            OutputFormat::Text if self.diff => {
+                for diff in report.thunks.iter().filter_map(|thunk| thunk.diff.as_ref()) {
+                    print!("{diff}");
+                }
+            }
+            OutputFormat::Text if self.mode == Mode::List => {
+                for thunk in &report.thunks {
+                    if let ThunkStatus::Mutated { exported_types }
+                    | ThunkStatus::UpToDate { exported_types } = &thunk.status
+                    {
+                        if exported_types.is_empty() {
+                            continue;
+                        }
+                        println!("{}", thunk.path.display());
+                        for exported_type in exported_types {
+                            println!("  {}", exported_type.name);
+                        }
+                    }
+                }
+            }
+            OutputFormat::Text if self.mode == Mode::Types => {
+                self.print_types(&report, &context)?
+            }
+            OutputFormat::Text => (),
+            OutputFormat::Json => println!(
+                "{}",
+                serde_json::to_string_pretty(&report).context("Failed to serialize report")?
+            ),
+        }
+
+        if let Some(strictness_report_path) = strictness_report_path {
+            let packages =
+                analyse_packages(&report, |target| linked_type_declarations(target, &context))?;
+            files
+                .write(strictness_report_path, &strictness::render(&packages))
+                .with_context(|| {
+                    format!(
+                        "Failed to write report to '{}'",
+                        strictness_report_path.display()
+                    )
+                })?;
+        }
+
+        if let Some(html_report_path) = html_report_path {
+            files
+                .write(html_report_path, &html_report::render(&report))
+                .with_context(|| {
+                    format!("Failed to write report to '{}'", html_report_path.display())
+                })?;
+        }
+
+        if let (Some(workspace_edit_path), Some(workspace_edits)) =
+            (&self.emit_workspace_edit, &context.workspace_edits)
+        {
+            let workspace_edit = serde_json::to_string_pretty(&workspace_edits.workspace_edit()?)
+                .context("Failed to serialize workspace edit")?;
+            files
+                .write(workspace_edit_path, &workspace_edit)
+                .with_context(|| {
+                    format!(
+                        "Failed to write workspace edit to '{}'",
+                        workspace_edit_path.display()
+                    )
+                })?;
+        }
+
+        timings.phase("write report");
+        timings.print();
+
+        self.print_problems(&report);
+        if self.summary_only && !self.stdout {
+            println!("{}", report.summary_line(start.elapsed()));
+        } else if !self.stdout && self.error_format != Some(ErrorFormat::Json) {
+            eprintln!("{}", report.summary_line(start.elapsed()));
+        }
+
+        let denied_warnings = report
+            .warnings
+            .iter()
+            .filter(|warning| warning.denied)
+            .count();
+        let failures = report.failures().collect::<Vec<_>>();
+        let exceeded_max_warnings = self
+            .max_warnings
+            .filter(|max_warnings| report.warnings.len() > *max_warnings);
+        let outdated = match self.mode {
+            Mode::Check => report
+                .thunks
+                .iter()
+                .filter(|thunk| matches!(thunk.status, ThunkStatus::Mutated { .. }))
+                .collect(),
+            _ => Vec::new(),
+        };
+        if failures.is_empty() && denied_warnings == 0 && exceeded_max_warnings.is_none() {
+            if outdated.is_empty() {
+                return Ok(());
+            }
+
+            error!("{} link file(s) are out of date:", outdated.len());
+            for thunk in outdated {
+                error!("  {}", thunk.path.display());
+            }
+            bail!("Run `wally-package-types fix` to update link files");
+        } else if failures.is_empty() && denied_warnings > 0 {
+            bail!("{denied_warnings} warning(s) were promoted to errors");
+        } else if let (true, Some(max_warnings)) = (failures.is_empty(), exceeded_max_warnings) {
+            error!(
+                "{} warning(s) were emitted, more than the maximum of {max_warnings}:",
+                report.warnings.len()
+            );
+            for (category, count) in report.warning_counts() {
+                error!("  {category}: {count}");
+            }
+            bail!("Too many warnings");
+        } else {
+            bail!(ClassifiedError::new(
+                most_common_failure_class(&failures),
+                format!(
+                    "Failed to mutate {} link file(s), mutation did not complete successfully",
+                    failures.len()
+                )
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn repairs_only_the_require_argument() {
+        let contents = "-- Was scope_pkg@1.0.0\nreturn require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg)\n";
+        let parsed_code = full_moon::parse(contents).unwrap();
+        let link_require = find_link_require(&parsed_code).unwrap();
+        let require = match_require(link_require.expression()).unwrap();
+
+        assert_eq!(
+            repair_require_argument(
+                contents,
+                &require.argument,
+                "scope_pkg@1.0.0",
+                "scope_pkg@1.1.0"
+            ),
+            "-- Was scope_pkg@1.0.0\nreturn require(script.Parent._Index[\"scope_pkg@1.1.0\"].pkg)\n"
+        );
+    }
+
+    #[test]
+    fn strict_check_fails_on_stale_links() {
+        let directory = std::env::temp_dir().join(format!(
+            "wally-package-types-strict-check-{}",
+            std::process::id()
+        ));
+        let package = directory.join("Packages/_Index/sleitnick_signal@1.5.0/signal");
+        std::fs::create_dir_all(&package).unwrap();
+        let link = directory.join("Packages/Signal.lua");
+        std::fs::write(
+            &link,
+            "return require(script.Parent._Index[\"sleitnick_signal@1.5.0\"][\"signal\"])\n",
+        )
+        .unwrap();
+        std::fs::write(
+            package.join("init.lua"),
+            "export type Connection = { Disconnect: (self: any) -> () }\nreturn {}\n",
+        )
+        .unwrap();
+        let sourcemap = directory.join("sourcemap.json");
+        std::fs::write(
+            &sourcemap,
+            serde_json::json!({
+                "name": "Packages", "className": "Folder", "filePaths": [directory.join("Packages")],
+                "children": [
+                    {"name": "Signal", "className": "ModuleScript", "filePaths": [link]},
+                    {"name": "_Index", "className": "Folder", "children": [
+                        {"name": "sleitnick_signal@1.5.0", "className": "Folder", "children": [
+                            {"name": "signal", "className": "ModuleScript", "filePaths": [package.join("init.lua")]}
+                        ]}
+                    ]}
+                ]
+            })
+            .to_string(),
+        )
+        .unwrap();
+
+        let command = Command::parse_from([
+            "wally-package-types".as_ref(),
+            "check".as_ref(),
+            "--strict".as_ref(),
+            "--sourcemap".as_ref(),
+            sourcemap.as_os_str(),
+            directory.join("Packages").as_os_str(),
+        ]);
+        assert!(command.run().is_err());
+        assert!(!std::fs::read_to_string(&link)
+            .unwrap()
+            .contains("export type"));
+
+        let command = Command::parse_from([
+            "wally-package-types".as_ref(),
+            "fix".as_ref(),
+            "--strict".as_ref(),
+            "--sourcemap".as_ref(),
+            sourcemap.as_os_str(),
+            directory.join("Packages").as_os_str(),
+        ]);
+        command.run().unwrap();
+        assert!(std::fs::read_to_string(&link)
+            .unwrap()
+            .contains("export type Connection = REQUIRED_MODULE.Connection"));
+
+        std::fs::remove_dir_all(&directory).unwrap();
     }
 }
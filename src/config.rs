@@ -0,0 +1,171 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+use crate::diagnostics::WarningCategory;
+use crate::failure::ErrorFormat;
+use crate::formatting::LineEndings;
+use crate::link_mutator::{ShadowedBuiltinBehaviour, TypeOverrides};
+use crate::report::OutputFormat;
+use crate::sourcemap::RootMap;
+
+/// The name of the configuration file, which is looked for in the current directory
+pub const CONFIG_FILE_NAME: &str = "wally-package-types.toml";
+
+/// The table of `wally.toml` which may hold the configuration instead
+const WALLY_MANIFEST_TABLE: &str = "package-types";
+
+/// Options which can be checked into a project rather than passed on the command line.
+/// Any option also given on the command line takes precedence
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Config {
+    pub sourcemap: Option<PathBuf>,
+    pub generate_sourcemap: bool,
+    pub sourcemap_command: Option<String>,
+    pub packages: Vec<PathBuf>,
+    pub skip: Vec<String>,
+    pub root_map: Vec<RootMap>,
+    pub lockfile: Option<PathBuf>,
+    pub line_endings: Option<LineEndings>,
+    pub stylua_config: Option<PathBuf>,
+    pub output_format: Option<OutputFormat>,
+    pub error_format: Option<ErrorFormat>,
+    pub diff: bool,
+    pub backup: bool,
+    pub no_canonicalize: bool,
+    pub summary_only: bool,
+    pub quiet: bool,
+    pub shadowed_builtins: Option<ShadowedBuiltinBehaviour>,
+    pub namespace_types: bool,
+    pub luaurc: bool,
+    pub follow_re_exports: bool,
+    pub incremental: bool,
+    pub fail_on_warning: bool,
+    pub deny: Vec<WarningCategory>,
+    pub allow: Vec<WarningCategory>,
+    pub max_warnings: Option<usize>,
+    /// Changes to the types re-exported from each package, keyed by package name, e.g. `sleitnick/signal`
+    pub types: BTreeMap<String, TypeOverrides>,
+}
+
+impl Config {
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).with_context(|| format!("Failed to parse {CONFIG_FILE_NAME}"))
+    }
+
+    /// Reads the configuration from the `[package-types]` table of a `wally.toml`, if it has one
+    pub fn parse_wally_manifest(contents: &str) -> Result<Option<Self>> {
+        let mut manifest: toml::Table =
+            toml::from_str(contents).context("Failed to parse wally.toml")?;
+        manifest
+            .remove(WALLY_MANIFEST_TABLE)
+            .map(|table| {
+                table
+                    .try_into()
+                    .context("Failed to parse [package-types] table of wally.toml")
+            })
+            .transpose()
+    }
+
+    /// Finds the configuration in the given directory, preferring `wally-package-types.toml` over `wally.toml`.
+    /// Paths in the configuration are relative to the directory it is found in
+    pub fn discover(directory: &Path) -> Result<Option<Self>> {
+        let config_path = directory.join(CONFIG_FILE_NAME);
+        let manifest_path = directory.join("wally.toml");
+        let config = if config_path.is_file() {
+            let contents = std::fs::read_to_string(&config_path)
+                .with_context(|| format!("Failed to read '{}'", config_path.display()))?;
+            Some(Self::parse(&contents)?)
+        } else if manifest_path.is_file() {
+            let contents = std::fs::read_to_string(&manifest_path)
+                .with_context(|| format!("Failed to read '{}'", manifest_path.display()))?;
+            Self::parse_wally_manifest(&contents)?
+        } else {
+            None
+        };
+
+        Ok(config.map(|config| config.relative_to(directory)))
+    }
+
+    fn relative_to(mut self, directory: &Path) -> Self {
+        self.sourcemap = self.sourcemap.map(|path| directory.join(path));
+        self.packages = self
+            .packages
+            .into_iter()
+            .map(|path| directory.join(path))
+            .collect();
+        self.lockfile = self.lockfile.map(|path| directory.join(path));
+        self.stylua_config = self.stylua_config.map(|path| directory.join(path));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_config_from_wally_manifest() {
+        let config = Config::parse_wally_manifest(
+            r#"
+            [package]
+            name = "user/project"
+            version = "0.1.0"
+
+            [package-types]
+            sourcemap = "sourcemap.json"
+            packages = ["Packages", "ServerPackages"]
+            skip = ["roblox_roact"]
+            root-map = ["game/ReplicatedStorage/Packages=game/ReplicatedStorage/Shared/Packages"]
+            shadowed-builtins = "rename"
+            deny = ["stripped-defaults"]
+
+            [package-types.types."sleitnick/signal"]
+            exclude = ["Connection"]
+            rename = { Signal = "ScopedSignal" }
+            "#,
+        )
+        .unwrap()
+        .unwrap()
+        .relative_to(Path::new("project"));
+
+        assert_eq!(
+            config,
+            Config {
+                sourcemap: Some(PathBuf::from("project/sourcemap.json")),
+                packages: vec![
+                    PathBuf::from("project/Packages"),
+                    PathBuf::from("project/ServerPackages")
+                ],
+                skip: vec!["roblox_roact".to_string()],
+                root_map: vec![
+                    "game/ReplicatedStorage/Packages=game/ReplicatedStorage/Shared/Packages"
+                        .parse()
+                        .unwrap()
+                ],
+                shadowed_builtins: Some(ShadowedBuiltinBehaviour::Rename),
+                deny: vec![WarningCategory::StrippedDefaults],
+                types: BTreeMap::from([(
+                    "sleitnick/signal".to_string(),
+                    TypeOverrides {
+                        exclude: vec!["Connection".to_string()],
+                        rename: BTreeMap::from([(
+                            "Signal".to_string(),
+                            "ScopedSignal".to_string()
+                        )]),
+                    }
+                )]),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            Config::parse_wally_manifest("[package]\nname = \"user/project\"\n").unwrap(),
+            None
+        );
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use anyhow::{Context, Result};
+use full_moon::ast::luau::ExportedTypeDeclaration;
+
+use crate::file_access::FileAccess;
+use crate::link_mutator::type_declarations_from_source;
+use crate::watchdog::run_with_timeout;
+
+type CacheEntry = Arc<Mutex<Option<Arc<Vec<ExportedTypeDeclaration>>>>>;
+
+/// Caches the exported type declarations of linked modules, keyed by their canonical file path,
+/// so that a module shared by many packages is only read and parsed once
+#[derive(Default)]
+pub struct DeclarationCache {
+    entries: Mutex<HashMap<PathBuf, CacheEntry>>,
+}
+
+impl DeclarationCache {
+    /// Retrieves the exported type declarations of the file at the given path, parsing it if it
+    /// has not been seen before. Parsing is abandoned if it takes longer than the timeout
+    pub fn get_or_parse(
+        &self,
+        path: &Path,
+        files: &FileAccess,
+        parse_timeout: Option<Duration>,
+    ) -> Result<Arc<Vec<ExportedTypeDeclaration>>> {
+        // Only hold the map lock whilst retrieving the entry, so different files can be parsed in parallel
+        let entry = self
+            .entries
+            .lock()
+            .unwrap()
+            .entry(path.to_path_buf())
+            .or_default()
+            .clone();
+
+        // Holding the entry lock whilst parsing ensures other threads wait for this result
+        // rather than parsing the same file again
+        let mut entry = entry.lock().unwrap();
+        if let Some(type_declarations) = entry.as_ref() {
+            return Ok(type_declarations.clone());
+        }
+
+        let contents = files
+            .read_module(path)
+            .context("Failed to read linked file")?;
+        let type_declarations = run_with_timeout(
+            parse_timeout,
+            &format!("Parsing '{}'", path.display()),
+            move || type_declarations_from_source(&contents),
+        )??;
+        let type_declarations = Arc::new(type_declarations);
+        *entry = Some(type_declarations.clone());
+
+        Ok(type_declarations)
+    }
+}
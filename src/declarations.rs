@@ -0,0 +1,144 @@
+use anyhow::Result;
+use full_moon::ast::luau::{ExportedTypeDeclaration, GenericParameterInfo};
+use serde::Serialize;
+
+use crate::link_mutator::type_declarations_from_source;
+
+/// A generic parameter of an exported type, such as `T`, `S = string` or `T...`
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct GenericParameter {
+    pub name: String,
+    /// Whether this is a generic type pack, e.g. `T...`
+    pub variadic: bool,
+    /// The default type of the parameter, as written in the source
+    pub default: Option<String>,
+}
+
+/// An exported type declaration, independent of the parser used to discover it
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct ExportedType {
+    pub name: String,
+    pub generics: Vec<GenericParameter>,
+    /// The definition of the type, as written in the source
+    pub definition: String,
+}
+
+/// Converts a full_moon declaration directly. Opt in with the `full-moon-ast` feature, as it ties downstream crates
+/// to the version of full_moon this crate depends on
+#[cfg(feature = "full-moon-ast")]
+impl From<&ExportedTypeDeclaration> for ExportedType {
+    fn from(stmt: &ExportedTypeDeclaration) -> Self {
+        Self::from_declaration(stmt)
+    }
+}
+
+impl ExportedType {
+    pub(crate) fn from_declaration(stmt: &ExportedTypeDeclaration) -> Self {
+        let type_declaration = stmt.type_declaration();
+
+        let generics = type_declaration.generics().map_or(vec![], |generics| {
+            generics
+                .generics()
+                .iter()
+                .map(|generic| {
+                    let (name, variadic) = match generic.parameter() {
+                        GenericParameterInfo::Name(name) => (name, false),
+                        GenericParameterInfo::Variadic { name, .. } => (name, true),
+                        other => unreachable!("unknown node: {:?}", other),
+                    };
+
+                    GenericParameter {
+                        name: name.token().to_string(),
+                        variadic,
+                        default: generic
+                            .default_type()
+                            .map(|default| default.to_string().trim().to_string()),
+                    }
+                })
+                .collect()
+        });
+
+        ExportedType {
+            name: type_declaration.type_name().token().to_string(),
+            generics,
+            definition: type_declaration
+                .type_definition()
+                .to_string()
+                .trim()
+                .to_string(),
+        }
+    }
+
+    /// The name and generics of the type, e.g. `Value<T, S = T, U... = ...any>`
+    pub fn signature(&self) -> String {
+        if self.generics.is_empty() {
+            return self.name.clone();
+        }
+
+        let generics = self
+            .generics
+            .iter()
+            .map(|generic| {
+                let variadic = if generic.variadic { "..." } else { "" };
+                match &generic.default {
+                    Some(default) => format!("{}{variadic} = {default}", generic.name),
+                    None => format!("{}{variadic}", generic.name),
+                }
+            })
+            .collect::<Vec<_>>()
+            .join(", ");
+        format!("{}<{generics}>", self.name)
+    }
+}
+
+/// Finds all exported types declared in the given source
+pub fn exported_types_from_source(code: &str) -> Result<Vec<ExportedType>> {
+    Ok(type_declarations_from_source(code)?
+        .iter()
+        .map(ExportedType::from_declaration)
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn converts_generics_and_defaults() {
+        let exported_types = exported_types_from_source(
+            r"
+            export type Value<T, S = T, U... = ...any> = Types.Value<T, S>
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            exported_types,
+            vec![ExportedType {
+                name: "Value".to_string(),
+                generics: vec![
+                    GenericParameter {
+                        name: "T".to_string(),
+                        variadic: false,
+                        default: None,
+                    },
+                    GenericParameter {
+                        name: "S".to_string(),
+                        variadic: false,
+                        default: Some("T".to_string()),
+                    },
+                    GenericParameter {
+                        name: "U".to_string(),
+                        variadic: true,
+                        default: Some("...any".to_string()),
+                    },
+                ],
+                definition: "Types.Value<T, S>".to_string(),
+            }]
+        );
+        assert_eq!(
+            exported_types[0].signature(),
+            "Value<T, S = T, U... = ...any>"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Categories of warnings, which can individually be promoted to errors
+#[derive(
+    ValueEnum, Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum WarningCategory {
+    /// A generic default could not be resolved in the link file, so was removed
+    StrippedDefaults,
+    /// A link file was not in a recognised form, so was left untouched
+    SkippedThunks,
+    /// A re-exported type shadows a builtin type
+    ShadowedBuiltins,
+    /// A root link points to a version which is not locked in wally.lock
+    VersionMismatches,
+    /// A generated name collided with a name declared in the link file, so was renamed
+    NamingCollisions,
+    /// An installed package is not locked in wally.lock, or a locked package is not installed
+    LockfileMismatches,
+}
+
+impl fmt::Display for WarningCategory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let value = self.to_possible_value().unwrap();
+        write!(f, "{}", value.get_name())
+    }
+}
+
+/// A warning produced whilst creating a new link, before it is attributed to a link file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Warning {
+    pub category: WarningCategory,
+    pub message: String,
+}
+
+impl Warning {
+    pub fn new(category: WarningCategory, message: impl Into<String>) -> Self {
+        Self {
+            category,
+            message: message.into(),
+        }
+    }
+}
+
+/// A warning which has been emitted for a link file
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct EmittedWarning {
+    pub path: PathBuf,
+    pub category: WarningCategory,
+    pub message: String,
+    /// Whether the warning was promoted to an error
+    pub denied: bool,
+}
+
+/// Collects all warnings emitted during a run, and decides which are promoted to errors
+#[derive(Default)]
+pub struct Diagnostics {
+    fail_on_warning: bool,
+    deny: Vec<WarningCategory>,
+    allow: Vec<WarningCategory>,
+    emitted: Mutex<Vec<EmittedWarning>>,
+}
+
+impl Diagnostics {
+    pub fn new(
+        fail_on_warning: bool,
+        deny: Vec<WarningCategory>,
+        allow: Vec<WarningCategory>,
+    ) -> Self {
+        Self {
+            fail_on_warning,
+            deny,
+            allow,
+            emitted: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Whether warnings of the given category are promoted to errors. An explicit `--allow`
+    /// takes precedence over `--fail-on-warning`, and an explicit `--deny` over both
+    pub fn is_denied(&self, category: WarningCategory) -> bool {
+        self.deny.contains(&category) || (self.fail_on_warning && !self.allow.contains(&category))
+    }
+
+    /// Records a warning for a link file. Warnings are printed together once the run completes,
+    /// rather than interleaved with the output of other link files
+    pub fn emit(&self, path: &Path, warning: Warning) {
+        let denied = self.is_denied(warning.category);
+        self.emitted.lock().unwrap().push(EmittedWarning {
+            path: path.to_path_buf(),
+            category: warning.category,
+            message: warning.message,
+            denied,
+        });
+    }
+
+    pub fn emitted(&self) -> Vec<EmittedWarning> {
+        self.emitted.lock().unwrap().clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allow_overrides_fail_on_warning() {
+        let diagnostics = Diagnostics::new(true, vec![], vec![WarningCategory::ShadowedBuiltins]);
+
+        assert!(diagnostics.is_denied(WarningCategory::StrippedDefaults));
+        assert!(!diagnostics.is_denied(WarningCategory::ShadowedBuiltins));
+    }
+
+    #[test]
+    fn deny_promotes_single_category() {
+        let diagnostics = Diagnostics::new(false, vec![WarningCategory::StrippedDefaults], vec![]);
+
+        assert!(diagnostics.is_denied(WarningCategory::StrippedDefaults));
+        assert!(!diagnostics.is_denied(WarningCategory::SkippedThunks));
+    }
+}
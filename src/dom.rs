@@ -0,0 +1,609 @@
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use rayon::prelude::*;
+
+use crate::command::{discover_thunks, handled_mutate_thunk, MutationContext};
+use crate::file_access::FileAccess;
+use crate::link_mutator::MutateLinkOptions;
+use crate::report::Report;
+use crate::sourcemap::{mutate_sourcemap, SourcemapNode};
+use crate::trace::{hash_contents, PathKind, RecordedDirEntry, RecordedFile, Trace};
+
+/// A tree of instances which link files can be read from and written back to, such as a Rojo DOM
+/// or a place file loaded with rbx_dom, used in place of a sourcemap and the filesystem
+pub trait InstanceTree {
+    type Id: Copy + Eq + Hash;
+
+    fn root(&self) -> Self::Id;
+    fn name(&self, id: Self::Id) -> &str;
+    fn class_name(&self, id: Self::Id) -> &str;
+    fn children(&self, id: Self::Id) -> Vec<Self::Id>;
+    /// The `Source` property of the instance, if it is a script
+    fn source(&self, id: Self::Id) -> Option<String>;
+    fn set_source(&mut self, id: Self::Id, source: String);
+}
+
+/// The root that virtual file paths are created under, so they can never collide with real files
+const VIRTUAL_ROOT: &str = "<dom>";
+
+/// Mirrors an instance tree as a sourcemap and an in-memory trace, where every script is given a
+/// virtual file path built from its position in the tree, and every instance with children a virtual directory
+struct VirtualTree<Id> {
+    sourcemap: SourcemapNode,
+    trace: Trace,
+    scripts: HashMap<PathBuf, Id>,
+    directories: HashMap<Id, PathBuf>,
+}
+
+/// Picks a file name for each of the given sibling instances. Instances can share a name, unlike files,
+/// so later siblings with a taken name are suffixed with their occurrence, e.g. `Name~2`
+fn unique_file_names<T: InstanceTree>(tree: &T, children: &[T::Id]) -> Vec<String> {
+    let mut taken = HashSet::new();
+    children
+        .iter()
+        .map(|child| {
+            let name = tree.name(*child);
+            let mut file_name = name.to_string();
+            let mut occurrence = 1;
+            while !taken.insert(file_name.clone()) {
+                occurrence += 1;
+                file_name = format!("{name}~{occurrence}");
+            }
+            file_name
+        })
+        .collect()
+}
+
+fn virtualize<T: InstanceTree>(tree: &T) -> VirtualTree<T::Id> {
+    let mut trace = Trace::default();
+    let mut scripts = HashMap::new();
+    let mut directories = HashMap::new();
+
+    fn visit<T: InstanceTree>(
+        tree: &T,
+        id: T::Id,
+        file_name: &str,
+        directory: &Path,
+        trace: &mut Trace,
+        scripts: &mut HashMap<PathBuf, T::Id>,
+        directories: &mut HashMap<T::Id, PathBuf>,
+    ) -> SourcemapNode {
+        let name = tree.name(id);
+        let mut file_paths = Vec::new();
+        if let Some(source) = tree.source(id) {
+            let path = directory.join(format!("{file_name}.luau"));
+            trace.files.insert(
+                path.clone(),
+                RecordedFile {
+                    hash: hash_contents(&source),
+                    contents: source,
+                },
+            );
+            trace.canonicalized.insert(path.clone(), Some(path.clone()));
+            trace.path_kinds.insert(path.clone(), PathKind::File);
+            scripts.insert(path.clone(), id);
+            file_paths.push(path);
+        }
+
+        // Directories are canonicalized for files which do not exist
+        trace
+            .canonicalized
+            .insert(directory.to_path_buf(), Some(directory.to_path_buf()));
+
+        let directory = directory.join(file_name);
+        let children = tree.children(id);
+        let child_file_names = unique_file_names(tree, &children);
+        if !children.is_empty() {
+            let mut entries = Vec::new();
+            for (child, child_name) in children.iter().zip(&child_file_names) {
+                if tree.source(*child).is_some() {
+                    entries.push(RecordedDirEntry {
+                        path: directory.join(format!("{child_name}.luau")),
+                        is_file: true,
+                    });
+                }
+                if !tree.children(*child).is_empty() {
+                    entries.push(RecordedDirEntry {
+                        path: directory.join(child_name),
+                        is_file: false,
+                    });
+                }
+            }
+            trace.directories.insert(directory.clone(), entries);
+            trace
+                .path_kinds
+                .insert(directory.clone(), PathKind::Directory);
+            directories.insert(id, directory.clone());
+        }
+
+        SourcemapNode {
+            name: name.to_string(),
+            class_name: tree.class_name(id).to_string(),
+            file_paths,
+            children: children
+                .into_iter()
+                .zip(&child_file_names)
+                .map(|(child, child_name)| {
+                    visit(
+                        tree,
+                        child,
+                        child_name,
+                        &directory,
+                        trace,
+                        scripts,
+                        directories,
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    let sourcemap = visit(
+        tree,
+        tree.root(),
+        tree.name(tree.root()),
+        Path::new(VIRTUAL_ROOT),
+        &mut trace,
+        &mut scripts,
+        &mut directories,
+    );
+    VirtualTree {
+        sourcemap,
+        trace,
+        scripts,
+        directories,
+    }
+}
+
+/// Mutates the link files inside of the given packages instance, writing the new link files back into the tree.
+/// Paths in the returned report are virtual, and only identify instances by their position in the tree
+pub fn mutate_instance_tree<T: InstanceTree>(
+    tree: &mut T,
+    packages: T::Id,
+    options: MutateLinkOptions,
+) -> Result<Report> {
+    let VirtualTree {
+        sourcemap,
+        trace,
+        scripts,
+        directories,
+    } = virtualize(tree);
+    let packages_folder = directories
+        .get(&packages)
+        .context("Packages instance has no children")?;
+
+    let files = FileAccess::new(1).in_memory(trace);
+    let sourcemap = mutate_sourcemap(sourcemap, &files)?;
+    let context = MutationContext::new(&sourcemap, &files, options);
+
+    let mut report = Report::default();
+    // Link files are found in the same way as on the filesystem
+    let thunks = discover_thunks(std::slice::from_ref(packages_folder), &files, &mut report)?;
+    report.thunks.par_extend(
+        thunks
+            .par_iter()
+            .map(|thunk| handled_mutate_thunk(thunk, &context)),
+    );
+    report.thunks.sort_by(|a, b| a.path.cmp(&b.path));
+    report.warnings = context.diagnostics.emitted();
+
+    for (path, contents) in files.written_files().unwrap_or_default() {
+        if let Some(id) = scripts.get(&path) {
+            tree.set_source(*id, contents);
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::ThunkStatus;
+
+    struct TestInstance {
+        name: &'static str,
+        class_name: &'static str,
+        source: Option<String>,
+        children: Vec<usize>,
+    }
+
+    #[derive(Default)]
+    struct TestTree {
+        instances: Vec<TestInstance>,
+    }
+
+    impl TestTree {
+        fn insert(
+            &mut self,
+            parent: Option<usize>,
+            name: &'static str,
+            class_name: &'static str,
+            source: Option<&str>,
+        ) -> usize {
+            let id = self.instances.len();
+            self.instances.push(TestInstance {
+                name,
+                class_name,
+                source: source.map(String::from),
+                children: Vec::new(),
+            });
+            if let Some(parent) = parent {
+                self.instances[parent].children.push(id);
+            }
+            id
+        }
+    }
+
+    impl InstanceTree for TestTree {
+        type Id = usize;
+
+        fn root(&self) -> usize {
+            0
+        }
+
+        fn name(&self, id: usize) -> &str {
+            self.instances[id].name
+        }
+
+        fn class_name(&self, id: usize) -> &str {
+            self.instances[id].class_name
+        }
+
+        fn children(&self, id: usize) -> Vec<usize> {
+            self.instances[id].children.clone()
+        }
+
+        fn source(&self, id: usize) -> Option<String> {
+            self.instances[id].source.clone()
+        }
+
+        fn set_source(&mut self, id: usize, source: String) {
+            self.instances[id].source = Some(source);
+        }
+    }
+
+    #[test]
+    fn mutates_links_in_instance_tree() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "Packages", "Folder", None);
+        let link = tree.insert(
+            Some(packages),
+            "Signal",
+            "ModuleScript",
+            Some("return require(script.Parent._Index[\"sleitnick_signal@1.5.0\"][\"signal\"])\n"),
+        );
+        let index = tree.insert(Some(packages), "_Index", "Folder", None);
+        let package = tree.insert(Some(index), "sleitnick_signal@1.5.0", "Folder", None);
+        tree.insert(
+            Some(package),
+            "signal",
+            "ModuleScript",
+            Some("export type Connection = { Disconnect: () -> () }\nreturn {}\n"),
+        );
+
+        let report =
+            mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+
+        assert_eq!(report.thunks.len(), 1);
+        assert!(matches!(
+            report.thunks[0].status,
+            ThunkStatus::Mutated { .. }
+        ));
+        assert_eq!(
+            tree.source(link).unwrap(),
+            "local REQUIRED_MODULE = require(script.Parent._Index[\"sleitnick_signal@1.5.0\"][\"signal\"])\n\
+             export type Connection = REQUIRED_MODULE.Connection \n\
+             return REQUIRED_MODULE\n"
+        );
+    }
+
+    #[test]
+    fn mutates_links_with_the_same_name() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "Packages", "Folder", None);
+        let index = tree.insert(Some(packages), "_Index", "Folder", None);
+        let package = tree.insert(Some(index), "scope_pkg@1.0.0", "Folder", None);
+        tree.insert(
+            Some(package),
+            "pkg",
+            "ModuleScript",
+            Some("export type X = number\nreturn {}\n"),
+        );
+        let link_source = "return require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg)\n";
+        let first = tree.insert(Some(packages), "Dup", "ModuleScript", Some(link_source));
+        let second = tree.insert(Some(packages), "Dup", "ModuleScript", Some(link_source));
+
+        let report =
+            mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+        assert_eq!(report.thunks.len(), 2);
+        assert!(tree.source(first).unwrap().contains("export type X"));
+        assert!(tree.source(second).unwrap().contains("export type X"));
+    }
+
+    #[test]
+    fn regenerates_links_inside_index() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "Packages", "Folder", None);
+        let index = tree.insert(Some(packages), "_Index", "Folder", None);
+        let package = tree.insert(Some(index), "scope_pkg@1.0.0", "Folder", None);
+        let link = tree.insert(
+            Some(package),
+            "Dep",
+            "ModuleScript",
+            Some("return require(script.Parent.Parent[\"scope_dep@1.0.0\"].dep)\n"),
+        );
+        let dep_package = tree.insert(Some(index), "scope_dep@1.0.0", "Folder", None);
+        let dep = tree.insert(
+            Some(dep_package),
+            "dep",
+            "ModuleScript",
+            Some("export type X = number\nreturn {}\n"),
+        );
+
+        mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+        assert!(tree.source(link).unwrap().contains("export type X"));
+
+        tree.set_source(dep, "export type Y = number\nreturn {}\n".to_string());
+        let report =
+            mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+        assert_eq!(report.thunks.len(), 1);
+        let source = tree.source(link).unwrap();
+        assert!(source.contains("export type Y"));
+        assert!(!source.contains("export type X"));
+    }
+
+    #[test]
+    fn regenerates_links_from_previous_runs() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "Packages", "Folder", None);
+        let link = tree.insert(
+            Some(packages),
+            "Signal",
+            "ModuleScript",
+            Some("return require(script.Parent._Index[\"sleitnick_signal@1.5.0\"][\"signal\"])\n"),
+        );
+        let index = tree.insert(Some(packages), "_Index", "Folder", None);
+        let package = tree.insert(Some(index), "sleitnick_signal@1.5.0", "Folder", None);
+        let module = tree.insert(
+            Some(package),
+            "signal",
+            "ModuleScript",
+            Some("export type Connection = { Disconnect: () -> () }\nreturn {}\n"),
+        );
+
+        mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+        let report =
+            mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+        assert!(matches!(
+            report.thunks[0].status,
+            ThunkStatus::UpToDate { .. }
+        ));
+
+        tree.set_source(
+            module,
+            "export type Handle = { Disconnect: () -> () }\nreturn {}\n".to_string(),
+        );
+        mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+        assert_eq!(
+            tree.source(link).unwrap(),
+            "local REQUIRED_MODULE = require(script.Parent._Index[\"sleitnick_signal@1.5.0\"][\"signal\"])\n\
+             export type Handle = REQUIRED_MODULE.Handle \n\
+             return REQUIRED_MODULE\n"
+        );
+    }
+
+    #[test]
+    fn re_exports_types_of_member_accessed_on_required_module() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "Packages", "Folder", None);
+        let child_link = tree.insert(
+            Some(packages),
+            "Child",
+            "ModuleScript",
+            Some("return require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg).Child\n"),
+        );
+        let field_link = tree.insert(
+            Some(packages),
+            "Field",
+            "ModuleScript",
+            Some("return require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg).Field\n"),
+        );
+        let index = tree.insert(Some(packages), "_Index", "Folder", None);
+        let package = tree.insert(Some(index), "scope_pkg@1.0.0", "Folder", None);
+        let pkg = tree.insert(
+            Some(package),
+            "pkg",
+            "ModuleScript",
+            Some("return { Child = require(script.Child), Field = require(script.Inner.Field) }\n"),
+        );
+        tree.insert(
+            Some(pkg),
+            "Child",
+            "ModuleScript",
+            Some("export type Child = number\nreturn {}\n"),
+        );
+        let inner = tree.insert(Some(pkg), "Inner", "Folder", None);
+        tree.insert(
+            Some(inner),
+            "Field",
+            "ModuleScript",
+            Some("export type Field = string\nreturn {}\n"),
+        );
+
+        mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+
+        assert_eq!(
+            tree.source(child_link).unwrap(),
+            "local REQUIRED_MODULE = require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg.Child)\n\
+             export type Child = REQUIRED_MODULE.Child \n\
+             return require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg).Child\n"
+        );
+        assert_eq!(
+            tree.source(field_link).unwrap(),
+            "local REQUIRED_MODULE = require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg.Inner.Field)\n\
+             export type Field = REQUIRED_MODULE.Field \n\
+             return require(script.Parent._Index[\"scope_pkg@1.0.0\"].pkg).Field\n"
+        );
+    }
+
+    #[test]
+    fn follows_re_exported_types_to_their_declaration() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "Packages", "Folder", None);
+        let link = tree.insert(
+            Some(packages),
+            "A",
+            "ModuleScript",
+            Some("return require(script.Parent._Index[\"scope_a@1.0.0\"].a)\n"),
+        );
+        let index = tree.insert(Some(packages), "_Index", "Folder", None);
+        let package_a = tree.insert(Some(index), "scope_a@1.0.0", "Folder", None);
+        tree.insert(
+            Some(package_a),
+            "a",
+            "ModuleScript",
+            Some(
+                "local B = require(script.Parent.B)\n\
+                 export type Signal<T...> = B.Signal<T...>\n\
+                 return { new = B.new }\n",
+            ),
+        );
+        tree.insert(
+            Some(package_a),
+            "B",
+            "ModuleScript",
+            Some("return require(script.Parent.Parent[\"scope_b@1.0.0\"].b)\n"),
+        );
+        let package_b = tree.insert(Some(index), "scope_b@1.0.0", "Folder", None);
+        tree.insert(
+            Some(package_b),
+            "b",
+            "ModuleScript",
+            Some(
+                "export type Signal<T...> = { Fire: (self: Signal<T...>, T...) -> () }\n\
+                 export type Connection = { Disconnect: (self: Connection) -> () }\n\
+                 return {}\n",
+            ),
+        );
+
+        mutate_instance_tree(
+            &mut tree,
+            packages,
+            MutateLinkOptions {
+                follow_re_exports: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+
+        // Only the types the package re-exports are re-exported, straight from the peer dependency
+        assert_eq!(
+            tree.source(link).unwrap(),
+            "local REQUIRED_MODULE = require(script.Parent._Index[\"scope_b@1.0.0\"].b)\n\
+             export type Signal<T...> = REQUIRED_MODULE.Signal<T...>\n\
+             return require(script.Parent._Index[\"scope_a@1.0.0\"].a)\n"
+        );
+    }
+
+    #[test]
+    fn mutates_pesde_links() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "roblox_packages", "Folder", None);
+        // pesde generates links which already re-export the names of the package's types, through a local which is kept
+        let link = tree.insert(
+            Some(packages),
+            "Signal",
+            "ModuleScript",
+            Some(
+                "local module = require(script.Parent[\".pesde\"][\"sleitnick+signal\"][\"1.5.0\"].signal)\n\
+                 export type Signal<T...> = module.Signal<T...>\n\
+                 return module\n",
+            ),
+        );
+        let index = tree.insert(Some(packages), ".pesde", "Folder", None);
+        let package = tree.insert(Some(index), "sleitnick+signal", "Folder", None);
+        let version = tree.insert(Some(package), "1.5.0", "Folder", None);
+        tree.insert(
+            Some(version),
+            "signal",
+            "ModuleScript",
+            Some(
+                "export type Signal<T... = ...any> = { Fire: (self: Signal<T...>, T...) -> () }\n\
+                 export type Connection = { Disconnect: (self: Connection) -> () }\n\
+                 return {}\n",
+            ),
+        );
+
+        let report =
+            mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+
+        assert_eq!(report.thunks.len(), 1);
+        assert_eq!(
+            tree.source(link).unwrap(),
+            "local module = require(script.Parent[\".pesde\"][\"sleitnick+signal\"][\"1.5.0\"].signal)\n\
+             export type Signal<T... = ...any> = module.Signal<T... >\n\
+             export type Connection = module.Connection \n\
+             return module\n"
+        );
+    }
+
+    #[test]
+    fn mutates_links_in_nested_index() {
+        let mut tree = TestTree::default();
+        let game = tree.insert(None, "Game", "DataModel", None);
+        let packages = tree.insert(Some(game), "Packages", "Folder", None);
+        let index = tree.insert(Some(packages), "_Index", "Folder", None);
+        let package = tree.insert(Some(index), "scope_pkg@1.0.0", "Folder", None);
+        let nested_index = tree.insert(Some(package), "_Index", "Folder", None);
+        let nested_package = tree.insert(Some(nested_index), "scope_dep@2.0.0", "Folder", None);
+        let link = tree.insert(
+            Some(nested_package),
+            "Other",
+            "ModuleScript",
+            Some("return require(script.Parent.Parent[\"scope_other@1.0.0\"].other)\n"),
+        );
+        let other_package = tree.insert(Some(nested_index), "scope_other@1.0.0", "Folder", None);
+        tree.insert(
+            Some(other_package),
+            "other",
+            "ModuleScript",
+            Some("export type Other = number\nreturn {}\n"),
+        );
+        let dep = tree.insert(
+            Some(nested_package),
+            "dep",
+            "ModuleScript",
+            Some("return {}\n"),
+        );
+        // A module of the package itself, which has the shape of a link
+        let module = tree.insert(
+            Some(dep),
+            "Other",
+            "ModuleScript",
+            Some("return require(script.Parent.Parent.Parent[\"scope_other@1.0.0\"].other)\n"),
+        );
+
+        let report =
+            mutate_instance_tree(&mut tree, packages, MutateLinkOptions::default()).unwrap();
+
+        assert_eq!(report.thunks.len(), 1);
+        assert!(tree
+            .source(link)
+            .unwrap()
+            .contains("export type Other = REQUIRED_MODULE.Other"));
+        assert!(!tree.source(module).unwrap().contains("export type"));
+    }
+}
@@ -0,0 +1,187 @@
+use std::{fmt, io, path::Path};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::resolution_error::ResolutionError;
+
+/// How errors which end a run are output
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ErrorFormat {
+    /// Human readable log lines
+    #[default]
+    Text,
+    /// A JSON object per error on its own line of stderr, with all other logging suppressed
+    Json,
+}
+
+/// The classes of failure a run can end with, each of which exits with its own code so that scripts can tell
+/// them apart. Exit code 2 is left to invalid arguments
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "kebab-case")]
+pub enum FailureClass {
+    /// The sourcemap is missing, or could not be generated or parsed
+    Sourcemap,
+    /// The require of a link file could not be resolved
+    Resolution,
+    /// A link file, or a module it links to, is not valid Luau
+    Parse,
+    /// A file could not be read or written
+    Filesystem,
+    /// Any other failure, such as warnings promoted to errors or link files which are out of date
+    Other,
+}
+
+impl FailureClass {
+    pub fn exit_code(self) -> i32 {
+        match self {
+            FailureClass::Other => 1,
+            FailureClass::Sourcemap => 3,
+            FailureClass::Resolution => 4,
+            FailureClass::Parse => 5,
+            FailureClass::Filesystem => 6,
+        }
+    }
+
+    /// Classifies an error by the first known cause in its chain
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(classified) = err.downcast_ref::<ClassifiedError>() {
+            classified.class
+        } else if err.downcast_ref::<ResolutionError>().is_some() {
+            FailureClass::Resolution
+        } else if err.downcast_ref::<LuauParseError>().is_some() {
+            FailureClass::Parse
+        } else if err.downcast_ref::<io::Error>().is_some() {
+            FailureClass::Filesystem
+        } else {
+            FailureClass::Other
+        }
+    }
+}
+
+/// The exit code a run which failed with the given error exits with
+pub fn exit_code(err: &anyhow::Error) -> i32 {
+    FailureClass::of(err).exit_code()
+}
+
+/// An error given a class explicitly, rather than being classified by its causes. Can also be attached to an
+/// error as its context
+#[derive(Debug)]
+pub struct ClassifiedError {
+    class: FailureClass,
+    message: String,
+}
+
+impl ClassifiedError {
+    pub fn new(class: FailureClass, message: impl Into<String>) -> Self {
+        Self {
+            class,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for ClassifiedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for ClassifiedError {}
+
+/// The errors from parsing a Luau file
+#[derive(Debug)]
+pub struct LuauParseError(String);
+
+impl LuauParseError {
+    pub fn new(errors: &[full_moon::Error]) -> Self {
+        Self(
+            errors
+                .iter()
+                .map(|err| err.to_string())
+                .collect::<Vec<_>>()
+                .join("\n"),
+        )
+    }
+}
+
+impl fmt::Display for LuauParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for LuauParseError {}
+
+/// An error output with `--error-format json`
+#[derive(Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct ErrorObject<'a> {
+    code: i32,
+    class: FailureClass,
+    file: Option<&'a Path>,
+    message: &'a str,
+}
+
+/// Renders an error as a single line JSON object, e.g.
+/// `{"code":4,"class":"resolution","file":"Packages/Signal.lua","message":"..."}`
+pub fn json_error(class: FailureClass, file: Option<&Path>, message: &str) -> String {
+    serde_json::to_string(&ErrorObject {
+        code: class.exit_code(),
+        class,
+        file,
+        message,
+    })
+    .expect("error objects are always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::Context;
+
+    use super::*;
+    use crate::resolution_error::ErrorCode;
+
+    #[test]
+    fn classifies_errors_by_their_causes() {
+        let read: anyhow::Result<()> = Err(io::Error::from(io::ErrorKind::NotFound).into());
+        assert_eq!(
+            FailureClass::of(&read.context("Failed to read linked file").unwrap_err()),
+            FailureClass::Filesystem
+        );
+
+        let sourcemap: anyhow::Result<()> = Err(io::Error::from(io::ErrorKind::NotFound).into());
+        let sourcemap = sourcemap
+            .context(ClassifiedError::new(
+                FailureClass::Sourcemap,
+                "Failed to read sourcemap file",
+            ))
+            .unwrap_err();
+        assert_eq!(FailureClass::of(&sourcemap), FailureClass::Sourcemap);
+        assert_eq!(exit_code(&sourcemap), 3);
+
+        let resolution = anyhow::Error::new(ResolutionError::new(ErrorCode::NoParent, "no parent"))
+            .context("Could not convert require expression to file path");
+        assert_eq!(FailureClass::of(&resolution), FailureClass::Resolution);
+
+        let parse = full_moon::parse("local = 1").unwrap_err();
+        assert_eq!(
+            FailureClass::of(&anyhow::Error::new(LuauParseError::new(&parse))),
+            FailureClass::Parse
+        );
+        assert_eq!(
+            FailureClass::of(&anyhow::anyhow!("Too many warnings")),
+            FailureClass::Other
+        );
+
+        assert_eq!(
+            json_error(
+                FailureClass::Resolution,
+                Some(Path::new("Packages/Signal.lua")),
+                "Child 'signal' not found"
+            ),
+            r#"{"code":4,"class":"resolution","file":"Packages/Signal.lua","message":"Child 'signal' not found"}"#
+        );
+    }
+}
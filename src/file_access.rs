@@ -0,0 +1,711 @@
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use log::{info, warn};
+
+use crate::path_normalization::{normalize_lexically, simplify};
+use crate::trace::{hash_contents, PathKind, RecordedDirEntry, RecordedFile, Trace};
+
+/// The path which stands for stdin, e.g. `--sourcemap -`
+pub const STDIN_PATH: &str = "-";
+
+/// How many times an operation is retried after running out of file descriptors
+const MAX_RETRIES: u32 = 5;
+
+/// The initial delay before retrying, doubled on each subsequent attempt
+const INITIAL_RETRY_DELAY: Duration = Duration::from_millis(10);
+
+#[cfg(unix)]
+const TOO_MANY_OPEN_FILES_CODES: &[i32] = &[23 /* ENFILE */, 24 /* EMFILE */];
+#[cfg(windows)]
+const TOO_MANY_OPEN_FILES_CODES: &[i32] = &[4 /* ERROR_TOO_MANY_OPEN_FILES */];
+#[cfg(not(any(unix, windows)))]
+const TOO_MANY_OPEN_FILES_CODES: &[i32] = &[];
+
+fn is_too_many_open_files(err: &io::Error) -> bool {
+    err.raw_os_error()
+        .is_some_and(|code| TOO_MANY_OPEN_FILES_CODES.contains(&code))
+}
+
+/// How long an operation is retried for whilst the file is locked by another process
+const LOCKED_RETRY_TIMEOUT: Duration = Duration::from_secs(2);
+
+#[cfg(unix)]
+const LOCKED_CODES: &[i32] = &[16 /* EBUSY */, 26 /* ETXTBSY */];
+#[cfg(windows)]
+const LOCKED_CODES: &[i32] = &[
+    32, /* ERROR_SHARING_VIOLATION */
+    33, /* ERROR_LOCK_VIOLATION */
+];
+#[cfg(not(any(unix, windows)))]
+const LOCKED_CODES: &[i32] = &[];
+
+/// Whether an error is likely caused by another process, such as an antivirus scanner, briefly locking the file
+fn is_locked(err: &io::Error) -> bool {
+    err.raw_os_error()
+        .is_some_and(|code| LOCKED_CODES.contains(&code))
+}
+
+fn still_locked(err: io::Error) -> io::Error {
+    io::Error::new(
+        err.kind(),
+        format!(
+            "{err}. The file is likely locked by another process, such as an antivirus scanner, \
+             or a sync client like OneDrive or Dropbox. Try excluding the project from it, then run again"
+        ),
+    )
+}
+
+fn too_large(path: &Path, max_file_size: u64) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!(
+            "'{}' is larger than the maximum of {max_file_size} bytes. It may be corrupt or an \
+             incomplete download, try reinstalling the package, or raise --max-file-size",
+            path.display()
+        ),
+    )
+}
+
+/// The filesystem the core reads link files, modules and the sourcemap from, and writes link files to.
+/// Implemented for the real filesystem by [`StdFileSystem`], and in memory by
+/// [`MemoryFileSystem`](crate::MemoryFileSystem) for embedding the crate without touching the real filesystem
+pub trait FileSystem: Send + Sync {
+    fn read_to_string(&self, path: &Path) -> io::Result<String>;
+
+    /// Reads a file, failing if it is larger than `max_file_size` bytes
+    fn read_limited(&self, path: &Path, max_file_size: u64) -> io::Result<String> {
+        let contents = self.read_to_string(path)?;
+        if contents.len() as u64 > max_file_size {
+            return Err(too_large(path, max_file_size));
+        }
+        Ok(contents)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()>;
+
+    /// Moves a file, replacing any file already at `to`
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+
+    fn remove_file(&self, path: &Path) -> io::Result<()>;
+
+    /// Creates a directory and any of its missing parents
+    fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+
+    /// The absolute path with all `.`, `..` and symlinks resolved. Fails if nothing exists at the path
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf>;
+
+    fn path_kind(&self, path: &Path) -> PathKind;
+
+    /// Lists the entries of a directory, returning their paths and whether they are files
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<RecordedDirEntry>>;
+}
+
+/// The real filesystem, used by the CLI
+pub struct StdFileSystem;
+
+impl FileSystem for StdFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        std::fs::read_to_string(path)
+    }
+
+    /// Fails as soon as more than `max_file_size` bytes have been read, so that a pathologically large file
+    /// is never read into memory in full
+    fn read_limited(&self, path: &Path, max_file_size: u64) -> io::Result<String> {
+        let mut contents = Vec::new();
+        File::open(path)?
+            .take(max_file_size.saturating_add(1))
+            .read_to_end(&mut contents)?;
+        if contents.len() as u64 > max_file_size {
+            return Err(too_large(path, max_file_size));
+        }
+        String::from_utf8(contents).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        std::fs::write(path, contents)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        std::fs::rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        path.canonicalize()
+    }
+
+    fn path_kind(&self, path: &Path) -> PathKind {
+        if path.is_file() {
+            PathKind::File
+        } else if path.is_dir() {
+            PathKind::Directory
+        } else {
+            PathKind::Missing
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<RecordedDirEntry>> {
+        std::fs::read_dir(path)?
+            .flatten()
+            .map(|entry| {
+                Ok(RecordedDirEntry {
+                    path: entry.path(),
+                    is_file: entry.file_type()?.is_file(),
+                })
+            })
+            .collect()
+    }
+}
+
+/// Lets a filesystem be shared with the caller, e.g. to read back what was written to a `MemoryFileSystem`
+impl<T: FileSystem + ?Sized> FileSystem for Arc<T> {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        (**self).read_to_string(path)
+    }
+
+    fn read_limited(&self, path: &Path, max_file_size: u64) -> io::Result<String> {
+        (**self).read_limited(path, max_file_size)
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        (**self).write(path, contents)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        (**self).rename(from, to)
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        (**self).remove_file(path)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        (**self).create_dir_all(path)
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        (**self).canonicalize(path)
+    }
+
+    fn path_kind(&self, path: &Path) -> PathKind {
+        (**self).path_kind(path)
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<RecordedDirEntry>> {
+        (**self).read_dir(path)
+    }
+}
+
+fn not_recorded(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("'{}' was not recorded in the trace", path.display()),
+    )
+}
+
+enum Mode {
+    /// Operations go straight to the filesystem
+    Direct,
+    /// Operations go to the filesystem, and their results are recorded into a trace
+    Record(Mutex<Trace>),
+    /// Operations are answered from a previously recorded trace, and nothing is written
+    Replay(Trace),
+    /// Operations are answered from a trace built in memory, and writes are kept in memory
+    InMemory {
+        trace: Trace,
+        written: Mutex<BTreeMap<PathBuf, String>>,
+    },
+    /// Operations go to the filesystem, but writes are kept in memory
+    CaptureWrites(Mutex<BTreeMap<PathBuf, String>>),
+}
+
+/// Funnels all filesystem access made whilst running, so that it can be recorded and replayed.
+/// Also bounds the number of files that may be open at once across all threads, so that processing
+/// large numbers of thunks in parallel does not exhaust the process' file descriptor limit
+pub struct FileAccess {
+    available: Mutex<usize>,
+    released: Condvar,
+    mode: Mode,
+    /// The largest module which may be read, if limited
+    max_file_size: Option<u64>,
+    /// Writes kept in memory until they are committed, if staging
+    staged: Mutex<Option<BTreeMap<PathBuf, String>>>,
+    /// Normalize paths lexically rather than canonicalizing them
+    lexical_paths: bool,
+    /// The filesystem operations which are not answered from a trace go to
+    file_system: Box<dyn FileSystem>,
+}
+
+struct Permit<'a> {
+    access: &'a FileAccess,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        *self.access.available.lock().unwrap() += 1;
+        self.access.released.notify_one();
+    }
+}
+
+impl FileAccess {
+    pub fn new(max_open_files: usize) -> Self {
+        Self {
+            available: Mutex::new(max_open_files.max(1)),
+            released: Condvar::new(),
+            mode: Mode::Direct,
+            max_file_size: None,
+            staged: Mutex::new(None),
+            lexical_paths: false,
+            file_system: Box::new(StdFileSystem),
+        }
+    }
+
+    /// Fails reads of modules larger than the given number of bytes, rather than reading them in full
+    pub fn with_max_file_size(self, max_file_size: u64) -> Self {
+        Self {
+            max_file_size: Some(max_file_size),
+            ..self
+        }
+    }
+
+    /// Normalizes paths lexically rather than canonicalizing them, for symlinked workspaces and network drives
+    /// where canonical paths do not match the paths in the sourcemap
+    pub fn without_canonicalizing(self) -> Self {
+        Self {
+            lexical_paths: true,
+            ..self
+        }
+    }
+
+    /// Runs on the given filesystem rather than the real one
+    pub fn with_file_system(self, file_system: impl FileSystem + 'static) -> Self {
+        Self {
+            file_system: Box::new(file_system),
+            ..self
+        }
+    }
+
+    /// Records all filesystem access into the given trace
+    pub fn recording(self, trace: Trace) -> Self {
+        Self {
+            mode: Mode::Record(Mutex::new(trace)),
+            ..self
+        }
+    }
+
+    /// Answers all filesystem access from the given trace
+    pub fn replaying(self, trace: Trace) -> Self {
+        Self {
+            mode: Mode::Replay(trace),
+            ..self
+        }
+    }
+
+    /// Answers all filesystem access from the given trace, keeping anything written in memory
+    pub fn in_memory(self, trace: Trace) -> Self {
+        Self {
+            mode: Mode::InMemory {
+                trace,
+                written: Mutex::new(BTreeMap::new()),
+            },
+            ..self
+        }
+    }
+
+    /// Reads from the filesystem, but keeps anything written in memory
+    pub fn capturing_writes(self) -> Self {
+        Self {
+            mode: Mode::CaptureWrites(Mutex::new(BTreeMap::new())),
+            ..self
+        }
+    }
+
+    /// Keeps writes to the filesystem in memory until they are taken with [`FileAccess::take_staged`] to be
+    /// committed, so that a run which fails part way through leaves the filesystem untouched
+    pub fn staging(self) -> Self {
+        Self {
+            staged: Mutex::new(Some(BTreeMap::new())),
+            ..self
+        }
+    }
+
+    /// Returns the writes staged so far, after which writes go straight to the filesystem again
+    pub fn take_staged(&self) -> BTreeMap<PathBuf, String> {
+        self.staged.lock().unwrap().take().unwrap_or_default()
+    }
+
+    /// Returns the contents written so far, if writes are kept in memory
+    pub fn written_files(&self) -> Option<BTreeMap<PathBuf, String>> {
+        match &self.mode {
+            Mode::InMemory { written, .. } | Mode::CaptureWrites(written) => {
+                Some(written.lock().unwrap().clone())
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the trace operations are answered from, if not operating on the filesystem
+    fn answering_trace(&self) -> Option<&Trace> {
+        match &self.mode {
+            Mode::Replay(trace) | Mode::InMemory { trace, .. } => Some(trace),
+            _ => None,
+        }
+    }
+
+    /// Returns the trace recorded so far, if recording
+    pub fn recorded_trace(&self) -> Option<Trace> {
+        match &self.mode {
+            Mode::Record(trace) => Some(trace.lock().unwrap().clone()),
+            _ => None,
+        }
+    }
+
+    fn record(&self, record: impl FnOnce(&mut Trace)) {
+        if let Mode::Record(trace) = &self.mode {
+            record(&mut trace.lock().unwrap());
+        }
+    }
+
+    fn acquire(&self) -> Permit<'_> {
+        let mut available = self
+            .released
+            .wait_while(self.available.lock().unwrap(), |available| *available == 0)
+            .unwrap();
+        *available -= 1;
+        Permit { access: self }
+    }
+
+    /// Runs a file operation whilst holding a permit, retrying with a backoff if the OS
+    /// reports that too many files are open, or that the file is locked by another process
+    fn with_permit<T>(&self, operation: impl Fn() -> io::Result<T>) -> io::Result<T> {
+        let mut delay = INITIAL_RETRY_DELAY;
+        let mut attempt = 0;
+        // Only read the clock once a file is locked, as it is unavailable on some targets such as WebAssembly
+        let mut locked_since = None;
+
+        loop {
+            let result = {
+                let _permit = self.acquire();
+                operation()
+            };
+
+            match result {
+                Err(err) if is_too_many_open_files(&err) && attempt < MAX_RETRIES => {
+                    attempt += 1;
+                    thread::sleep(delay);
+                    delay *= 2;
+                }
+                Err(err) if is_locked(&err) => {
+                    let locked_since = *locked_since.get_or_insert_with(Instant::now);
+                    let remaining = LOCKED_RETRY_TIMEOUT.saturating_sub(locked_since.elapsed());
+                    if remaining.is_zero() {
+                        return Err(still_locked(err));
+                    }
+                    thread::sleep(delay.min(remaining));
+                    delay *= 2;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    pub fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        if let Some(trace) = self.answering_trace() {
+            let file = trace.files.get(path).ok_or_else(|| not_recorded(path))?;
+            if hash_contents(&file.contents) != file.hash {
+                warn!(
+                    "Recorded contents of '{}' do not match their hash",
+                    path.display()
+                );
+            }
+            return Ok(file.contents.clone());
+        }
+
+        let contents = self.with_permit(|| self.file_system.read_to_string(path))?;
+        self.record_file(path, &contents);
+        Ok(contents)
+    }
+
+    /// Reads a Luau module inside of a packages folder, which is subject to the maximum file size
+    pub fn read_module(&self, path: &Path) -> io::Result<String> {
+        let Some(max_file_size) = self.max_file_size else {
+            return self.read_to_string(path);
+        };
+        if self.answering_trace().is_some() {
+            let contents = self.read_to_string(path)?;
+            if contents.len() as u64 > max_file_size {
+                return Err(too_large(path, max_file_size));
+            }
+            return Ok(contents);
+        }
+
+        let contents = self.with_permit(|| self.file_system.read_limited(path, max_file_size))?;
+        self.record_file(path, &contents);
+        Ok(contents)
+    }
+
+    /// Reads all of stdin, which is recorded into the trace as the file `-`
+    pub fn read_stdin(&self) -> io::Result<String> {
+        let path = Path::new(STDIN_PATH);
+        if self.answering_trace().is_some() {
+            return self.read_to_string(path);
+        }
+
+        let contents = io::read_to_string(io::stdin())?;
+        self.record_file(path, &contents);
+        Ok(contents)
+    }
+
+    fn record_file(&self, path: &Path, contents: &str) {
+        self.record(|trace| {
+            trace.files.insert(
+                path.to_path_buf(),
+                RecordedFile {
+                    hash: hash_contents(contents),
+                    contents: contents.to_string(),
+                },
+            );
+        });
+    }
+
+    pub fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        match &self.mode {
+            Mode::Replay(_) => {
+                info!("Replaying, not writing to '{}'", path.display());
+                return Ok(());
+            }
+            Mode::InMemory { written, .. } | Mode::CaptureWrites(written) => {
+                written
+                    .lock()
+                    .unwrap()
+                    .insert(path.to_path_buf(), contents.to_string());
+                return Ok(());
+            }
+            _ => {}
+        }
+        if let Some(staged) = self.staged.lock().unwrap().as_mut() {
+            staged.insert(path.to_path_buf(), contents.to_string());
+            return Ok(());
+        }
+
+        self.with_permit(|| self.file_system.write(path, contents))
+    }
+
+    /// Moves a file, replacing any file already at `to`. Unlike writes, this is never staged
+    pub fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        match &self.mode {
+            Mode::Replay(_) => {
+                info!("Replaying, not renaming '{}'", from.display());
+                Ok(())
+            }
+            Mode::InMemory { written, .. } | Mode::CaptureWrites(written) => {
+                let mut written = written.lock().unwrap();
+                let contents = written.remove(from).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("'{}' was not written", from.display()),
+                    )
+                })?;
+                written.insert(to.to_path_buf(), contents);
+                Ok(())
+            }
+            _ => self.with_permit(|| self.file_system.rename(from, to)),
+        }
+    }
+
+    pub fn remove_file(&self, path: &Path) -> io::Result<()> {
+        match &self.mode {
+            Mode::Replay(_) => {
+                info!("Replaying, not removing '{}'", path.display());
+                Ok(())
+            }
+            Mode::InMemory { written, .. } | Mode::CaptureWrites(written) => {
+                written.lock().unwrap().remove(path);
+                Ok(())
+            }
+            _ => self.with_permit(|| self.file_system.remove_file(path)),
+        }
+    }
+
+    /// Creates a directory and any of its missing parents. Directories only exist implicitly when writes are kept in memory
+    pub fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+        match &self.mode {
+            Mode::Replay(_) => {
+                info!("Replaying, not creating '{}'", path.display());
+                Ok(())
+            }
+            Mode::InMemory { .. } | Mode::CaptureWrites(_) => Ok(()),
+            _ => self.with_permit(|| self.file_system.create_dir_all(path)),
+        }
+    }
+
+    /// The canonical form of a path, or its lexically normalized form if not canonicalizing
+    pub fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        if let Some(trace) = self.answering_trace() {
+            return match trace.canonicalized.get(path) {
+                Some(Some(canonical)) => Ok(canonical.clone()),
+                Some(None) => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("'{}' failed to canonicalize when recorded", path.display()),
+                )),
+                None => Err(not_recorded(path)),
+            };
+        }
+
+        let canonical = if self.lexical_paths {
+            Ok(normalize_lexically(path))
+        } else {
+            self.file_system
+                .canonicalize(path)
+                .map(|canonical| simplify(&canonical))
+        };
+        self.record(|trace| {
+            trace
+                .canonicalized
+                .insert(path.to_path_buf(), canonical.as_ref().ok().cloned());
+        });
+        canonical
+    }
+
+    pub fn path_kind(&self, path: &Path) -> PathKind {
+        if let Some(trace) = self.answering_trace() {
+            return trace
+                .path_kinds
+                .get(path)
+                .copied()
+                .unwrap_or(PathKind::Missing);
+        }
+
+        let kind = self.file_system.path_kind(path);
+        self.record(|trace| {
+            trace.path_kinds.insert(path.to_path_buf(), kind);
+        });
+        kind
+    }
+
+    /// Lists the entries of a directory, returning their paths and whether they are files
+    pub fn read_dir(&self, path: &Path) -> io::Result<Vec<RecordedDirEntry>> {
+        if let Some(trace) = self.answering_trace() {
+            return trace
+                .directories
+                .get(path)
+                .cloned()
+                .ok_or_else(|| not_recorded(path));
+        }
+
+        let entries = self.with_permit(|| self.file_system.read_dir(path))?;
+        self.record(|trace| {
+            trace
+                .directories
+                .insert(path.to_path_buf(), entries.clone());
+        });
+        Ok(entries)
+    }
+
+    /// Records the file a link's require expression resolved to. When replaying, warns if the
+    /// resolution differs from the one originally recorded
+    pub fn record_resolution(&self, link: &Path, target: &Path) {
+        match &self.mode {
+            Mode::Direct | Mode::InMemory { .. } | Mode::CaptureWrites(_) => {}
+            Mode::Record(trace) => {
+                trace
+                    .lock()
+                    .unwrap()
+                    .resolutions
+                    .insert(link.to_path_buf(), target.to_path_buf());
+            }
+            Mode::Replay(trace) => match trace.resolutions.get(link) {
+                Some(recorded) if recorded != target => warn!(
+                    "'{}' resolved to '{}', but was recorded resolving to '{}'",
+                    link.display(),
+                    target.display(),
+                    recorded.display()
+                ),
+                Some(_) => {}
+                None => warn!(
+                    "'{}' resolved to '{}', but had no recorded resolution",
+                    link.display(),
+                    target.display()
+                ),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_too_many_open_files() {
+        for code in TOO_MANY_OPEN_FILES_CODES {
+            assert!(is_too_many_open_files(&io::Error::from_raw_os_error(*code)));
+        }
+        assert!(!is_too_many_open_files(&io::Error::from(
+            io::ErrorKind::NotFound
+        )));
+    }
+
+    #[test]
+    fn keeps_captured_writes_in_memory() {
+        let path = std::env::temp_dir().join("wally-package-types-captured-write.lua");
+        let files = FileAccess::new(1).capturing_writes();
+        files.write(&path, "return {}\n").unwrap();
+
+        assert!(!path.exists());
+        assert_eq!(
+            files.written_files(),
+            Some(BTreeMap::from([(path, "return {}\n".to_string())]))
+        );
+    }
+
+    #[test]
+    fn retries_whilst_file_is_locked() {
+        let Some(code) = LOCKED_CODES.first() else {
+            return;
+        };
+
+        let files = FileAccess::new(1);
+        let attempts = Mutex::new(0);
+        let result = files.with_permit(|| {
+            let mut attempts = attempts.lock().unwrap();
+            *attempts += 1;
+            if *attempts < 3 {
+                Err(io::Error::from_raw_os_error(*code))
+            } else {
+                Ok(*attempts)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+    }
+
+    #[test]
+    fn fails_to_read_modules_over_the_maximum_size() {
+        let path = std::env::temp_dir().join("wally-package-types-large-module.lua");
+        std::fs::write(&path, "return {}\n").unwrap();
+
+        let files = FileAccess::new(1).with_max_file_size(8);
+        let err = files.read_module(&path).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("maximum of 8 bytes"));
+        assert_eq!(files.read_to_string(&path).unwrap(), "return {}\n");
+
+        let files = FileAccess::new(1).with_max_file_size(10);
+        assert_eq!(files.read_module(&path).unwrap(), "return {}\n");
+    }
+}
@@ -0,0 +1,105 @@
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use serde::Deserialize;
+
+/// The line endings written to link files
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum LineEndings {
+    /// Use the line endings the link file already uses
+    #[default]
+    Auto,
+    /// `\n`
+    #[serde(alias = "Unix")]
+    Unix,
+    /// `\r\n`
+    #[serde(alias = "Windows")]
+    Windows,
+}
+
+/// The subset of a `stylua.toml` which applies to generated link files
+#[derive(Deserialize, Debug, Default)]
+#[serde(default)]
+struct StyluaConfig {
+    line_endings: Option<LineEndings>,
+}
+
+/// How the contents of generated link files are laid out
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct OutputStyle {
+    pub line_endings: LineEndings,
+    /// Remove whitespace left at the end of lines by generated statements, as StyLua would
+    pub trim_trailing_whitespace: bool,
+}
+
+impl OutputStyle {
+    /// The style StyLua would format link files in given the contents of its configuration file
+    pub fn from_stylua_config(contents: &str) -> Result<Self> {
+        let config: StyluaConfig =
+            toml::from_str(contents).context("Failed to parse StyLua configuration")?;
+        Ok(Self {
+            // StyLua writes Unix line endings unless configured otherwise
+            line_endings: config.line_endings.unwrap_or(LineEndings::Unix),
+            trim_trailing_whitespace: true,
+        })
+    }
+
+    /// Lays out new contents of a link file in this style, given the contents it is replacing
+    pub fn apply(&self, contents: &str, original_contents: &str) -> String {
+        let windows = match self.line_endings {
+            LineEndings::Auto => original_contents.contains("\r\n"),
+            LineEndings::Unix => false,
+            LineEndings::Windows => true,
+        };
+
+        let contents = contents.replace("\r\n", "\n");
+        let contents = match self.trim_trailing_whitespace {
+            true => contents
+                .split('\n')
+                .map(str::trim_end)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            false => contents,
+        };
+        match windows {
+            true => contents.replace('\n', "\r\n"),
+            false => contents,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lays_out_contents_in_the_output_style() {
+        let generated = "local REQUIRED_MODULE = require(script.Parent.Module)\r\n\
+                         export type Value = REQUIRED_MODULE.Value \n\
+                         return REQUIRED_MODULE\r\n";
+
+        assert_eq!(
+            OutputStyle::default().apply(generated, "return require(script.Parent.Module)\r\n"),
+            "local REQUIRED_MODULE = require(script.Parent.Module)\r\n\
+             export type Value = REQUIRED_MODULE.Value \r\n\
+             return REQUIRED_MODULE\r\n"
+        );
+        assert_eq!(
+            OutputStyle::default().apply(generated, "return require(script.Parent.Module)\n"),
+            "local REQUIRED_MODULE = require(script.Parent.Module)\n\
+             export type Value = REQUIRED_MODULE.Value \n\
+             return REQUIRED_MODULE\n"
+        );
+
+        let stylua = OutputStyle::from_stylua_config(
+            "column_width = 120\nline_endings = \"Windows\"\nindent_type = \"Tabs\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            stylua.apply(generated, ""),
+            "local REQUIRED_MODULE = require(script.Parent.Module)\r\n\
+             export type Value = REQUIRED_MODULE.Value\r\n\
+             return REQUIRED_MODULE\r\n"
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt::Write;
+
+use crate::report::{Report, ThunkReport, ThunkStatus};
+
+/// Escapes text for inclusion in HTML element content or attribute values
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;}\
+section{border:1px solid #ddd;border-radius:4px;margin:1em 0;padding:0.5em 1em;}\
+h2{font-family:monospace;font-size:1em;}\
+.status{font-weight:bold;}\
+.mutated{color:#1a7f37;}.skipped,.unchanged{color:#9a6700;}.failed,.denied{color:#cf222e;}\
+pre{background:#f6f8fa;overflow-x:auto;padding:0.5em;}";
+
+fn status_label(status: &ThunkStatus) -> (&'static str, &'static str) {
+    match status {
+        ThunkStatus::Mutated { .. } => ("mutated", "Mutated"),
+        ThunkStatus::Unchanged => ("unchanged", "Unchanged"),
+        ThunkStatus::UpToDate { .. } => ("unchanged", "Up to date"),
+        ThunkStatus::Skipped { .. } => ("skipped", "Skipped"),
+        ThunkStatus::Failed { .. } => ("failed", "Failed"),
+        ThunkStatus::NotALink => ("unchanged", "Not a link file"),
+        ThunkStatus::Cleaned => ("mutated", "Cleaned"),
+    }
+}
+
+fn render_thunk(html: &mut String, report: &Report, thunk: &ThunkReport) {
+    let (class, label) = status_label(&thunk.status);
+    let _ = writeln!(html, "<section>");
+    let _ = writeln!(
+        html,
+        "<h2>{}</h2>",
+        escape(&thunk.path.display().to_string())
+    );
+    let _ = writeln!(html, "<p class=\"status {class}\">{label}</p>");
+
+    if let Some(target) = &thunk.target {
+        let _ = writeln!(
+            html,
+            "<p>Target: <code>{}</code></p>",
+            escape(&target.display().to_string())
+        );
+    }
+
+    match &thunk.status {
+        ThunkStatus::Mutated { exported_types } | ThunkStatus::UpToDate { exported_types }
+            if !exported_types.is_empty() =>
+        {
+            let _ = writeln!(html, "<ul>");
+            for exported_type in exported_types {
+                let _ = writeln!(
+                    html,
+                    "<li><code>{}</code></li>",
+                    escape(&exported_type.signature)
+                );
+            }
+            let _ = writeln!(html, "</ul>");
+        }
+        ThunkStatus::Skipped { reason } => {
+            let _ = writeln!(html, "<p>{}</p>", escape(reason));
+        }
+        ThunkStatus::Failed { error, .. } => {
+            let _ = writeln!(html, "<p>{}</p>", escape(error));
+        }
+        _ => (),
+    }
+
+    let warnings: Vec<_> = report
+        .warnings
+        .iter()
+        .filter(|warning| warning.path == thunk.path)
+        .collect();
+    if !warnings.is_empty() {
+        let _ = writeln!(html, "<ul>");
+        for warning in warnings {
+            let class = if warning.denied {
+                " class=\"denied\""
+            } else {
+                ""
+            };
+            let _ = writeln!(
+                html,
+                "<li{class}>{} [{}]</li>",
+                escape(&warning.message),
+                warning.category
+            );
+        }
+        let _ = writeln!(html, "</ul>");
+    }
+
+    if let Some(diff) = &thunk.diff {
+        let _ = writeln!(html, "<pre>{}</pre>", escape(diff));
+    }
+
+    let _ = writeln!(html, "</section>");
+}
+
+/// Renders a report as a self-contained static HTML page
+pub fn render(report: &Report) -> String {
+    let mut html = String::new();
+    let _ = writeln!(html, "<!DOCTYPE html>");
+    let _ = writeln!(html, "<html lang=\"en\">");
+    let _ = writeln!(html, "<head>");
+    let _ = writeln!(html, "<meta charset=\"utf-8\">");
+    let _ = writeln!(html, "<title>wally-package-types report</title>");
+    let _ = writeln!(html, "<style>{STYLE}</style>");
+    let _ = writeln!(html, "</head>");
+    let _ = writeln!(html, "<body>");
+    let _ = writeln!(html, "<h1>wally-package-types report</h1>");
+    if report.dry_run {
+        let _ = writeln!(html, "<p>Dry run: no link files were written</p>");
+    }
+
+    let mut thunks: Vec<_> = report.thunks.iter().collect();
+    thunks.sort_by(|a, b| a.path.cmp(&b.path));
+    for thunk in thunks {
+        render_thunk(&mut html, report, thunk);
+    }
+
+    if let Some(build_info) = &report.build_info {
+        let _ = writeln!(
+            html,
+            "<p>Generated by wally-package-types {}</p>",
+            escape(&build_info.summary())
+        );
+    }
+
+    let _ = writeln!(html, "</body>");
+    let _ = writeln!(html, "</html>");
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::link_mutator::ReExportedType;
+
+    #[test]
+    fn escapes_signatures_and_diffs() {
+        let report = Report {
+            dry_run: true,
+            warnings: vec![],
+            packages: vec![],
+            thunks: vec![ThunkReport {
+                path: PathBuf::from("Packages/Signal.lua"),
+                target: None,
+                status: ThunkStatus::Mutated {
+                    exported_types: vec![ReExportedType {
+                        name: "Signal".to_string(),
+                        signature: "Signal<T...>".to_string(),
+                    }],
+                },
+                diff: Some("+export type Signal<T...> = REQUIRED_MODULE.Signal<T...>".to_string()),
+            }],
+            build_info: None,
+        };
+
+        let html = render(&report);
+        assert!(html.contains("<code>Signal&lt;T...&gt;</code>"));
+        assert!(html.contains("<pre>+export type Signal&lt;T...&gt;"));
+        assert!(!html.contains("Signal<T...>"));
+    }
+}
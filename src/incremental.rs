@@ -0,0 +1,184 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::info;
+use serde::{Deserialize, Serialize};
+
+use crate::file_access::FileAccess;
+use crate::link_mutator::ReExportedType;
+use crate::report::{Report, ThunkReport, ThunkStatus};
+use crate::trace::{hash_contents, PathKind};
+
+/// The name of the cache file written by `--incremental`, which is kept next to the packages folder
+pub const CACHE_FILE_NAME: &str = ".wally-package-types-cache.json";
+
+/// The state of a link file and the module it links to when it was last brought up to date
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CachedLink {
+    pub link_hash: String,
+    pub target: PathBuf,
+    pub target_hash: String,
+    pub exported_types: Vec<ReExportedType>,
+}
+
+/// Remembers which link files were up to date at the end of a run, so that later runs can skip them
+/// if neither they nor the modules they link to have changed since
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct IncrementalCache {
+    /// Identifies the version and options which produced the cache, as they also affect the generated links
+    pub fingerprint: String,
+    pub links: BTreeMap<PathBuf, CachedLink>,
+}
+
+fn hash_file(path: &Path, files: &FileAccess) -> Option<String> {
+    files
+        .read_to_string(path)
+        .ok()
+        .map(|contents| hash_contents(&contents))
+}
+
+impl IncrementalCache {
+    /// Loads the cache, starting afresh if it is missing, unreadable, or was produced with different options
+    pub fn load(path: &Path, fingerprint: &str, files: &FileAccess) -> Self {
+        let empty = Self {
+            fingerprint: fingerprint.to_string(),
+            ..Default::default()
+        };
+        if files.path_kind(path) != PathKind::File {
+            return empty;
+        }
+
+        match files
+            .read_to_string(path)
+            .ok()
+            .and_then(|contents| serde_json::from_str::<Self>(&contents).ok())
+        {
+            Some(cache) if cache.fingerprint == fingerprint => cache,
+            Some(_) => {
+                info!(
+                    "Options or version changed since the last run, ignoring the incremental cache"
+                );
+                empty
+            }
+            None => {
+                info!("Failed to read the incremental cache, ignoring it");
+                empty
+            }
+        }
+    }
+
+    pub fn save(&self, path: &Path, files: &FileAccess) -> Result<()> {
+        let contents =
+            serde_json::to_string_pretty(self).context("Failed to serialize incremental cache")?;
+        files
+            .write(path, &contents)
+            .with_context(|| format!("Failed to write incremental cache '{}'", path.display()))
+    }
+
+    /// Returns the report of a link file which is unchanged since it was last brought up to date,
+    /// or `None` if it needs to be mutated again
+    pub fn unchanged_link(&self, path: &Path, files: &FileAccess) -> Option<ThunkReport> {
+        let cached = self.links.get(path)?;
+        if hash_file(path, files)? != cached.link_hash
+            || hash_file(&cached.target, files)? != cached.target_hash
+        {
+            return None;
+        }
+
+        let status = if cached.exported_types.is_empty() {
+            ThunkStatus::Unchanged
+        } else {
+            ThunkStatus::UpToDate {
+                exported_types: cached.exported_types.clone(),
+            }
+        };
+        Some(ThunkReport {
+            path: path.to_path_buf(),
+            target: Some(cached.target.clone()),
+            status,
+            diff: None,
+        })
+    }
+
+    /// Records the state of every link file which is up to date at the end of a run. Link files which
+    /// emitted warnings are left out, so that their warnings are emitted again by the next run
+    pub fn update(&mut self, report: &Report, files: &FileAccess) {
+        let warned: BTreeSet<&Path> = report
+            .warnings
+            .iter()
+            .map(|warning| warning.path.as_path())
+            .collect();
+
+        self.links.clear();
+        for thunk in &report.thunks {
+            let exported_types = match &thunk.status {
+                ThunkStatus::Mutated { exported_types }
+                | ThunkStatus::UpToDate { exported_types } => exported_types.clone(),
+                ThunkStatus::Unchanged => Vec::new(),
+                _ => continue,
+            };
+            let Some(target) = &thunk.target else {
+                continue;
+            };
+            if warned.contains(thunk.path.as_path()) {
+                continue;
+            }
+
+            if let (Some(link_hash), Some(target_hash)) =
+                (hash_file(&thunk.path, files), hash_file(target, files))
+            {
+                self.links.insert(
+                    thunk.path.clone(),
+                    CachedLink {
+                        link_hash,
+                        target: target.clone(),
+                        target_hash,
+                        exported_types,
+                    },
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn skips_links_unchanged_since_last_run() {
+        let directory = std::env::temp_dir().join("wally-package-types-incremental");
+        std::fs::create_dir_all(&directory).unwrap();
+        let link = directory.join("Signal.lua");
+        let target = directory.join("signal.lua");
+        std::fs::write(&link, "return require(script.Parent.signal)\n").unwrap();
+        std::fs::write(&target, "return {}\n").unwrap();
+
+        let files = FileAccess::new(1);
+        let mut cache = IncrementalCache::default();
+        cache.update(
+            &Report {
+                thunks: vec![ThunkReport {
+                    path: link.clone(),
+                    target: Some(target.clone()),
+                    status: ThunkStatus::Unchanged,
+                    diff: None,
+                }],
+                ..Default::default()
+            },
+            &files,
+        );
+        assert!(matches!(
+            cache.unchanged_link(&link, &files).unwrap().status,
+            ThunkStatus::Unchanged
+        ));
+
+        std::fs::write(&target, "export type Value = number\nreturn {}\n").unwrap();
+        assert!(cache.unchanged_link(&link, &files).is_none());
+    }
+}
@@ -1,6 +1,48 @@
+mod build_info;
 mod command;
+mod config;
+mod declaration_cache;
+mod declarations;
+mod diagnostics;
+mod dom;
+mod failure;
+mod file_access;
+mod formatting;
+mod html_report;
+mod incremental;
 mod link_mutator;
+mod lockfile;
+mod luaurc;
+mod memory_fs;
+mod path_normalization;
+mod progress;
+mod report;
 mod require_parser;
+mod resolution_error;
 mod sourcemap;
+mod sourcemap_command;
+mod strictness;
+mod string_require;
+mod suggestions;
+mod summary;
+mod timings;
+mod trace;
+mod transaction;
+mod watchdog;
+mod workspace_edit;
 
-pub use command::Command;
+pub use build_info::BuildInfo;
+pub use command::{mutate_file_system, Command};
+pub use declarations::{exported_types_from_source, ExportedType, GenericParameter};
+pub use diagnostics::{EmittedWarning, WarningCategory};
+pub use dom::{mutate_instance_tree, InstanceTree};
+pub use failure::{exit_code, FailureClass};
+pub use file_access::{FileSystem, StdFileSystem};
+pub use link_mutator::{
+    MutateLinkOptions, ReExportedType, ShadowedBuiltinBehaviour, TypeOverrides,
+};
+pub use memory_fs::MemoryFileSystem;
+pub use progress::LogWriter;
+pub use report::{Report, ThunkReport, ThunkStatus};
+pub use sourcemap::{diff_sourcemaps, Descendants, SourcemapDiff, SourcemapNode};
+pub use trace::{PathKind, RecordedDirEntry};
@@ -1,26 +1,167 @@
+use std::collections::BTreeMap;
+
 use anyhow::{bail, Result};
+use clap::ValueEnum;
 use full_moon::{
     ast::{
         luau::{
-            ExportedTypeDeclaration, GenericDeclaration, GenericDeclarationParameter,
-            GenericParameterInfo, IndexedTypeInfo, TypeInfo,
+            ExportedTypeDeclaration, ExportedTypeFunction, GenericDeclaration,
+            GenericDeclarationParameter, GenericParameterInfo, IndexedTypeInfo, TypeFieldKey,
+            TypeInfo,
         },
         punctuated::{Pair, Punctuated},
         span::ContainedSpan,
-        Ast, Expression, LastStmt, LocalAssignment, Return, Stmt,
+        Ast, Call, Expression, FunctionArgs, LastStmt, LocalAssignment, Parameter, Return, Stmt,
+        Suffix, Var,
     },
+    node::Node,
     tokenizer::{Token, TokenReference, TokenType},
 };
+use serde::{Deserialize, Serialize};
+
+use crate::declarations::ExportedType;
+use crate::diagnostics::{Warning, WarningCategory};
+use crate::failure::LuauParseError;
+use crate::require_parser::{
+    is_require, local_link_require, match_require, rewrap_expression, unwrap_expression,
+};
+
+/// Words reserved by Luau, which cannot be used as names
+const RESERVED_WORDS: &[&str] = &[
+    "and", "break", "do", "else", "elseif", "end", "false", "for", "function", "if", "in", "local",
+    "nil", "not", "or", "repeat", "return", "then", "true", "until", "while",
+];
+
+/// Whether a name is a valid Luau identifier, so it can be declared as a type or indexed with `.name`
+/// rather than `["name"]`
+pub fn is_identifier(name: &str) -> bool {
+    !RESERVED_WORDS.contains(&name)
+        && name
+            .chars()
+            .next()
+            .is_some_and(|char| char.is_ascii_alphabetic() || char == '_')
+        && name
+            .chars()
+            .all(|char| char.is_ascii_alphanumeric() || char == '_')
+}
+
+/// Builtin Luau types and ambient Roblox types which a re-exported type could shadow
+const BUILTIN_TYPE_NAMES: &[&str] = &[
+    "any",
+    "nil",
+    "boolean",
+    "number",
+    "string",
+    "thread",
+    "buffer",
+    "vector",
+    "userdata",
+    "unknown",
+    "never",
+    "Instance",
+    "Enum",
+    "EnumItem",
+    "Enums",
+    "Axes",
+    "BrickColor",
+    "CFrame",
+    "Color3",
+    "ColorSequence",
+    "DateTime",
+    "Faces",
+    "Font",
+    "NumberRange",
+    "NumberSequence",
+    "PhysicalProperties",
+    "Random",
+    "Ray",
+    "RBXScriptConnection",
+    "RBXScriptSignal",
+    "Rect",
+    "Region3",
+    "TweenInfo",
+    "UDim",
+    "UDim2",
+    "Vector2",
+    "Vector3",
+];
+
+/// What to do with a re-exported type whose name shadows a builtin type
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ShadowedBuiltinBehaviour {
+    /// Re-export the type under its original name
+    #[default]
+    Keep,
+    /// Do not re-export the type
+    Skip,
+    /// Re-export the type with a trailing underscore appended to its name
+    Rename,
+}
+
+/// Changes to the types re-exported from a package, e.g. to avoid colliding with types declared in the project
+#[derive(Deserialize, Clone, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct TypeOverrides {
+    /// Exported types which are not re-exported
+    pub exclude: Vec<String>,
+    /// Exported types which are re-exported under another name, keyed by their original name
+    pub rename: BTreeMap<String, String>,
+}
+
+/// Options controlling how a link file is mutated
+#[derive(Clone, Debug, Default)]
+pub struct MutateLinkOptions {
+    pub shadowed_builtins: ShadowedBuiltinBehaviour,
+    /// Also export a type holding the type of the whole required module, named after the link file
+    pub namespace_types: bool,
+    /// When the linked module only re-exports the types of another module, re-export them from the module
+    /// they are declared in instead
+    pub follow_re_exports: bool,
+    /// Changes to the types re-exported from the linked package
+    pub type_overrides: TypeOverrides,
+}
 
-/// Finds all exported type declarations from a give source file
+/// Stands in for an exported type function with a type declaration taking a generic for each of its parameters,
+/// e.g. `export type function Foo(a, b)` as `export type Foo<a, b> = unknown`, so it is re-exported as
+/// `export type Foo<a, b> = REQUIRED_MODULE.Foo<a, b>`. Type functions taking `...` cannot be re-exported
+fn type_function_declaration(stmt: &ExportedTypeFunction) -> Option<ExportedTypeDeclaration> {
+    let type_function = stmt.type_function();
+    let parameters = type_function
+        .function_body()
+        .parameters()
+        .iter()
+        .map(|parameter| match parameter {
+            Parameter::Name(name) => Some(name.token().to_string()),
+            _ => None,
+        })
+        .collect::<Option<Vec<_>>>()?;
+    let generics = match parameters.is_empty() {
+        true => String::new(),
+        false => format!("<{}>", parameters.join(", ")),
+    };
+
+    let code = format!(
+        "export type {}{generics} = unknown",
+        type_function.function_name().token()
+    );
+    // The original export token is kept, so that the declaration points at the type function in the source
+    // and keeps its doc comments
+    match full_moon::parse(&code).ok()?.nodes().stmts().next()? {
+        Stmt::ExportedTypeDeclaration(declaration) => Some(
+            declaration
+                .clone()
+                .with_export_token(stmt.export_token().clone()),
+        ),
+        _ => None,
+    }
+}
+
+/// Finds all exported type declarations from a give source file, including type functions
 pub fn type_declarations_from_source(code: &str) -> Result<Vec<ExportedTypeDeclaration>> {
     let parsed_module = match full_moon::parse(code) {
         Ok(parsed_code) => parsed_code,
-        Err(errors) => bail!(errors
-            .iter()
-            .map(|err| err.to_string())
-            .collect::<Vec<_>>()
-            .join("\n")),
+        Err(errors) => bail!(LuauParseError::new(&errors)),
     };
 
     Ok(parsed_module
@@ -28,37 +169,393 @@ pub fn type_declarations_from_source(code: &str) -> Result<Vec<ExportedTypeDecla
         .stmts()
         .filter_map(|stmt| match stmt {
             Stmt::ExportedTypeDeclaration(stmt) => Some(stmt.clone()),
+            Stmt::ExportedTypeFunction(stmt) => type_function_declaration(stmt),
             _ => None,
         })
         .collect())
 }
 
-fn should_keep_default_type(type_info: &TypeInfo, resolved_types: &[String]) -> bool {
-    // TODO: we could be more clever here, but for now we keep it simple
-    match type_info {
-        TypeInfo::Basic(name) => resolved_types.contains(&name.token().to_string()),
-        TypeInfo::Boolean(_) => true,
-        _ => false,
+/// Builtin Luau type functions, such as `keyof<T>`, which are available in any file
+const BUILTIN_TYPE_FUNCTIONS: &[&str] = &[
+    "keyof",
+    "rawkeyof",
+    "index",
+    "rawget",
+    "getmetatable",
+    "setmetatable",
+    "add",
+    "sub",
+    "mul",
+    "div",
+    "idiv",
+    "pow",
+    "mod",
+    "concat",
+    "unm",
+    "len",
+    "lt",
+    "le",
+    "eq",
+    "not",
+    "and",
+    "or",
+];
+
+/// The name the link file usually binds the required module to
+const REQUIRED_MODULE: &str = "REQUIRED_MODULE";
+
+/// Collects the names of the generic parameters a type declaration declares
+fn generic_parameter_names(stmt: &ExportedTypeDeclaration) -> Vec<String> {
+    stmt.type_declaration()
+        .generics()
+        .map_or(vec![], |generics| {
+            generics
+                .generics()
+                .iter()
+                .map(|generic| match generic.parameter() {
+                    GenericParameterInfo::Name(name) => name.token().to_string(),
+                    GenericParameterInfo::Variadic { name, .. } => name.token().to_string(),
+                    other => unreachable!("unknown node: {:?}", other),
+                })
+                .collect()
+        })
+}
+
+/// The module required by a link file: the local it is bound to, and the names of the types it exports
+struct RequiredModule {
+    local_name: String,
+    type_names: Vec<String>,
+}
+
+impl RequiredModule {
+    /// Binds the module to `REQUIRED_MODULE`, unless a type or generic parameter declared in the link file
+    /// is already called that, in which case a numbered suffix is added until the name is free
+    fn new(
+        stmts: &[ExportedTypeDeclaration],
+        namespace: Option<&str>,
+        warnings: &mut Vec<Warning>,
+    ) -> Self {
+        let (type_names, declared_names) = Self::declared_names(stmts, namespace);
+        let mut local_name = REQUIRED_MODULE.to_string();
+        let mut suffix = 0;
+        while declared_names.contains(&local_name) {
+            suffix += 1;
+            local_name = format!("{REQUIRED_MODULE}_{suffix}");
+        }
+        if suffix > 0 {
+            warnings.push(Warning::new(
+                WarningCategory::NamingCollisions,
+                format!("'{REQUIRED_MODULE}' is already declared by the required module, binding it to '{local_name}' instead"),
+            ));
+        }
+
+        Self {
+            local_name,
+            type_names,
+        }
+    }
+
+    /// Binds the module to a local the link file already declares, unless a type or generic parameter declared in
+    /// the link file is called the same
+    fn bound_to(
+        stmts: &[ExportedTypeDeclaration],
+        namespace: Option<&str>,
+        local_name: &str,
+    ) -> Option<Self> {
+        let (type_names, declared_names) = Self::declared_names(stmts, namespace);
+        (!declared_names.iter().any(|name| name == local_name)).then(|| Self {
+            local_name: local_name.to_string(),
+            type_names,
+        })
+    }
+
+    /// The names of the exported types, and of every type and generic parameter declared in the link file
+    fn declared_names(
+        stmts: &[ExportedTypeDeclaration],
+        namespace: Option<&str>,
+    ) -> (Vec<String>, Vec<String>) {
+        let type_names: Vec<String> = stmts
+            .iter()
+            .map(|stmt| stmt.type_declaration().type_name().token().to_string())
+            .collect();
+        let mut declared_names = type_names.clone();
+        declared_names.extend(stmts.iter().flat_map(generic_parameter_names));
+        declared_names.extend(namespace.map(String::from));
+        (type_names, declared_names)
     }
+
+    fn local_token(&self) -> Token {
+        Token::new(TokenType::Identifier {
+            identifier: self.local_name.as_str().into(),
+        })
+    }
+}
+
+/// Creates a `REQUIRED_MODULE.X` type referencing a type exported by the required module
+fn qualify_module_type(
+    name: &TokenReference,
+    generics: Option<(&ContainedSpan, Punctuated<TypeInfo>)>,
+    module: &RequiredModule,
+) -> TypeInfo {
+    let base = TokenReference::new(
+        vec![],
+        name.token().clone(),
+        name.trailing_trivia().cloned().collect(),
+    );
+
+    TypeInfo::Module {
+        module: TokenReference::new(
+            name.leading_trivia().cloned().collect(),
+            module.local_token(),
+            vec![],
+        ),
+        punctuation: TokenReference::symbol(".").unwrap(),
+        type_info: Box::new(match generics {
+            Some((arrows, generics)) => IndexedTypeInfo::Generic {
+                base,
+                arrows: arrows.clone(),
+                generics,
+            },
+            None => IndexedTypeInfo::Basic(base),
+        }),
+    }
+}
+
+fn resolve_punctuated<T>(
+    punctuated: &Punctuated<T>,
+    mut resolve: impl FnMut(&T) -> Option<T>,
+) -> Option<Punctuated<T>> {
+    punctuated
+        .pairs()
+        .map(|pair| match pair {
+            Pair::Punctuated(value, punctuation) => {
+                Some(Pair::Punctuated(resolve(value)?, punctuation.clone()))
+            }
+            Pair::End(value) => Some(Pair::End(resolve(value)?)),
+        })
+        .collect()
+}
+
+/// Resolves a default generic type so that it can be used in the link file. Generic parameters, builtin types
+/// and literals are kept as is, whilst types exported by the required module are rewritten to `REQUIRED_MODULE.X`.
+/// Returns `None` if any part of the type cannot be resolved
+fn resolve_default_type(
+    type_info: &TypeInfo,
+    resolved_types: &[String],
+    module: &RequiredModule,
+) -> Option<TypeInfo> {
+    let resolve = |type_info: &TypeInfo| resolve_default_type(type_info, resolved_types, module);
+
+    Some(match type_info {
+        TypeInfo::Basic(name) => {
+            let name_string = name.token().to_string();
+            if resolved_types.contains(&name_string) {
+                type_info.clone()
+            } else if module.type_names.contains(&name_string) {
+                qualify_module_type(name, None, module)
+            } else if BUILTIN_TYPE_NAMES.contains(&name_string.as_str()) {
+                type_info.clone()
+            } else {
+                return None;
+            }
+        }
+        TypeInfo::Boolean(_) | TypeInfo::String(_) => type_info.clone(),
+        TypeInfo::GenericPack { name, .. } | TypeInfo::VariadicPack { name, .. } => {
+            if !resolved_types.contains(&name.token().to_string()) {
+                return None;
+            }
+            type_info.clone()
+        }
+        TypeInfo::Generic {
+            base,
+            arrows,
+            generics,
+        } => {
+            let base_string = base.token().to_string();
+            let generics = resolve_punctuated(generics, resolve)?;
+            if module.type_names.contains(&base_string) {
+                qualify_module_type(base, Some((arrows, generics)), module)
+            } else if BUILTIN_TYPE_FUNCTIONS.contains(&base_string.as_str()) {
+                TypeInfo::Generic {
+                    base: base.clone(),
+                    arrows: arrows.clone(),
+                    generics,
+                }
+            } else {
+                return None;
+            }
+        }
+        TypeInfo::Array {
+            braces,
+            access,
+            type_info,
+        } => TypeInfo::Array {
+            braces: braces.clone(),
+            access: access.clone(),
+            type_info: Box::new(resolve(type_info)?),
+        },
+        TypeInfo::Optional {
+            base,
+            question_mark,
+        } => TypeInfo::Optional {
+            base: Box::new(resolve(base)?),
+            question_mark: question_mark.clone(),
+        },
+        TypeInfo::Variadic {
+            ellipsis,
+            type_info,
+        } => TypeInfo::Variadic {
+            ellipsis: ellipsis.clone(),
+            type_info: Box::new(resolve(type_info)?),
+        },
+        TypeInfo::Tuple { parentheses, types } => TypeInfo::Tuple {
+            parentheses: parentheses.clone(),
+            types: resolve_punctuated(types, resolve)?,
+        },
+        TypeInfo::Union(union) => TypeInfo::Union(
+            union
+                .clone()
+                .with_types(resolve_punctuated(union.types(), resolve)?),
+        ),
+        TypeInfo::Intersection(intersection) => TypeInfo::Intersection(
+            intersection
+                .clone()
+                .with_types(resolve_punctuated(intersection.types(), resolve)?),
+        ),
+        TypeInfo::Table { braces, fields } => TypeInfo::Table {
+            braces: braces.clone(),
+            fields: resolve_punctuated(fields, |field| {
+                let key = match field.key() {
+                    TypeFieldKey::IndexSignature { brackets, inner } => {
+                        TypeFieldKey::IndexSignature {
+                            brackets: brackets.clone(),
+                            inner: resolve(inner)?,
+                        }
+                    }
+                    key => key.clone(),
+                };
+                Some(
+                    field
+                        .clone()
+                        .with_key(key)
+                        .with_value(resolve(field.value())?),
+                )
+            })?,
+        },
+        TypeInfo::Callback {
+            generics,
+            parentheses,
+            arguments,
+            arrow,
+            return_type,
+        } => {
+            // Generics declared by the callback itself are resolvable within it
+            let mut callback_resolved_types = resolved_types.to_vec();
+            if let Some(generics) = generics {
+                callback_resolved_types.extend(generics.generics().iter().map(|generic| {
+                    match generic.parameter() {
+                        GenericParameterInfo::Name(name) => name.token().to_string(),
+                        GenericParameterInfo::Variadic { name, .. } => name.token().to_string(),
+                        other => unreachable!("unknown node: {:?}", other),
+                    }
+                }));
+            }
+            let resolve = |type_info: &TypeInfo| {
+                resolve_default_type(type_info, &callback_resolved_types, module)
+            };
+
+            TypeInfo::Callback {
+                generics: generics.clone(),
+                parentheses: parentheses.clone(),
+                arguments: resolve_punctuated(arguments, |argument| {
+                    Some(
+                        argument
+                            .clone()
+                            .with_type_info(resolve(argument.type_info())?),
+                    )
+                })?,
+                arrow: arrow.clone(),
+                return_type: Box::new(resolve(return_type)?),
+            }
+        }
+        // Types from other modules and `typeof` refer to locals of the required module, which the link cannot see
+        _ => return None,
+    })
 }
 
+/// Keeps the defaults of generics which can be resolved in the link file, and removes the rest
 fn strip_unknown_default_generics(
+    type_name: &str,
     generics: &GenericDeclaration,
     resolved_types: &[String],
+    module: &RequiredModule,
+    warnings: &mut Vec<Warning>,
 ) -> Punctuated<GenericDeclarationParameter> {
-    generics
+    let generics = generics
         .generics()
         .pairs()
         .map(|pair| {
-            pair.clone().map(|decl| match decl.default_type() {
-                Some(type_info) if should_keep_default_type(type_info, resolved_types) => decl,
-                _ => decl.with_default(None),
+            pair.clone().map(|decl| {
+                let Some(type_info) = decl.default_type() else {
+                    return decl;
+                };
+
+                if let Some(resolved) = resolve_default_type(type_info, resolved_types, module) {
+                    let equals = decl.equals().unwrap().clone();
+                    return decl.with_default(Some((equals, resolved)));
+                }
+
+                warnings.push(Warning::new(
+                    WarningCategory::StrippedDefaults,
+                    format!(
+                        "Default '{}' of '{}' in type '{type_name}' cannot be resolved in the link file, removing it",
+                        type_info.to_string().trim(),
+                        decl.parameter().to_string().trim(),
+                    ),
+                ));
+                decl.with_default(None)
             })
         })
-        .collect::<Punctuated<_>>()
+        .collect::<Punctuated<GenericDeclarationParameter>>();
+
+    // Generics with defaults must come after those without, including packs such as `T... = ()`, so removing the
+    // default of a generic means removing the defaults of those before it too
+    let Some(last_without_default) = generics
+        .iter()
+        .enumerate()
+        .filter(|(_, decl)| decl.default_type().is_none())
+        .map(|(index, _)| index)
+        .last()
+    else {
+        return generics;
+    };
+    generics
+        .into_pairs()
+        .enumerate()
+        .map(|(index, pair)| {
+            pair.map(|decl| match decl.default_type() {
+                Some(type_info) if index < last_without_default => {
+                    warnings.push(Warning::new(
+                        WarningCategory::StrippedDefaults,
+                        format!(
+                            "Default '{}' of '{}' in type '{type_name}' must be removed, as a generic after it has no default",
+                            type_info.to_string().trim(),
+                            decl.parameter().to_string().trim(),
+                        ),
+                    ));
+                    decl.with_default(None)
+                }
+                _ => decl,
+            })
+        })
+        .collect()
 }
 
-pub fn create_new_type_declaration(stmt: &ExportedTypeDeclaration) -> ExportedTypeDeclaration {
+fn create_new_type_declaration(
+    stmt: &ExportedTypeDeclaration,
+    module: &RequiredModule,
+    warnings: &mut Vec<Warning>,
+) -> ExportedTypeDeclaration {
     let type_info = match stmt.type_declaration().generics() {
         Some(generics) => IndexedTypeInfo::Generic {
             base: stmt.type_declaration().type_name().clone(),
@@ -87,55 +584,202 @@ pub fn create_new_type_declaration(stmt: &ExportedTypeDeclaration) -> ExportedTy
     };
 
     // Modify the original type declaration to remove the default generics, if they are not resolvable
-    let resolved_types = stmt
-        .type_declaration()
-        .generics()
-        .map_or(vec![], |generics| {
-            generics
-                .generics()
-                .iter()
-                .map(|generic| match generic.parameter() {
-                    GenericParameterInfo::Name(name) => name.token().to_string(),
-                    GenericParameterInfo::Variadic { name, .. } => name.token().to_string(),
-                    other => unreachable!("unknown node: {:?}", other),
-                })
-                .collect()
-        });
+    let resolved_types = generic_parameter_names(stmt);
 
     let original_type_declaration = match stmt.type_declaration().generics() {
-        Some(generics) => stmt.type_declaration().clone().with_generics(Some(
-            generics
+        Some(generics) => {
+            stmt.type_declaration()
                 .clone()
-                .with_generics(strip_unknown_default_generics(generics, &resolved_types)),
-        )),
+                .with_generics(Some(generics.clone().with_generics(
+                    strip_unknown_default_generics(
+                        &stmt.type_declaration().type_name().token().to_string(),
+                        generics,
+                        &resolved_types,
+                        module,
+                        warnings,
+                    ),
+                )))
+        }
         None => stmt.type_declaration().clone(),
     };
 
     // Can't use TypeDeclaration::new(), since it always panics
     let type_declaration = original_type_declaration.with_type_definition(TypeInfo::Module {
-        module: TokenReference::new(
-            vec![],
-            Token::new(TokenType::Identifier {
-                identifier: "REQUIRED_MODULE".into(),
-            }),
-            vec![],
-        ),
+        module: TokenReference::new(vec![], module.local_token(), vec![]),
         punctuation: TokenReference::symbol(".").unwrap(),
         type_info: Box::new(type_info),
     });
 
-    ExportedTypeDeclaration::new(type_declaration)
+    let export_token = TokenReference::new(
+        doc_comments(stmt),
+        Token::new(TokenType::Identifier {
+            identifier: "export".into(),
+        }),
+        vec![Token::new(TokenType::spaces(1))],
+    );
+    ExportedTypeDeclaration::new(type_declaration).with_export_token(export_token)
+}
+
+/// Whether a comment is a doc comment, i.e. `--- comment` or a moonwave `--[=[ comment ]=]` block
+fn is_doc_comment(token: &Token) -> bool {
+    match token.token_type() {
+        TokenType::SingleLineComment { comment } => comment.starts_with('-'),
+        TokenType::MultiLineComment { blocks, .. } => *blocks > 0,
+        _ => false,
+    }
+}
+
+/// Returns the doc comments directly above a type declaration, each followed by a newline, so that
+/// hovering the re-exported type still shows its documentation
+fn doc_comments(stmt: &ExportedTypeDeclaration) -> Vec<Token> {
+    let leading_trivia: Vec<&Token> = stmt.export_token().leading_trivia().collect();
+    let mut comments = Vec::new();
+    let mut newlines = 0;
+    for token in leading_trivia.into_iter().rev() {
+        match token.token_type() {
+            TokenType::Whitespace { characters } => {
+                newlines += characters.matches('\n').count();
+                // A blank line separates the comments from the declaration
+                if newlines > 1 {
+                    break;
+                }
+            }
+            _ if is_doc_comment(token) => {
+                comments.push(token.clone());
+                newlines = 0;
+            }
+            _ => break,
+        }
+    }
+
+    comments
+        .into_iter()
+        .rev()
+        .flat_map(|comment| {
+            [
+                comment,
+                Token::new(TokenType::Whitespace {
+                    characters: "\n".into(),
+                }),
+            ]
+        })
+        .collect()
+}
+
+/// Handles a re-exported type declaration whose name shadows a builtin type, returning `None` if it should be skipped
+fn handle_shadowed_builtin(
+    stmt: ExportedTypeDeclaration,
+    behaviour: ShadowedBuiltinBehaviour,
+    declared_names: &mut Vec<String>,
+    warnings: &mut Vec<Warning>,
+) -> Option<ExportedTypeDeclaration> {
+    let type_name = stmt.type_declaration().type_name().token().to_string();
+    if !BUILTIN_TYPE_NAMES.contains(&type_name.as_str()) {
+        return Some(stmt);
+    }
+
+    match behaviour {
+        ShadowedBuiltinBehaviour::Keep => {
+            warnings.push(Warning::new(
+                WarningCategory::ShadowedBuiltins,
+                format!("Re-exported type '{type_name}' shadows a builtin type"),
+            ));
+            Some(stmt)
+        }
+        ShadowedBuiltinBehaviour::Skip => {
+            warnings.push(Warning::new(
+                WarningCategory::ShadowedBuiltins,
+                format!("Re-exported type '{type_name}' shadows a builtin type, skipping"),
+            ));
+            None
+        }
+        ShadowedBuiltinBehaviour::Rename => {
+            let mut new_name = format!("{type_name}_");
+            while declared_names.contains(&new_name) {
+                warnings.push(Warning::new(
+                    WarningCategory::NamingCollisions,
+                    format!("Cannot rename re-exported type '{type_name}' to '{new_name}', as it is already declared"),
+                ));
+                new_name.push('_');
+            }
+            declared_names.push(new_name.clone());
+            warnings.push(Warning::new(
+                WarningCategory::ShadowedBuiltins,
+                format!(
+                    "Re-exported type '{type_name}' shadows a builtin type, renaming to '{new_name}'"
+                ),
+            ));
+            Some(with_type_name(stmt, &new_name))
+        }
+    }
+}
+
+/// Renames a re-exported type declaration, keeping the type it refers to
+fn with_type_name(stmt: ExportedTypeDeclaration, new_name: &str) -> ExportedTypeDeclaration {
+    let original_name = stmt.type_declaration().type_name();
+    let new_name = TokenReference::new(
+        original_name.leading_trivia().cloned().collect(),
+        Token::new(TokenType::Identifier {
+            identifier: new_name.into(),
+        }),
+        original_name.trailing_trivia().cloned().collect(),
+    );
+    let type_declaration = stmt.type_declaration().clone().with_type_name(new_name);
+    stmt.with_type_declaration(type_declaration)
+}
+
+/// Renames a re-exported type if it is renamed by the type overrides, unless its new name is already declared
+fn handle_renamed_type(
+    stmt: ExportedTypeDeclaration,
+    overrides: &TypeOverrides,
+    declared_names: &mut Vec<String>,
+    warnings: &mut Vec<Warning>,
+) -> ExportedTypeDeclaration {
+    let type_name = stmt.type_declaration().type_name().token().to_string();
+    let Some(new_name) = overrides.rename.get(&type_name) else {
+        return stmt;
+    };
+    if declared_names.contains(new_name) {
+        warnings.push(Warning::new(
+            WarningCategory::NamingCollisions,
+            format!("Cannot rename re-exported type '{type_name}' to '{new_name}', as it is already declared"),
+        ));
+        return stmt;
+    }
+    declared_names.push(new_name.clone());
+    with_type_name(stmt, new_name)
 }
 
 // Creates a list of re-exported type declarations from the type declarations found in the source file
 fn re_export_type_declarations(
-    stmts: Vec<ExportedTypeDeclaration>,
+    stmts: &[ExportedTypeDeclaration],
+    module: &RequiredModule,
+    options: &MutateLinkOptions,
+    warnings: &mut Vec<Warning>,
 ) -> Vec<(Stmt, Option<TokenReference>)> {
+    let mut declared_names = module.type_names.clone();
+
+    let overrides = &options.type_overrides;
+
     stmts
         .iter()
+        .filter(|stmt| {
+            let type_name = stmt.type_declaration().type_name().token().to_string();
+            !overrides.exclude.contains(&type_name)
+        })
+        .filter_map(|stmt| {
+            let new_stmt = create_new_type_declaration(stmt, module, warnings);
+            let new_stmt = handle_renamed_type(new_stmt, overrides, &mut declared_names, warnings);
+            handle_shadowed_builtin(
+                new_stmt,
+                options.shadowed_builtins,
+                &mut declared_names,
+                warnings,
+            )
+        })
         .map(|stmt| {
             (
-                Stmt::ExportedTypeDeclaration(create_new_type_declaration(stmt)),
+                Stmt::ExportedTypeDeclaration(stmt),
                 Some(TokenReference::new(
                     vec![],
                     Token::new(TokenType::Whitespace {
@@ -148,77 +792,445 @@ fn re_export_type_declarations(
         .collect()
 }
 
-/// Extracts a require expression out into a local variable of form `local REQUIRED_MODULE = ...`
-fn extract_require_into_local_stmt(
-    return_expressions: Punctuated<Expression>,
+/// Replaces the trailing trivia of a `require(...)` call with a single newline, so that statements can follow it
+fn with_trailing_newline(expression: &Expression) -> Expression {
+    with_trailing_trivia(
+        expression,
+        vec![Token::new(TokenType::Whitespace {
+            characters: "\n".into(),
+        })],
+    )
+}
+
+/// Replaces the trailing trivia of a `require(...)` call
+fn with_trailing_trivia(expression: &Expression, trailing_trivia: Vec<Token>) -> Expression {
+    let Expression::FunctionCall(call) = expression else {
+        return expression.clone();
+    };
+
+    let mut suffixes: Vec<Suffix> = call.suffixes().cloned().collect();
+    if let Some(Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses {
+        parentheses,
+        arguments,
+    }))) = suffixes.last()
+    {
+        let (open, close) = parentheses.tokens();
+        let close = TokenReference::new(
+            close.leading_trivia().cloned().collect(),
+            close.token().clone(),
+            trailing_trivia,
+        );
+        let args = FunctionArgs::Parentheses {
+            parentheses: ContainedSpan::new(open.clone(), close),
+            arguments: arguments.clone(),
+        };
+        *suffixes.last_mut().unwrap() = Suffix::Call(Call::AnonymousCall(args));
+    }
+
+    Expression::FunctionCall(call.clone().with_suffixes(suffixes))
+}
+
+/// Creates a local variable of form `local REQUIRED_MODULE = ...` holding the given require expressions
+fn create_require_local_stmt(
+    expressions: Punctuated<Expression>,
+    module: &RequiredModule,
 ) -> (Stmt, Option<TokenReference>) {
     (
         Stmt::LocalAssignment(
             LocalAssignment::new(
                 std::iter::once(Pair::End(TokenReference::new(
                     vec![],
-                    Token::new(TokenType::Identifier {
-                        identifier: "REQUIRED_MODULE".into(),
-                    }),
+                    module.local_token(),
                     vec![],
                 )))
                 .collect(),
             )
             .with_equal_token(Some(TokenReference::symbol(" = ").unwrap()))
-            .with_expressions(return_expressions),
+            .with_expressions(expressions),
         ),
         None,
     )
 }
 
-/// Creates a `return REQUIRED_MODULE` node
-fn create_return_require_variable() -> (LastStmt, Option<TokenReference>) {
+/// Extracts a require expression out into a local variable of form `local REQUIRED_MODULE = ...`.
+/// Any parentheses or type assertions around the require are left out, so that its types can be referenced
+fn extract_require_into_local_stmt(
+    return_expressions: &Punctuated<Expression>,
+    module: &RequiredModule,
+) -> (Stmt, Option<TokenReference>) {
+    create_require_local_stmt(
+        return_expressions
+            .pairs()
+            .map(|pair| {
+                pair.clone().map(|expression| {
+                    let require = unwrap_expression(&expression);
+                    if std::ptr::eq(require, &expression) {
+                        expression.clone()
+                    } else {
+                        with_trailing_newline(require)
+                    }
+                })
+            })
+            .collect(),
+        module,
+    )
+}
+
+/// Creates a `return REQUIRED_MODULE` node, keeping any parentheses or type assertions the original return had
+fn create_return_require_variable(
+    return_expressions: &Punctuated<Expression>,
+    module: &RequiredModule,
+) -> (LastStmt, Option<TokenReference>) {
+    let returned_expression = return_expressions.iter().next();
+    let is_wrapped = returned_expression
+        .is_some_and(|expression| !std::ptr::eq(unwrap_expression(expression), expression));
+
+    // Keep the whitespace the require had before any wrapping tokens, e.g. the space in `require(x) :: any`
+    let trailing_trivia = match returned_expression {
+        Some(expression) if is_wrapped => unwrap_expression(expression)
+            .tokens()
+            .max_by_key(|token| token.token().end_position().bytes())
+            .map_or(vec![], |token| token.trailing_trivia().cloned().collect()),
+        _ => vec![Token::new(TokenType::Whitespace {
+            characters: "\n".into(),
+        })],
+    };
+    let required_module = Expression::Symbol(TokenReference::new(
+        vec![],
+        module.local_token(),
+        trailing_trivia,
+    ));
+    let required_module = match returned_expression {
+        Some(expression) if is_wrapped => rewrap_expression(expression, required_module),
+        _ => required_module,
+    };
+
     (
         LastStmt::Return(
-            Return::new().with_returns(
-                std::iter::once(Pair::End(Expression::Symbol(TokenReference::new(
-                    vec![],
-                    Token::new(TokenType::Identifier {
-                        identifier: "REQUIRED_MODULE".into(),
-                    }),
-                    vec![Token::new(TokenType::Whitespace {
-                        characters: "\n".into(),
-                    })],
-                ))))
-                .collect(),
-            ),
+            Return::new().with_returns(std::iter::once(Pair::End(required_module)).collect()),
         ),
         None,
     )
 }
 
+/// A type re-exported by a link file
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct ReExportedType {
+    pub name: String,
+    /// The name and generics of the re-exported type, e.g. `Value<T, S = T>`
+    pub signature: String,
+}
+
+/// What is kept of a link file when it is regenerated
+pub enum LinkBody {
+    /// The expressions returned by a link of the form `return require(...)`
+    Return(Punctuated<Expression>),
+    /// Every statement of a link which assigns its require to a local before returning it. The types are
+    /// re-exported from that local where possible
+    Local {
+        local_name: String,
+        /// The expression assigned to the local
+        require: Box<Expression>,
+    },
+}
+
 pub enum MutateLinkResult {
-    Changed(Ast),
+    Changed {
+        ast: Box<Ast>,
+        /// The types re-exported by the new link
+        exported_types: Vec<ReExportedType>,
+        warnings: Vec<Warning>,
+    },
     Unchanged,
 }
 
-/// Given an old link and the contents of the file it points to, creates a new link source
+/// Creates a new link re-exporting the given type declarations. If a separate require is given for the types
+/// (e.g. when the link returns a member of the required module), the link's return is kept as is.
+/// Links which assign their require to a local keep all of their statements, with the re-exports appended
 pub fn mutate_link(
     parsed_code: Ast,
-    return_expressions: Punctuated<Expression>,
-    contents: &str,
+    body: LinkBody,
+    types_require: Option<Expression>,
+    type_declarations: &[ExportedTypeDeclaration],
+    namespace: Option<&str>,
+    options: &MutateLinkOptions,
 ) -> Result<MutateLinkResult> {
-    let type_declarations = type_declarations_from_source(contents)?;
-
-    if type_declarations.is_empty() {
+    let mut warnings = Vec::new();
+    let namespace = namespace.filter(|namespace| {
+        let collides = type_declarations
+            .iter()
+            .any(|stmt| stmt.type_declaration().type_name().token().to_string() == *namespace);
+        if collides {
+            warnings.push(Warning::new(
+                WarningCategory::NamingCollisions,
+                format!("The required module already exports a type named '{namespace}', not creating a namespace type"),
+            ));
+        }
+        !collides
+    });
+    // Names which are not valid type names, such as keywords, cannot be declared, so get no namespace type
+    let namespace = namespace.filter(|namespace| is_identifier(namespace));
+    if type_declarations.is_empty() && namespace.is_none() {
         return Ok(MutateLinkResult::Unchanged);
     }
 
+    // A local holding the required module itself, rather than a member of it or a type assertion, can be reused
+    let reused_local = match &body {
+        LinkBody::Local {
+            local_name,
+            require,
+        } if types_require.is_none() && matches!(**require, Expression::FunctionCall(_)) => {
+            RequiredModule::bound_to(type_declarations, namespace, local_name)
+        }
+        _ => None,
+    };
+    let reuses_local = reused_local.is_some();
+    let module = reused_local
+        .unwrap_or_else(|| RequiredModule::new(type_declarations, namespace, &mut warnings));
+    let mut re_exports =
+        re_export_type_declarations(type_declarations, &module, options, &mut warnings);
+    if let Some(namespace) = namespace {
+        re_exports.push(create_namespace_type_declaration(namespace, &module));
+    }
+    let exported_types = re_exports
+        .iter()
+        .filter_map(|(stmt, _)| match stmt {
+            Stmt::ExportedTypeDeclaration(stmt) => {
+                let exported_type = ExportedType::from_declaration(stmt);
+                Some(ReExportedType {
+                    signature: exported_type.signature(),
+                    name: exported_type.name,
+                })
+            }
+            _ => None,
+        })
+        .collect();
+
+    let (stmts, last_stmt) = match (body, types_require) {
+        (LinkBody::Return(return_expressions), Some(types_require)) => (
+            vec![create_require_local_stmt(
+                std::iter::once(Pair::End(types_require)).collect(),
+                &module,
+            )],
+            Some((
+                LastStmt::Return(Return::new().with_returns(return_expressions)),
+                None,
+            )),
+        ),
+        (LinkBody::Return(return_expressions), None) => (
+            vec![extract_require_into_local_stmt(
+                &return_expressions,
+                &module,
+            )],
+            Some(create_return_require_variable(&return_expressions, &module)),
+        ),
+        (LinkBody::Local { require, .. }, types_require) => {
+            let mut stmts: Vec<_> = parsed_code
+                .nodes()
+                .stmts_with_semicolon()
+                .cloned()
+                .collect();
+            if !reuses_local {
+                let types_require = types_require.unwrap_or_else(|| {
+                    let unwrapped = unwrap_expression(&require);
+                    if std::ptr::eq(unwrapped, &*require) {
+                        *require.clone()
+                    } else {
+                        with_trailing_newline(unwrapped)
+                    }
+                });
+                stmts.push(create_require_local_stmt(
+                    std::iter::once(Pair::End(types_require)).collect(),
+                    &module,
+                ));
+            }
+            (
+                stmts,
+                parsed_code.nodes().last_stmt_with_semicolon().cloned(),
+            )
+        }
+    };
+
     let new_nodes = parsed_code
         .nodes()
         .clone()
-        .with_stmts(
-            std::iter::once(extract_require_into_local_stmt(return_expressions))
-                .chain(re_export_type_declarations(type_declarations))
-                .collect(),
-        )
-        .with_last_stmt(Some(create_return_require_variable()));
-    Ok(MutateLinkResult::Changed(parsed_code.with_nodes(new_nodes)))
+        .with_stmts(stmts.into_iter().chain(re_exports).collect())
+        .with_last_stmt(last_stmt);
+    Ok(MutateLinkResult::Changed {
+        ast: Box::new(parsed_code.with_nodes(new_nodes)),
+        exported_types,
+        warnings,
+    })
+}
+
+/// Creates a type of form `export type Name = typeof(REQUIRED_MODULE)`, so that the types of the members of the
+/// required module can be referred to through a single type
+fn create_namespace_type_declaration(
+    name: &str,
+    module: &RequiredModule,
+) -> (Stmt, Option<TokenReference>) {
+    let code = format!("export type {name} = typeof({})\n", module.local_name);
+    let stmt = full_moon::parse(&code)
+        .expect("namespace type declaration should parse")
+        .nodes()
+        .stmts()
+        .next()
+        .expect("namespace type declaration should be a statement")
+        .clone();
+    (stmt, None)
+}
+
+/// Creates a declaration stub from a link file generated by [`mutate_link`], which re-exports the same types from
+/// the module at the given string require path rather than through the link's own require
+pub fn declaration_stub(generated: &Ast, require: &str) -> Option<Ast> {
+    let stmts: Vec<_> = generated.nodes().stmts_with_semicolon().cloned().collect();
+    // Links which keep their own statements may declare other locals before the one the types are re-exported from
+    let local_name = stmts.iter().find_map(|(stmt, _)| re_exported_local(stmt))?;
+    let binds_module = |stmt: &Stmt| match stmt {
+        Stmt::LocalAssignment(local) => {
+            local.names().len() == 1
+                && local
+                    .names()
+                    .iter()
+                    .next()
+                    .is_some_and(|name| name.token().to_string() == local_name)
+        }
+        _ => false,
+    };
+
+    let escaped = require.replace('\\', "\\\\").replace('"', "\\\"");
+    let template = full_moon::parse(&format!(
+        "local {local_name} = require(\"{escaped}\")\nreturn {local_name}\n"
+    ))
+    .ok()?;
+    let required_module = template.nodes().stmts_with_semicolon().next()?.clone();
+    if !stmts.iter().any(|(stmt, _)| binds_module(stmt)) {
+        return None;
+    }
+    let stmts = stmts
+        .into_iter()
+        .filter_map(|(stmt, semicolon)| match stmt {
+            _ if binds_module(&stmt) => Some(required_module.clone()),
+            // Other requires of the link cannot be resolved from the declarations directory
+            Stmt::LocalAssignment(local) if local.expressions().iter().any(is_require) => None,
+            stmt => Some((stmt, semicolon)),
+        })
+        .collect::<Vec<_>>();
+
+    let new_nodes = generated
+        .nodes()
+        .clone()
+        .with_stmts(stmts)
+        .with_last_stmt(template.nodes().last_stmt_with_semicolon().cloned());
+    Some(generated.clone().with_nodes(new_nodes))
+}
+
+/// The local a statement re-exports a type of, if it is a re-export generated by [`mutate_link`]
+fn re_exported_local(stmt: &Stmt) -> Option<String> {
+    let Stmt::ExportedTypeDeclaration(stmt) = stmt else {
+        return None;
+    };
+    match stmt.type_declaration().type_definition() {
+        TypeInfo::Module { module, .. } => Some(module.token().to_string()),
+        TypeInfo::Typeof { inner, .. } => Some(inner.to_string().trim().to_string()),
+        _ => None,
+    }
+}
+
+/// Given a link file previously generated by [`mutate_link`], recreates the original link, either
+/// `return require(...)` or the statements of a link which assigns its require to a local.
+/// Returns `None` if the file does not have the shape of a generated link
+pub fn restore_link(parsed_code: &Ast) -> Option<Ast> {
+    if local_link_require(parsed_code).is_some() {
+        return None;
+    }
+    restore_return_link(parsed_code).or_else(|| restore_local_link(parsed_code))
+}
+
+/// Recreates a `return require(...)` link, whose require was extracted into `REQUIRED_MODULE`
+fn restore_return_link(parsed_code: &Ast) -> Option<Ast> {
+    let mut stmts = parsed_code.nodes().stmts();
+    let Some(Stmt::LocalAssignment(local)) = stmts.next() else {
+        return None;
+    };
+    if local.names().len() != 1 || local.expressions().len() != 1 {
+        return None;
+    }
+    let local_name = local.names().iter().next()?.token().to_string();
+    let require = local.expressions().iter().next()?;
+    // Links which already assigned their require to a local keep it under its own name
+    if !local_name.starts_with(REQUIRED_MODULE) || match_require(require).is_err() {
+        return None;
+    }
+
+    // Every other statement must re-export a type of the required module
+    if !stmts.all(|stmt| re_exported_local(stmt).as_ref() == Some(&local_name)) {
+        return None;
+    }
+
+    let Some(LastStmt::Return(r#return)) = parsed_code.nodes().last_stmt() else {
+        return None;
+    };
+    if r#return.returns().len() != 1 {
+        return None;
+    }
+    let returned_expression = r#return.returns().iter().next()?;
+
+    // Links returning a member of the required module keep their original return, so only the local needs removing
+    let returns = match unwrap_expression(returned_expression) {
+        Expression::Var(Var::Name(symbol)) if symbol.token().to_string() == local_name => {
+            let require =
+                with_trailing_trivia(require, symbol.trailing_trivia().cloned().collect());
+            std::iter::once(Pair::End(rewrap_expression(returned_expression, require))).collect()
+        }
+        _ => r#return.returns().clone(),
+    };
+
+    let new_nodes = parsed_code
+        .nodes()
+        .clone()
+        .with_stmts(Vec::new())
+        .with_last_stmt(Some((
+            LastStmt::Return(r#return.clone().with_returns(returns)),
+            None,
+        )));
+    Some(parsed_code.clone().with_nodes(new_nodes))
+}
+
+/// Recreates a link which assigns its require to a local, by removing the re-exports appended to its statements,
+/// and the local they re-export from if it was added alongside them
+fn restore_local_link(parsed_code: &Ast) -> Option<Ast> {
+    let stmts: Vec<_> = parsed_code
+        .nodes()
+        .stmts_with_semicolon()
+        .cloned()
+        .collect();
+    let local_name = re_exported_local(&stmts.last()?.0)?;
+    let kept = stmts
+        .iter()
+        .rposition(|(stmt, _)| re_exported_local(stmt).as_ref() != Some(&local_name))
+        .map_or(0, |position| position + 1);
+
+    let restored = |stmts: &[(Stmt, Option<TokenReference>)]| {
+        let new_nodes = parsed_code.nodes().clone().with_stmts(stmts.to_vec());
+        parsed_code.clone().with_nodes(new_nodes)
+    };
+    let with_local = restored(&stmts[..kept]);
+    if local_link_require(&with_local).is_some_and(|require| require.local_name == local_name) {
+        return Some(with_local);
+    }
+
+    let Some((Stmt::LocalAssignment(local), _)) = kept.checked_sub(1).map(|last| &stmts[last])
+    else {
+        return None;
+    };
+    if local.names().iter().next()?.token().to_string() != local_name {
+        return None;
+    }
+    let without_local = restored(&stmts[..kept - 1]);
+    local_link_require(&without_local)
+        .is_some()
+        .then_some(without_local)
 }
 
 #[cfg(test)]
@@ -234,7 +1246,12 @@ mod tests {
         let type_declarations = type_declarations_from_source(code).unwrap();
         assert_eq!(type_declarations.len(), 1);
 
-        let reexported_type_declarations = re_export_type_declarations(type_declarations);
+        let reexported_type_declarations = re_export_type_declarations(
+            &type_declarations,
+            &RequiredModule::new(&type_declarations, None, &mut Vec::new()),
+            &MutateLinkOptions::default(),
+            &mut Vec::new(),
+        );
         assert_eq!(reexported_type_declarations.len(), 1);
 
         assert_eq!(
@@ -252,7 +1269,12 @@ mod tests {
         let type_declarations = type_declarations_from_source(code).unwrap();
         assert_eq!(type_declarations.len(), 1);
 
-        let reexported_type_declarations = re_export_type_declarations(type_declarations);
+        let reexported_type_declarations = re_export_type_declarations(
+            &type_declarations,
+            &RequiredModule::new(&type_declarations, None, &mut Vec::new()),
+            &MutateLinkOptions::default(),
+            &mut Vec::new(),
+        );
         assert_eq!(reexported_type_declarations.len(), 1);
 
         assert_eq!(
@@ -260,4 +1282,552 @@ mod tests {
             "export type Value<T, S > = REQUIRED_MODULE.Value<T, S >"
         );
     }
+
+    #[test]
+    fn renames_types_shadowing_builtins() {
+        let code = r"
+            export type Instance = Types.Instance
+            export type Object = Types.Object
+        ";
+
+        let type_declarations = type_declarations_from_source(code).unwrap();
+        let reexported_type_declarations = re_export_type_declarations(
+            &type_declarations,
+            &RequiredModule::new(&type_declarations, None, &mut Vec::new()),
+            &MutateLinkOptions {
+                shadowed_builtins: ShadowedBuiltinBehaviour::Rename,
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        );
+        assert_eq!(reexported_type_declarations.len(), 2);
+
+        assert_eq!(
+            reexported_type_declarations[0].0.to_string(),
+            "export type Instance_ = REQUIRED_MODULE.Instance "
+        );
+    }
+
+    #[test]
+    fn skips_types_shadowing_builtins() {
+        let code = r"
+            export type string = Types.string
+            export type Object = Types.Object
+        ";
+
+        let type_declarations = type_declarations_from_source(code).unwrap();
+        let reexported_type_declarations = re_export_type_declarations(
+            &type_declarations,
+            &RequiredModule::new(&type_declarations, None, &mut Vec::new()),
+            &MutateLinkOptions {
+                shadowed_builtins: ShadowedBuiltinBehaviour::Skip,
+                ..Default::default()
+            },
+            &mut Vec::new(),
+        );
+        assert_eq!(reexported_type_declarations.len(), 1);
+
+        assert_eq!(
+            reexported_type_declarations[0].0.to_string(),
+            "export type Object = REQUIRED_MODULE.Object "
+        );
+    }
+
+    fn re_export(code: &str) -> Vec<String> {
+        let type_declarations = type_declarations_from_source(code).unwrap();
+        re_export_type_declarations(
+            &type_declarations,
+            &RequiredModule::new(&type_declarations, None, &mut Vec::new()),
+            &MutateLinkOptions::default(),
+            &mut Vec::new(),
+        )
+        .into_iter()
+        .map(|(stmt, _)| stmt.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn re_exports_type_function_defaults() {
+        assert_eq!(
+            re_export(
+                r#"
+                export type Keys<T, K = keyof<T>> = Types.Keys<T, K>
+                export type Value<T, V = index<T, "value">> = Types.Value<T, V>
+                export type Raw<T, K, V = rawget<T, K>> = Types.Raw<T, K, V>
+                "#
+            ),
+            vec![
+                "export type Keys<T, K = keyof<T>> = REQUIRED_MODULE.Keys<T, K >",
+                "export type Value<T, V = index<T, \"value\">> = REQUIRED_MODULE.Value<T, V >",
+                "export type Raw<T, K, V = rawget<T, K>> = REQUIRED_MODULE.Raw<T, K, V >",
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_re_export_type_function_defaults_with_unknown_arguments() {
+        assert_eq!(
+            re_export(
+                r"
+                export type Keys<T, K = keyof<Object>> = Types.Keys<T, K>
+                "
+            ),
+            vec!["export type Keys<T, K > = REQUIRED_MODULE.Keys<T, K >"]
+        );
+    }
+
+    #[test]
+    fn re_exports_types_defined_with_type_functions() {
+        assert_eq!(
+            re_export(
+                r"
+                export type Keys = keyof<typeof(Defaults)>
+                "
+            ),
+            vec!["export type Keys = REQUIRED_MODULE.Keys "]
+        );
+    }
+
+    #[test]
+    fn qualifies_defaults_exported_by_the_module() {
+        assert_eq!(
+            re_export(
+                r"
+                export type AnyAction = { type: string }
+                export type Reducer<State = any, Action = AnyAction> = (State, Action) -> State
+                "
+            ),
+            vec![
+                "export type AnyAction = REQUIRED_MODULE.AnyAction ",
+                "export type Reducer<State = any, Action = REQUIRED_MODULE.AnyAction> = REQUIRED_MODULE.Reducer<State , Action >",
+            ]
+        );
+    }
+
+    #[test]
+    fn re_exports_composite_defaults() {
+        assert_eq!(
+            re_export(
+                r"
+                export type Map<K, V> = { [K]: V }
+                export type Store<T, S = { [string]: T }, E = string | number, M = Map<string, T>?> = Types.Store<T, S, E, M>
+                "
+            ),
+            vec![
+                "export type Map<K, V> = REQUIRED_MODULE.Map<K, V>",
+                "export type Store<T, S = { [string]: T }, E = string | number, M = REQUIRED_MODULE.Map<string, T>?> = REQUIRED_MODULE.Store<T, S , E , M >",
+            ]
+        );
+    }
+
+    #[test]
+    fn does_not_re_export_composite_defaults_with_unknown_types() {
+        assert_eq!(
+            re_export(
+                r"
+                export type Store<T, S = { [string]: Object }, F = (T) -> Types.Value> = Types.Store<T, S, F>
+                "
+            ),
+            vec!["export type Store<T, S , F > = REQUIRED_MODULE.Store<T, S , F >"]
+        );
+    }
+
+    #[test]
+    fn re_exports_generic_pack_defaults() {
+        assert_eq!(
+            re_export(
+                r"
+                export type Handler<Args... = ()> = (Args...) -> ()
+                export type Callback<T... = (string, Value, ...number)> = (T...) -> ()
+                export type Forward<A..., B... = A...> = (A...) -> B...
+                export type Value = number
+                "
+            ),
+            vec![
+                "export type Handler<Args... = ()> = REQUIRED_MODULE.Handler<Args... >",
+                "export type Callback<T... = (string, REQUIRED_MODULE.Value, ...number)> = REQUIRED_MODULE.Callback<T... >",
+                "export type Forward<A..., B... = A...> = REQUIRED_MODULE.Forward<A..., B... >",
+                "export type Value = REQUIRED_MODULE.Value ",
+            ]
+        );
+    }
+
+    #[test]
+    fn removes_defaults_before_generics_without_defaults() {
+        // `T = number` cannot be kept once the default of `U...` is removed, as it would then precede a generic
+        // without a default
+        assert_eq!(
+            re_export(
+                r"
+                export type Handler<T = number, U... = ...Unknown> = (T) -> U...
+                "
+            ),
+            vec!["export type Handler<T , U... > = REQUIRED_MODULE.Handler<T , U... >"]
+        );
+    }
+
+    fn mutate(link: &str, code: &str, options: &MutateLinkOptions) -> (String, Vec<Warning>) {
+        let parsed_code = full_moon::parse(link).unwrap();
+        let returns = crate::require_parser::link_return(&parsed_code)
+            .unwrap()
+            .returns()
+            .clone();
+        let type_declarations = type_declarations_from_source(code).unwrap();
+        match mutate_link(
+            parsed_code,
+            LinkBody::Return(returns),
+            None,
+            &type_declarations,
+            None,
+            options,
+        )
+        .unwrap()
+        {
+            MutateLinkResult::Changed { ast, warnings, .. } => (ast.to_string(), warnings),
+            MutateLinkResult::Unchanged => panic!("link was unchanged"),
+        }
+    }
+
+    #[test]
+    fn renames_required_module_local_colliding_with_declared_names() {
+        let (link, warnings) = mutate(
+            "return require(script.Parent.Module)\n",
+            r"
+            export type REQUIRED_MODULE = {}
+            export type Value<REQUIRED_MODULE_1 = REQUIRED_MODULE> = Types.Value<REQUIRED_MODULE_1>
+            ",
+            &MutateLinkOptions::default(),
+        );
+
+        assert_eq!(
+            link,
+            "local REQUIRED_MODULE_2 = require(script.Parent.Module)\n\
+             export type REQUIRED_MODULE = REQUIRED_MODULE_2.REQUIRED_MODULE \n\
+             export type Value<REQUIRED_MODULE_1 = REQUIRED_MODULE_2.REQUIRED_MODULE> = REQUIRED_MODULE_2.Value<REQUIRED_MODULE_1 >\n\
+             return REQUIRED_MODULE_2\n"
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].category, WarningCategory::NamingCollisions);
+    }
+
+    #[test]
+    fn renames_shadowed_builtins_without_colliding_with_exported_types() {
+        let (link, warnings) = mutate(
+            "return require(script.Parent.Module)\n",
+            r"
+            export type Instance = Types.Instance
+            export type Instance_ = Types.Instance_
+            ",
+            &MutateLinkOptions {
+                shadowed_builtins: ShadowedBuiltinBehaviour::Rename,
+                ..Default::default()
+            },
+        );
+
+        assert!(link.contains("export type Instance__ = REQUIRED_MODULE.Instance \n"));
+        assert!(link.contains("export type Instance_ = REQUIRED_MODULE.Instance_ \n"));
+        assert!(warnings
+            .iter()
+            .any(|warning| warning.category == WarningCategory::NamingCollisions));
+    }
+
+    #[test]
+    fn applies_type_overrides() {
+        let (link, warnings) = mutate(
+            "return require(script.Parent.Module)\n",
+            r"
+            export type Signal<T> = Types.Signal<T>
+            export type Connection = Types.Connection
+            export type Value = number
+            ",
+            &MutateLinkOptions {
+                type_overrides: TypeOverrides {
+                    exclude: vec!["Connection".to_string()],
+                    rename: BTreeMap::from([
+                        ("Signal".to_string(), "ScopedSignal".to_string()),
+                        ("Value".to_string(), "Signal".to_string()),
+                    ]),
+                },
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(
+            link,
+            "local REQUIRED_MODULE = require(script.Parent.Module)\n\
+             export type ScopedSignal<T> = REQUIRED_MODULE.Signal<T>\n\
+             export type Value = REQUIRED_MODULE.Value \n\
+             return REQUIRED_MODULE\n"
+        );
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].category, WarningCategory::NamingCollisions);
+    }
+
+    #[test]
+    fn preserves_doc_comments() {
+        let (link, _) = mutate(
+            "return require(script.Parent.Module)\n",
+            r"
+            --- A signal which can be fired
+            --- @within Signal
+            export type Signal = {}
+
+            --[=[
+                @type Connection
+            ]=]
+            export type Connection = {}
+
+            --- Not attached to a declaration
+
+            -- A regular comment
+            export type Value = {}
+            ",
+            &MutateLinkOptions::default(),
+        );
+
+        assert_eq!(
+            link,
+            "local REQUIRED_MODULE = require(script.Parent.Module)\n\
+             --- A signal which can be fired\n\
+             --- @within Signal\n\
+             export type Signal = REQUIRED_MODULE.Signal \n\
+             --[=[\n                @type Connection\n            ]=]\n\
+             export type Connection = REQUIRED_MODULE.Connection \n\
+             export type Value = REQUIRED_MODULE.Value \n\
+             return REQUIRED_MODULE\n"
+        );
+    }
+
+    #[test]
+    fn re_exports_type_functions() {
+        let (link, _) = mutate(
+            "return require(script.Parent.Module)\n",
+            r"
+            --- Makes every property of a table optional
+            export type function Partial(t)
+                return t
+            end
+            export type function Pick(t, keys)
+                return t
+            end
+            export type function Variadic(...)
+                return types.any
+            end
+            type function Private(t)
+                return t
+            end
+            ",
+            &MutateLinkOptions::default(),
+        );
+
+        assert_eq!(
+            link,
+            "local REQUIRED_MODULE = require(script.Parent.Module)\n\
+             --- Makes every property of a table optional\n\
+             export type Partial<t> = REQUIRED_MODULE.Partial<t>\n\
+             export type Pick<t, keys> = REQUIRED_MODULE.Pick<t, keys>\n\
+             return REQUIRED_MODULE\n"
+        );
+    }
+
+    #[test]
+    fn type_functions_keep_their_source_line() {
+        let type_declarations = type_declarations_from_source(
+            "export type Value = number\n\nexport type function Partial(t)\n    return t\nend\n",
+        )
+        .unwrap();
+
+        let lines = type_declarations
+            .iter()
+            .map(|stmt| stmt.export_token().token().start_position().line())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![1, 3]);
+    }
+
+    #[test]
+    fn restores_generated_links() {
+        for link in [
+            "return require(script.Parent.Module)\n",
+            "return require(script.Parent.Module) :: any\n",
+            "return (require(script.Parent.Module))\n",
+        ] {
+            let (generated, _) = mutate(
+                link,
+                "export type Value<T> = Types.Value<T>",
+                &MutateLinkOptions::default(),
+            );
+            let restored = restore_link(&full_moon::parse(&generated).unwrap()).unwrap();
+            assert_eq!(restored.to_string(), link);
+        }
+    }
+
+    #[test]
+    fn appends_re_exports_to_local_links() {
+        for (link, expected) in [
+            (
+                "-- version 1.5.0\nlocal _VERSION = \"1.5.0\"\nlocal module = require(script.Parent.Module)\nreturn module\n",
+                "-- version 1.5.0\nlocal _VERSION = \"1.5.0\"\nlocal module = require(script.Parent.Module)\n\
+                 export type Value<T> = module.Value<T>\n\
+                 return module\n",
+            ),
+            (
+                "local module = require(script.Parent.Module) :: any\nreturn module\n",
+                "local module = require(script.Parent.Module) :: any\n\
+                 local REQUIRED_MODULE = require(script.Parent.Module)\n\
+                 export type Value<T> = REQUIRED_MODULE.Value<T>\n\
+                 return module\n",
+            ),
+        ] {
+            let parsed_code = full_moon::parse(link).unwrap();
+            let local = crate::require_parser::local_link_require(&parsed_code).unwrap();
+            let body = LinkBody::Local {
+                local_name: local.local_name,
+                require: Box::new(local.expression.clone()),
+            };
+            let type_declarations =
+                type_declarations_from_source("export type Value<T> = Types.Value<T>").unwrap();
+            let MutateLinkResult::Changed { ast, .. } = mutate_link(
+                parsed_code,
+                body,
+                None,
+                &type_declarations,
+                None,
+                &MutateLinkOptions::default(),
+            )
+            .unwrap() else {
+                panic!("link was unchanged");
+            };
+
+            assert_eq!(ast.to_string(), expected);
+            let restored = restore_link(&full_moon::parse(&ast.to_string()).unwrap()).unwrap();
+            assert_eq!(restored.to_string(), link);
+        }
+    }
+
+    #[test]
+    fn does_not_restore_modules() {
+        for code in [
+            "return require(script.Parent.Module)\n",
+            "local REQUIRED_MODULE = require(script.Parent.Module)\nlocal x = 1\nreturn REQUIRED_MODULE\n",
+            "local Types = require(script.Types)\nexport type Value = Other.Value\nreturn Types\n",
+        ] {
+            assert!(restore_link(&full_moon::parse(code).unwrap()).is_none());
+        }
+    }
+
+    #[test]
+    fn skips_namespace_types_with_reserved_names() {
+        let parsed_code = full_moon::parse("return require(script.Parent.Module)\n").unwrap();
+        let returns = crate::require_parser::link_return(&parsed_code)
+            .unwrap()
+            .returns()
+            .clone();
+        let type_declarations =
+            type_declarations_from_source("export type ElementType = string").unwrap();
+        let MutateLinkResult::Changed { ast, .. } = mutate_link(
+            parsed_code,
+            LinkBody::Return(returns),
+            None,
+            &type_declarations,
+            Some("end"),
+            &MutateLinkOptions::default(),
+        )
+        .unwrap() else {
+            panic!("link was unchanged");
+        };
+
+        assert!(!ast.to_string().contains("typeof"));
+    }
+
+    #[test]
+    fn creates_namespace_types() {
+        let link = "return require(script.Parent.Module)\n";
+        let parsed_code = full_moon::parse(link).unwrap();
+        let returns = crate::require_parser::link_return(&parsed_code)
+            .unwrap()
+            .returns()
+            .clone();
+        let type_declarations =
+            type_declarations_from_source("export type ElementType = string").unwrap();
+        let MutateLinkResult::Changed { ast, .. } = mutate_link(
+            parsed_code,
+            LinkBody::Return(returns),
+            None,
+            &type_declarations,
+            Some("React"),
+            &MutateLinkOptions::default(),
+        )
+        .unwrap() else {
+            panic!("link was unchanged");
+        };
+
+        assert_eq!(
+            ast.to_string(),
+            "local REQUIRED_MODULE = require(script.Parent.Module)\n\
+             export type ElementType = REQUIRED_MODULE.ElementType \n\
+             export type React = typeof(REQUIRED_MODULE)\n\
+             return REQUIRED_MODULE\n"
+        );
+        let generated = full_moon::parse(&ast.to_string()).unwrap();
+        assert_eq!(restore_link(&generated).unwrap().to_string(), link);
+    }
+
+    #[test]
+    fn creates_declaration_stubs_for_local_links() {
+        for (link, expected) in [
+            (
+                "local VERSION = \"1.0\"\nlocal Foo = require(script.Parent.Module)\nreturn Foo\n",
+                "local VERSION = \"1.0\"\nlocal Foo = require(\"../Packages/Module\")\n\
+                 export type Value<T> = Foo.Value<T>\n\
+                 return Foo\n",
+            ),
+            (
+                "local module = require(script.Parent.Module) :: any\nreturn module\n",
+                "local REQUIRED_MODULE = require(\"../Packages/Module\")\n\
+                 export type Value<T> = REQUIRED_MODULE.Value<T>\n\
+                 return REQUIRED_MODULE\n",
+            ),
+        ] {
+            let parsed_code = full_moon::parse(link).unwrap();
+            let local = crate::require_parser::local_link_require(&parsed_code).unwrap();
+            let body = LinkBody::Local {
+                local_name: local.local_name,
+                require: Box::new(local.expression.clone()),
+            };
+            let type_declarations =
+                type_declarations_from_source("export type Value<T> = Types.Value<T>").unwrap();
+            let MutateLinkResult::Changed { ast, .. } = mutate_link(
+                parsed_code,
+                body,
+                None,
+                &type_declarations,
+                None,
+                &MutateLinkOptions::default(),
+            )
+            .unwrap() else {
+                panic!("link was unchanged");
+            };
+
+            let stub = declaration_stub(&ast, "../Packages/Module").unwrap();
+            assert_eq!(stub.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn creates_declaration_stubs() {
+        let (generated, _) = mutate(
+            "return require(script.Parent.Module) :: any\n",
+            "export type Value<T> = Types.Value<T>",
+            &MutateLinkOptions::default(),
+        );
+        let stub =
+            declaration_stub(&full_moon::parse(&generated).unwrap(), "../Packages/Module").unwrap();
+
+        assert_eq!(
+            stub.to_string(),
+            "local REQUIRED_MODULE = require(\"../Packages/Module\")\n\
+             export type Value<T> = REQUIRED_MODULE.Value<T>\n\
+             return REQUIRED_MODULE\n"
+        );
+    }
 }
@@ -0,0 +1,213 @@
+use std::{ffi::OsStr, path::Path};
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct LockedPackage {
+    pub name: String,
+    pub version: String,
+    /// The packages this one depends on, as pairs of alias and `scope/name@version`
+    #[serde(default)]
+    pub dependencies: Vec<(String, String)>,
+}
+
+/// The contents of a `wally.lock` file
+#[derive(Deserialize, Debug, Clone, Default)]
+pub struct Lockfile {
+    #[serde(default, rename = "package")]
+    pub packages: Vec<LockedPackage>,
+}
+
+impl Lockfile {
+    pub fn parse(contents: &str) -> Result<Self> {
+        toml::from_str(contents).context("Failed to parse lockfile")
+    }
+
+    pub fn is_locked(&self, name: &str, version: &str) -> bool {
+        self.packages
+            .iter()
+            .any(|package| package.name == name && package.version == version)
+    }
+
+    pub fn locked_versions(&self, name: &str) -> Vec<&str> {
+        self.packages
+            .iter()
+            .filter(|package| package.name == name)
+            .map(|package| package.version.as_str())
+            .collect()
+    }
+
+    /// The locked packages which are installed into an index folder, i.e. every package another one depends on,
+    /// leaving out the project itself
+    pub fn dependencies(&self) -> impl Iterator<Item = &LockedPackage> {
+        self.packages.iter().filter(|package| {
+            let id = format!("{}@{}", package.name, package.version);
+            self.packages.iter().any(|dependent| {
+                dependent
+                    .dependencies
+                    .iter()
+                    .any(|(_, dependency)| *dependency == id)
+            })
+        })
+    }
+}
+
+/// Parses an `_Index` folder name such as `sleitnick_signal@1.5.0` into the package name
+/// (`sleitnick/signal`) and version (`1.5.0`)
+pub fn parse_index_folder_name(folder_name: &str) -> Option<(String, String)> {
+    let (scoped_name, version) = folder_name.split_once('@')?;
+    let (scope, name) = scoped_name.split_once('_')?;
+    Some((format!("{scope}/{name}"), version.to_string()))
+}
+
+/// Creates the `_Index` folder name for a package, the inverse of [`parse_index_folder_name`]
+pub fn index_folder_name(name: &str, version: &str) -> String {
+    format!("{}@{version}", name.replacen('/', "_", 1))
+}
+
+/// The folders packages are installed into, which link files point into: `_Index` for wally, and `.pesde` for
+/// pesde, which installs each package into a `<scope>+<name>/<version>` folder inside of it
+const INDEX_FOLDER_NAMES: &[&str] = &["_Index", ".pesde"];
+
+pub fn is_index_folder(name: &OsStr) -> bool {
+    INDEX_FOLDER_NAMES
+        .iter()
+        .any(|index_folder| name == *index_folder)
+}
+
+/// How many folders deep packages are installed inside of an index folder: `_Index/<scope>_<name>@<version>` for
+/// wally, and `.pesde/<scope>+<name>/<version>` for pesde
+pub fn package_folder_depth(index_folder: &OsStr) -> usize {
+    match index_folder == ".pesde" {
+        true => 2,
+        false => 1,
+    }
+}
+
+/// Whether a file lives inside of an index folder, rather than at the root of a packages folder
+pub fn is_inside_index_folder(path: &Path) -> bool {
+    path.components()
+        .any(|component| is_index_folder(component.as_os_str()))
+}
+
+/// The folder of the package a file belongs to within an index folder, e.g. `sleitnick_signal@1.5.0`,
+/// or `sleitnick+signal@1.5.0` for a package installed by pesde
+pub fn containing_index_folder(path: &Path) -> Option<String> {
+    let mut components = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy());
+    let index_folder = components.find(|component| is_index_folder(OsStr::new(&**component)))?;
+    let folder = components.next()?;
+    if index_folder == ".pesde" {
+        let version = components.next()?;
+        return Some(format!("{folder}@{version}"));
+    }
+    Some(folder.to_string())
+}
+
+/// Writes a package name with an underscore between its scope and name, as in wally's index folder names
+fn underscored(name: &str) -> String {
+    name.replacen(['/', '+'], "_", 1)
+}
+
+/// Whether an index folder belongs to a package given as `scope/name`, `scope_name` or `scope_name@version`
+pub fn index_folder_matches(folder: &str, package: &str) -> bool {
+    let folder = underscored(folder);
+    let package = underscored(package);
+    folder == package
+        || folder
+            .split_once('@')
+            .is_some_and(|(scoped_name, _)| scoped_name == package)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn finds_containing_index_folders() {
+        assert_eq!(
+            containing_index_folder(Path::new(
+                "Packages/_Index/sleitnick_signal@1.5.0/signal/init.lua"
+            ))
+            .as_deref(),
+            Some("sleitnick_signal@1.5.0")
+        );
+        assert_eq!(
+            containing_index_folder(Path::new(
+                "roblox_packages/.pesde/sleitnick+signal/1.5.0/signal/init.luau"
+            ))
+            .as_deref(),
+            Some("sleitnick+signal@1.5.0")
+        );
+        assert_eq!(
+            containing_index_folder(Path::new("Packages/Signal.lua")),
+            None
+        );
+    }
+
+    #[test]
+    fn parses_index_folder_names() {
+        assert_eq!(
+            parse_index_folder_name("sleitnick_signal@1.5.0"),
+            Some(("sleitnick/signal".to_string(), "1.5.0".to_string()))
+        );
+        assert_eq!(
+            index_folder_name("sleitnick/signal", "1.5.0"),
+            "sleitnick_signal@1.5.0"
+        );
+        assert_eq!(parse_index_folder_name("signal"), None);
+    }
+
+    #[test]
+    fn matches_index_folders_to_packages() {
+        for package in [
+            "sleitnick/signal",
+            "sleitnick_signal",
+            "sleitnick_signal@1.5.0",
+            "sleitnick/signal@1.5.0",
+        ] {
+            assert!(index_folder_matches("sleitnick_signal@1.5.0", package));
+            assert!(index_folder_matches("sleitnick+signal@1.5.0", package));
+        }
+        assert!(!index_folder_matches(
+            "sleitnick_signal@1.5.0",
+            "sleitnick_signal@1.4.0"
+        ));
+        assert!(!index_folder_matches("sleitnick_signal@1.5.0", "sleitnick"));
+    }
+
+    #[test]
+    fn reads_locked_packages() {
+        let lockfile = Lockfile::parse(
+            r#"
+            # This file is automatically @generated by Wally.
+            # It is not intended for manual editing.
+            registry = "test"
+
+            [[package]]
+            name = "evaera/promise"
+            version = "4.0.0"
+            dependencies = []
+
+            [[package]]
+            name = "example/game"
+            version = "0.1.0"
+            dependencies = [["Promise", "evaera/promise@4.0.0"]]
+            "#,
+        )
+        .unwrap();
+
+        assert!(lockfile.is_locked("evaera/promise", "4.0.0"));
+        assert!(!lockfile.is_locked("evaera/promise", "3.0.0"));
+        assert_eq!(lockfile.locked_versions("evaera/promise"), vec!["4.0.0"]);
+        assert_eq!(
+            lockfile
+                .dependencies()
+                .map(|package| package.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["evaera/promise"]
+        );
+    }
+}
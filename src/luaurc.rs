@@ -0,0 +1,118 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::{Context, Result};
+use serde_json::{Map, Value};
+
+use crate::lockfile::is_inside_index_folder;
+use crate::report::Report;
+use crate::string_require::module_require_path;
+
+/// Creates an alias for each link at the root of a packages folder, named after the link and pointing to the
+/// module it links to relative to the project root, e.g. `Signal` to `Packages/_Index/sleitnick_signal@1.5.0/signal`
+pub fn package_aliases(report: &Report, project_root: &Path) -> BTreeMap<String, String> {
+    report
+        .thunks
+        .iter()
+        .filter(|thunk| !is_inside_index_folder(&thunk.path))
+        .filter_map(|thunk| {
+            let name = thunk.path.file_stem()?.to_string_lossy().to_string();
+            let target = module_require_path(thunk.target.as_ref()?);
+            let relative = target
+                .strip_prefix(project_root)
+                .ok()?
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy())
+                .collect::<Vec<_>>()
+                .join("/");
+            Some((name, relative))
+        })
+        .collect()
+}
+
+/// Adds the given aliases to the contents of a `.luaurc`, keeping any other configuration and aliases it has
+pub fn update_luaurc(contents: Option<&str>, aliases: &BTreeMap<String, String>) -> Result<String> {
+    let mut luaurc: Map<String, Value> = match contents {
+        Some(contents) => serde_json::from_str(contents).context("Failed to parse .luaurc")?,
+        None => Map::new(),
+    };
+
+    let existing_aliases = luaurc
+        .entry("aliases")
+        .or_insert_with(|| Value::Object(Map::new()))
+        .as_object_mut()
+        .context("Expected 'aliases' in .luaurc to be an object")?;
+    for (name, path) in aliases {
+        existing_aliases.insert(name.clone(), Value::String(path.clone()));
+    }
+
+    let mut contents =
+        serde_json::to_string_pretty(&luaurc).context("Failed to serialize .luaurc")?;
+    contents.push('\n');
+    Ok(contents)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::report::{ThunkReport, ThunkStatus};
+
+    fn thunk(path: &str, target: &str) -> ThunkReport {
+        ThunkReport {
+            path: PathBuf::from(path),
+            target: Some(PathBuf::from(target)),
+            status: ThunkStatus::Unchanged,
+            diff: None,
+        }
+    }
+
+    #[test]
+    fn creates_aliases_for_root_links() {
+        let report = Report {
+            thunks: vec![
+                thunk(
+                    "/project/Packages/Signal.lua",
+                    "/project/Packages/_Index/sleitnick_signal@1.5.0/signal/init.lua",
+                ),
+                thunk(
+                    "/project/Packages/Promise.lua",
+                    "/project/Packages/_Index/evaera_promise@4.0.0/promise.lua",
+                ),
+                thunk(
+                    "/project/Packages/_Index/sleitnick_signal@1.5.0/Promise.lua",
+                    "/project/Packages/_Index/evaera_promise@4.0.0/promise.lua",
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let aliases = package_aliases(&report, Path::new("/project"));
+        assert_eq!(
+            aliases,
+            BTreeMap::from([
+                (
+                    "Promise".to_string(),
+                    "Packages/_Index/evaera_promise@4.0.0/promise".to_string()
+                ),
+                (
+                    "Signal".to_string(),
+                    "Packages/_Index/sleitnick_signal@1.5.0/signal".to_string()
+                ),
+            ])
+        );
+
+        let luaurc = update_luaurc(
+            Some(r#"{ "languageMode": "strict", "aliases": { "Shared": "src/shared" } }"#),
+            &aliases,
+        )
+        .unwrap();
+        let luaurc: Value = serde_json::from_str(&luaurc).unwrap();
+        assert_eq!(luaurc["languageMode"], "strict");
+        assert_eq!(luaurc["aliases"]["Shared"], "src/shared");
+        assert_eq!(
+            luaurc["aliases"]["Signal"],
+            "Packages/_Index/sleitnick_signal@1.5.0/signal"
+        );
+    }
+}
@@ -4,11 +4,13 @@ use std::io::Write;
 use clap::Parser;
 
 use console::style;
-use wally_package_types::Command;
+use wally_package_types::{exit_code, Command, LogWriter};
 
 fn main() {
     env_logger::Builder::from_env("LOG")
         .filter_level(LevelFilter::Info)
+        // Log lines are printed above any progress bars, rather than through them
+        .target(env_logger::Target::Pipe(Box::new(LogWriter)))
         .format(move |buf, record| {
             let tag = match record.level() {
                 Level::Error => style("error").red(),
@@ -27,7 +29,7 @@ fn main() {
         Ok(_) => 0,
         Err(err) => {
             error!("{:#}", err);
-            1
+            exit_code(&err)
         }
     };
 
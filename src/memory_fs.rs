@@ -0,0 +1,283 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io,
+    path::{Component, Path, PathBuf},
+    sync::RwLock,
+};
+
+use crate::file_access::FileSystem;
+use crate::trace::{PathKind, RecordedDirEntry};
+
+/// A filesystem held in memory, for embedding the crate where there is no real filesystem, e.g. in a VS Code
+/// extension or a Lune plugin. Directories exist implicitly whenever a file is inside of them. Relative paths are
+/// relative to the root `/`
+#[derive(Debug, Default)]
+pub struct MemoryFileSystem {
+    files: RwLock<BTreeMap<PathBuf, String>>,
+}
+
+/// Resolves `.` and `..` components lexically, making the path absolute
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::Prefix(_) | Component::RootDir => normalized = PathBuf::from("/"),
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            Component::Normal(name) => normalized.push(name),
+        }
+    }
+    normalized
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("'{}' does not exist", path.display()),
+    )
+}
+
+impl MemoryFileSystem {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&self, path: impl AsRef<Path>, contents: impl Into<String>) {
+        self.files
+            .write()
+            .unwrap()
+            .insert(normalize(path.as_ref()), contents.into());
+    }
+
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<String> {
+        self.files
+            .read()
+            .unwrap()
+            .get(&normalize(path.as_ref()))
+            .cloned()
+    }
+
+    /// All files and their contents, keyed by their absolute paths
+    pub fn files(&self) -> BTreeMap<PathBuf, String> {
+        self.files.read().unwrap().clone()
+    }
+}
+
+impl FileSystem for MemoryFileSystem {
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        self.get(path).ok_or_else(|| not_found(path))
+    }
+
+    fn write(&self, path: &Path, contents: &str) -> io::Result<()> {
+        self.insert(path, contents);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+        let mut files = self.files.write().unwrap();
+        let contents = files
+            .remove(&normalize(from))
+            .ok_or_else(|| not_found(from))?;
+        files.insert(normalize(to), contents);
+        Ok(())
+    }
+
+    fn remove_file(&self, path: &Path) -> io::Result<()> {
+        self.files
+            .write()
+            .unwrap()
+            .remove(&normalize(path))
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+
+    /// Directories exist implicitly through the files inside of them
+    fn create_dir_all(&self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn canonicalize(&self, path: &Path) -> io::Result<PathBuf> {
+        match self.path_kind(path) {
+            PathKind::Missing => Err(not_found(path)),
+            _ => Ok(normalize(path)),
+        }
+    }
+
+    fn path_kind(&self, path: &Path) -> PathKind {
+        let path = normalize(path);
+        let files = self.files.read().unwrap();
+        if files.contains_key(&path) {
+            PathKind::File
+        } else if files.keys().any(|file| file.starts_with(&path)) {
+            PathKind::Directory
+        } else {
+            PathKind::Missing
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> io::Result<Vec<RecordedDirEntry>> {
+        if self.path_kind(path) != PathKind::Directory {
+            return Err(not_found(path));
+        }
+
+        let directory = normalize(path);
+        let files = self.files.read().unwrap();
+        let mut names = BTreeSet::new();
+        for file in files.keys() {
+            if let Ok(relative) = file.strip_prefix(&directory) {
+                let mut components = relative.components();
+                if let Some(name) = components.next() {
+                    names.insert((name.as_os_str().to_owned(), components.next().is_none()));
+                }
+            }
+        }
+        // Entries are joined onto the path as given, as with `std::fs::read_dir`
+        Ok(names
+            .into_iter()
+            .map(|(name, is_file)| RecordedDirEntry {
+                path: path.join(name),
+                is_file,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::command::mutate_file_system;
+    use crate::report::ThunkStatus;
+
+    #[test]
+    fn lists_implicit_directories() {
+        let file_system = MemoryFileSystem::new();
+        file_system.insert("Packages/Signal.lua", "return {}");
+        file_system.insert("Packages/_Index/sleitnick_signal@1.5.0/signal/init.lua", "");
+
+        assert_eq!(
+            file_system.path_kind(Path::new("Packages/_Index")),
+            PathKind::Directory
+        );
+        assert_eq!(
+            file_system
+                .canonicalize(Path::new("./Packages/_Index/../Signal.lua"))
+                .unwrap(),
+            PathBuf::from("/Packages/Signal.lua")
+        );
+        assert_eq!(
+            file_system.read_dir(Path::new("Packages")).unwrap(),
+            vec![
+                RecordedDirEntry {
+                    path: PathBuf::from("Packages/Signal.lua"),
+                    is_file: true,
+                },
+                RecordedDirEntry {
+                    path: PathBuf::from("Packages/_Index"),
+                    is_file: false,
+                },
+            ]
+        );
+        assert!(file_system.read_dir(Path::new("Missing")).is_err());
+    }
+
+    #[test]
+    fn mutates_links_in_memory() {
+        let file_system = Arc::new(MemoryFileSystem::new());
+        file_system.insert(
+            "sourcemap.json",
+            r#"{"name":"Packages","className":"Folder","filePaths":["Packages"],"children":[
+                {"name":"Signal","className":"ModuleScript","filePaths":["Packages/Signal.lua"]},
+                {"name":"_Index","className":"Folder","children":[
+                    {"name":"sleitnick_signal@1.5.0","className":"Folder","children":[
+                        {"name":"signal","className":"ModuleScript","filePaths":["Packages/_Index/sleitnick_signal@1.5.0/signal/init.lua"]}
+                    ]}
+                ]}
+            ]}"#,
+        );
+        file_system.insert(
+            "Packages/Signal.lua",
+            "return require(script.Parent._Index[\"sleitnick_signal@1.5.0\"][\"signal\"])\n",
+        );
+        file_system.insert(
+            "Packages/_Index/sleitnick_signal@1.5.0/signal/init.lua",
+            "export type Connection = { Disconnect: (self: any) -> () }\nreturn {}\n",
+        );
+
+        let report = mutate_file_system(
+            file_system.clone(),
+            Path::new("sourcemap.json"),
+            &[PathBuf::from("Packages")],
+            Default::default(),
+        )
+        .unwrap();
+
+        assert_eq!(report.thunks.len(), 1);
+        assert!(matches!(
+            report.thunks[0].status,
+            ThunkStatus::Mutated { .. }
+        ));
+        assert!(file_system
+            .get("Packages/Signal.lua")
+            .unwrap()
+            .contains("export type Connection = REQUIRED_MODULE.Connection"));
+    }
+
+    #[test]
+    fn only_collects_links_beside_packages() {
+        let file_system = Arc::new(MemoryFileSystem::new());
+        file_system.insert(
+            "sourcemap.json",
+            r#"{"name":"Packages","className":"Folder"}"#,
+        );
+        file_system.insert(
+            "Packages/Signal.lua",
+            "return require(script.Parent._Index[\"sleitnick_signal@1.5.0\"][\"signal\"])\n",
+        );
+        file_system.insert(
+            "Packages/_Index/sleitnick_signal@1.5.0/Janitor.lua",
+            "return require(script.Parent.Parent[\"howmanysmall_janitor@1.0.0\"][\"janitor\"])\n",
+        );
+        // A module of the package itself, which has the shape of a link
+        file_system.insert(
+            "Packages/_Index/sleitnick_signal@1.5.0/signal/Types.lua",
+            "local Types = require(script.Parent.Definitions)\nreturn Types\n",
+        );
+        file_system.insert(
+            "Packages/_Index/sleitnick_signal@1.5.0/_Index/evaera_promise@3.0.0/Util.lua",
+            "return require(script.Parent.Parent[\"evaera_util@1.0.0\"][\"util\"])\n",
+        );
+        file_system.insert(
+            "Packages/_Index/sleitnick_signal@1.5.0/_Index/evaera_promise@3.0.0/promise/init.lua",
+            "return require(script.Parent.lib)\n",
+        );
+
+        let report = mutate_file_system(
+            file_system,
+            Path::new("sourcemap.json"),
+            &[PathBuf::from("Packages")],
+            Default::default(),
+        )
+        .unwrap();
+
+        let mut thunks = report
+            .thunks
+            .iter()
+            .map(|thunk| thunk.path.clone())
+            .collect::<Vec<_>>();
+        thunks.sort();
+        assert_eq!(
+            thunks,
+            vec![
+                PathBuf::from("Packages/Signal.lua"),
+                PathBuf::from("Packages/_Index/sleitnick_signal@1.5.0/Janitor.lua"),
+                PathBuf::from(
+                    "Packages/_Index/sleitnick_signal@1.5.0/_Index/evaera_promise@3.0.0/Util.lua"
+                ),
+            ]
+        );
+    }
+}
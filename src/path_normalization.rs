@@ -0,0 +1,81 @@
+use std::path::{Component, Path, PathBuf};
+
+/// Strips the verbatim prefix Windows adds to canonicalized paths, so that `\\?\C:\Project` becomes `C:\Project`
+/// and `\\?\UNC\server\share` becomes `\\server\share`, matching the paths written in sourcemaps
+pub fn simplify(path: &Path) -> PathBuf {
+    let Some(path_str) = path.to_str() else {
+        return path.to_path_buf();
+    };
+    if let Some(unc) = path_str.strip_prefix(r"\\?\UNC\") {
+        PathBuf::from(format!(r"\\{unc}"))
+    } else if let Some(local) = path_str
+        .strip_prefix(r"\\?\")
+        .filter(|local| local.as_bytes().get(1) == Some(&b':'))
+    {
+        PathBuf::from(local)
+    } else {
+        path.to_path_buf()
+    }
+}
+
+/// Makes a path absolute and resolves its `.` and `..` components, without touching the filesystem. Unlike
+/// canonicalizing, this works for files which do not exist yet, and keeps symlinks as they are
+pub fn normalize_lexically(path: &Path) -> PathBuf {
+    let absolute = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut normalized = PathBuf::new();
+    for component in simplify(&absolute).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    normalized.push(component);
+                }
+            }
+            _ => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// The form paths are compared in, which ignores verbatim prefixes, and case on Windows
+pub fn path_key(path: &Path) -> PathBuf {
+    let simplified = simplify(path);
+    if cfg!(windows) {
+        match simplified.to_str() {
+            Some(path_str) => PathBuf::from(path_str.replace('/', r"\").to_lowercase()),
+            None => simplified,
+        }
+    } else {
+        simplified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn simplifies_verbatim_paths() {
+        assert_eq!(
+            simplify(Path::new(r"\\?\C:\Project\Packages")),
+            PathBuf::from(r"C:\Project\Packages")
+        );
+        assert_eq!(
+            simplify(Path::new(r"\\?\UNC\server\share\Packages")),
+            PathBuf::from(r"\\server\share\Packages")
+        );
+        assert_eq!(
+            simplify(Path::new(r"\\?\Volume{0}\Packages")),
+            PathBuf::from(r"\\?\Volume{0}\Packages")
+        );
+    }
+
+    #[test]
+    fn normalizes_without_the_filesystem() {
+        let root = std::env::current_dir().unwrap();
+        assert_eq!(
+            normalize_lexically(Path::new("./Packages/_Index/../Signal.lua")),
+            root.join("Packages").join("Signal.lua")
+        );
+    }
+}
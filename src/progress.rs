@@ -0,0 +1,189 @@
+use std::{
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+use log::info;
+
+/// How often a progress line is logged when stderr is not a terminal
+const LOG_INTERVAL: Duration = Duration::from_secs(5);
+
+/// The progress bars being drawn, if any, which log lines have to be printed above
+static DRAWN: RwLock<Option<MultiProgress>> = RwLock::new(None);
+
+/// Writes log lines to stderr, above any progress bars being drawn rather than through them
+pub struct LogWriter;
+
+impl Write for LogWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &*DRAWN.read().unwrap() {
+            Some(bars) => bars.suspend(|| io::stderr().write(buf)),
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+enum Indicator {
+    /// A progress bar for each packages folder, alongside the folder it counts
+    Bars(Vec<(PathBuf, ProgressBar)>),
+    /// A log line every so often, for when stderr is not a terminal
+    Log {
+        completed: AtomicUsize,
+        total: usize,
+        last_logged: Mutex<Instant>,
+    },
+    Hidden,
+}
+
+/// Shows how many of the link files being mutated are done, and the package currently being mutated
+pub struct Progress {
+    indicator: Indicator,
+}
+
+impl Progress {
+    /// Draws a progress bar for each packages folder when stderr is a terminal, and logs progress every so often
+    /// otherwise
+    pub fn new(packages_folders: &[PathBuf], thunks: &[PathBuf], visible: bool) -> Self {
+        let indicator = if !visible || thunks.is_empty() {
+            Indicator::Hidden
+        } else if console::Term::stderr().is_term() {
+            let bars = MultiProgress::with_draw_target(ProgressDrawTarget::stderr());
+            let style = ProgressStyle::with_template("{prefix} [{bar:30}] {pos}/{len} {wide_msg}")
+                .expect("progress bar template is valid")
+                .progress_chars("=> ");
+            let bars_by_folder = packages_folders
+                .iter()
+                .filter_map(|folder| {
+                    let count = thunks
+                        .iter()
+                        .filter(|thunk| Self::folder_of(packages_folders, thunk) == Some(folder))
+                        .count();
+                    (count > 0).then(|| {
+                        let bar = bars.add(
+                            ProgressBar::new(count as u64)
+                                .with_style(style.clone())
+                                .with_prefix(folder.display().to_string()),
+                        );
+                        (folder.clone(), bar)
+                    })
+                })
+                .collect();
+            *DRAWN.write().unwrap() = Some(bars);
+            Indicator::Bars(bars_by_folder)
+        } else {
+            Indicator::Log {
+                completed: AtomicUsize::new(0),
+                total: thunks.len(),
+                last_logged: Mutex::new(Instant::now()),
+            }
+        };
+
+        Self { indicator }
+    }
+
+    /// The packages folder a link file is in, preferring the innermost when folders are nested
+    fn folder_of<'a>(packages_folders: &'a [PathBuf], thunk: &Path) -> Option<&'a PathBuf> {
+        packages_folders
+            .iter()
+            .filter(|folder| thunk.starts_with(folder))
+            .max_by_key(|folder| folder.components().count())
+    }
+
+    fn bar(&self, thunk: &Path) -> Option<&ProgressBar> {
+        let Indicator::Bars(bars) = &self.indicator else {
+            return None;
+        };
+        bars.iter()
+            .filter(|(folder, _)| thunk.starts_with(folder))
+            .max_by_key(|(folder, _)| folder.components().count())
+            .map(|(_, bar)| bar)
+    }
+
+    /// Marks a link file of the given package as being mutated
+    pub fn start(&self, thunk: &Path, package: &str) {
+        if let Some(bar) = self.bar(thunk) {
+            bar.set_message(package.to_string());
+        }
+    }
+
+    /// Marks a link file of the given package as done
+    pub fn finish(&self, thunk: &Path, package: &str) {
+        match &self.indicator {
+            Indicator::Bars(_) => {
+                if let Some(bar) = self.bar(thunk) {
+                    bar.inc(1);
+                }
+            }
+            Indicator::Log {
+                completed,
+                total,
+                last_logged,
+            } => {
+                let completed = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                let mut last_logged = last_logged.lock().unwrap();
+                if last_logged.elapsed() >= LOG_INTERVAL && completed < *total {
+                    info!("Mutated {completed} of {total} link files, currently '{package}'");
+                    *last_logged = Instant::now();
+                }
+            }
+            Indicator::Hidden => {}
+        }
+    }
+
+    /// Removes the progress bars, so that anything printed afterwards follows the log lines directly
+    pub fn clear(&self) {
+        if let Indicator::Bars(bars) = &self.indicator {
+            for (_, bar) in bars {
+                bar.finish_and_clear();
+            }
+            *DRAWN.write().unwrap() = None;
+        }
+    }
+}
+
+impl Drop for Progress {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_link_files_in_the_innermost_folder() {
+        let packages_folders = [
+            PathBuf::from("Packages"),
+            PathBuf::from("Packages/_Index/scope_pkg@1.0.0/Packages"),
+        ];
+        assert_eq!(
+            Progress::folder_of(
+                &packages_folders,
+                Path::new("Packages/_Index/scope_pkg@1.0.0/Packages/Dep.lua")
+            ),
+            Some(&packages_folders[1])
+        );
+        assert_eq!(
+            Progress::folder_of(&packages_folders, Path::new("Packages/Signal.lua")),
+            Some(&packages_folders[0])
+        );
+        assert_eq!(
+            Progress::folder_of(&packages_folders, Path::new("ServerPackages/Signal.lua")),
+            None
+        );
+
+        let progress = Progress::new(&packages_folders, &[], true);
+        assert!(matches!(progress.indicator, Indicator::Hidden));
+    }
+}
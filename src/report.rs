@@ -0,0 +1,219 @@
+use std::{collections::BTreeMap, path::PathBuf, time::Duration};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::build_info::BuildInfo;
+use crate::diagnostics::{EmittedWarning, WarningCategory};
+use crate::failure::FailureClass;
+use crate::link_mutator::ReExportedType;
+
+/// How the results of a run are output
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputFormat {
+    /// Human readable log lines
+    #[default]
+    Text,
+    /// A structured JSON report printed to stdout once the run completes
+    Json,
+}
+
+/// What happened to a single link file during the run
+#[derive(Serialize, Debug, Clone)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ThunkStatus {
+    /// The link file was rewritten to re-export the given types
+    #[serde(rename_all = "camelCase")]
+    Mutated { exported_types: Vec<ReExportedType> },
+    /// The linked module has no exported types, so the link file was left as is
+    Unchanged,
+    /// The link file was generated by a previous run and already re-exports the given types, so was left as is
+    #[serde(rename_all = "camelCase")]
+    UpToDate { exported_types: Vec<ReExportedType> },
+    /// The link file was not in a recognised form, so was left as is
+    Skipped { reason: String },
+    /// An error occurred whilst mutating the link file
+    Failed { error: String, class: FailureClass },
+    /// The file is a module rather than a link, so was left untouched
+    NotALink,
+    /// The link file was generated by a previous run, and was restored to a plain link
+    Cleaned,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct ThunkReport {
+    pub path: PathBuf,
+    /// The file the link's require expression resolved to, if it could be resolved
+    pub target: Option<PathBuf>,
+    #[serde(flatten)]
+    pub status: ThunkStatus,
+    /// A unified diff of the changes made to the link file, if requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<String>,
+}
+
+impl ThunkReport {
+    pub fn is_failure(&self) -> bool {
+        matches!(
+            self.status,
+            ThunkStatus::Skipped { .. } | ThunkStatus::Failed { .. }
+        )
+    }
+
+    /// A short description of why this thunk failed, for the end of run summary
+    pub fn failure_reason(&self) -> Option<&str> {
+        match &self.status {
+            ThunkStatus::Skipped { reason } => Some(reason),
+            ThunkStatus::Failed { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+
+    /// The class of failure of this thunk, if it failed. Link files which were skipped fail as `Other`
+    pub fn failure_class(&self) -> Option<FailureClass> {
+        match &self.status {
+            ThunkStatus::Skipped { .. } => Some(FailureClass::Other),
+            ThunkStatus::Failed { class, .. } => Some(*class),
+            _ => None,
+        }
+    }
+}
+
+/// A package installed into an index folder, which link files belong to or link into
+#[derive(Serialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "camelCase")]
+pub struct PackageReport {
+    pub name: String,
+    pub version: String,
+    /// Whether the package is locked in wally.lock, if one was read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub locked: Option<bool>,
+}
+
+/// The results of a whole run
+#[derive(Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Report {
+    /// Whether the run was a dry run, in which case no link files were written
+    pub dry_run: bool,
+    pub thunks: Vec<ThunkReport>,
+    pub warnings: Vec<EmittedWarning>,
+    /// The packages link files belong to or link into
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<PackageReport>,
+    /// The binary which produced the report
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub build_info: Option<BuildInfo>,
+}
+
+impl Report {
+    pub fn failures(&self) -> impl Iterator<Item = &ThunkReport> {
+        self.thunks.iter().filter(|thunk| thunk.is_failure())
+    }
+
+    /// The number of warnings emitted in each category
+    pub fn warning_counts(&self) -> BTreeMap<WarningCategory, usize> {
+        let mut counts = BTreeMap::new();
+        for warning in &self.warnings {
+            *counts.entry(warning.category).or_default() += 1;
+        }
+        counts
+    }
+
+    /// A single line summarising the run, e.g.
+    /// `wally-package-types: 97 link files updated, 0 up to date, 412 types re-exported, 5 without exported types, 3 skipped, 0 errors in 1.2s`,
+    /// where link files are counted as ones which "would update" on a dry run
+    pub fn summary_line(&self, elapsed: Duration) -> String {
+        let mut updated = 0;
+        let mut up_to_date = 0;
+        let mut re_exported = 0;
+        let mut without_types = 0;
+        let mut skipped = 0;
+        let mut errors = 0;
+        for thunk in &self.thunks {
+            match &thunk.status {
+                ThunkStatus::Mutated { exported_types } => {
+                    updated += 1;
+                    re_exported += exported_types.len();
+                }
+                ThunkStatus::Cleaned => updated += 1,
+                ThunkStatus::UpToDate { exported_types } => {
+                    up_to_date += 1;
+                    re_exported += exported_types.len();
+                }
+                ThunkStatus::Unchanged => without_types += 1,
+                ThunkStatus::Skipped { .. } | ThunkStatus::NotALink => skipped += 1,
+                ThunkStatus::Failed { .. } => errors += 1,
+            }
+        }
+
+        // Nothing is written on a dry run, so link files are only reported as needing an update
+        let updated = match self.dry_run {
+            true => format!("{updated} link files would update"),
+            false => format!("{updated} link files updated"),
+        };
+        format!(
+            "wally-package-types: {updated}, {up_to_date} up to date, {re_exported} types re-exported, \
+             {without_types} without exported types, {skipped} skipped, {errors} errors in {:.1}s",
+            elapsed.as_secs_f64()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn thunk(status: ThunkStatus) -> ThunkReport {
+        ThunkReport {
+            path: PathBuf::from("Packages/Example.lua"),
+            target: None,
+            status,
+            diff: None,
+        }
+    }
+
+    #[test]
+    fn summarises_thunk_statuses() {
+        let report = Report {
+            dry_run: false,
+            warnings: vec![],
+            packages: vec![],
+            build_info: None,
+            thunks: vec![
+                thunk(ThunkStatus::Mutated {
+                    exported_types: vec![ReExportedType {
+                        name: "Signal".to_string(),
+                        signature: "Signal<T...>".to_string(),
+                    }],
+                }),
+                thunk(ThunkStatus::Unchanged),
+                thunk(ThunkStatus::Skipped {
+                    reason: "Malformed link file".to_string(),
+                }),
+                thunk(ThunkStatus::Failed {
+                    error: "Child 'Example' not found".to_string(),
+                    class: FailureClass::Resolution,
+                }),
+            ],
+        };
+
+        assert_eq!(
+            report.summary_line(Duration::from_millis(1234)),
+            "wally-package-types: 1 link files updated, 0 up to date, 1 types re-exported, 1 without exported types, \
+             1 skipped, 1 errors in 1.2s"
+        );
+
+        let dry_run = Report {
+            dry_run: true,
+            ..report
+        };
+        assert_eq!(
+            dry_run.summary_line(Duration::from_millis(1234)),
+            "wally-package-types: 1 link files would update, 0 up to date, 1 types re-exported, \
+             1 without exported types, 1 skipped, 1 errors in 1.2s"
+        );
+    }
+}
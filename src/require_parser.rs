@@ -1,71 +1,473 @@
-use anyhow::{bail, Result};
+use std::collections::HashMap;
+
+use anyhow::{bail, Context, Result};
 use full_moon::{
-    ast::{Call, Expression, FunctionArgs, Index, Suffix, Var},
+    ast::{
+        luau::{GenericParameterInfo, IndexedTypeInfo, TypeInfo},
+        Ast, Call, Expression, Field, FunctionArgs, Index, LastStmt, MethodCall, Return, Stmt,
+        Suffix, Var,
+    },
     tokenizer::TokenType,
 };
 
+/// Extracts the value of a string literal expression
+fn string_literal(expression: &Expression) -> Option<String> {
+    let Expression::String(token) = expression else {
+        return None;
+    };
+    let TokenType::StringLiteral { literal, .. } = token.token_type() else {
+        return None;
+    };
+    Some(literal.trim().to_string())
+}
+
+/// Extracts the single string argument passed to a function call, e.g. `("value")` or `"value"`
+fn single_string_argument(args: &FunctionArgs) -> Option<String> {
+    match args {
+        FunctionArgs::Parentheses { arguments, .. } if arguments.len() == 1 => {
+            string_literal(arguments.iter().next().unwrap())
+        }
+        FunctionArgs::String(token) => match token.token_type() {
+            TokenType::StringLiteral { literal, .. } => Some(literal.trim().to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Converts a method call to the child it looks up, e.g. `game:GetService("ReplicatedStorage")`
+/// or `script.Parent:WaitForChild("Example")`
+fn method_call_to_component(method_call: &MethodCall, components: &[String]) -> Result<String> {
+    let method_name = method_call.name().token().to_string();
+
+    if method_name == "GetService" && components == ["game"] {
+        return single_string_argument(method_call.args()).context(
+            "require expression not supported: GetService must be called with a single string",
+        );
+    }
+
+    if method_name == "WaitForChild" || method_name == "FindFirstChild" {
+        return single_string_argument(method_call.args()).with_context(|| {
+            format!("require expression not supported: {method_name} must be called with a single string")
+        });
+    }
+
+    bail!("require expression not supported: method call ':{method_name}' is not supported")
+}
+
 /// Decomposes a VarExpression into a list of string components
 pub fn expression_to_components(expression: &Expression) -> Result<Vec<String>> {
     let mut components = Vec::new();
 
-    let Expression::Var(Var::Expression(var_expression)) = expression else {
-        bail!("require expression not supported: expression must contain components of form `.value` or `['value']`")
+    // `game:GetService("X")` on its own is a function call rather than a var expression
+    let (prefix, suffixes): (_, Vec<&Suffix>) = match expression {
+        Expression::Var(Var::Expression(var_expression)) => {
+            (var_expression.prefix(), var_expression.suffixes().collect())
+        }
+        Expression::FunctionCall(call) => (call.prefix(), call.suffixes().collect()),
+        _ => bail!("require expression not supported: expression must contain components of form `.value` or `['value']`"),
     };
 
-    components.push(var_expression.prefix().to_string().trim().to_string());
-
-    for suffix in var_expression.suffixes() {
-        let Suffix::Index(index) = suffix else {
-            bail!("require expression not supported: expression must contain components of form `.value` or `['value']`")
-        };
+    components.push(prefix.to_string().trim().to_string());
 
-        match index {
-            Index::Dot { name, .. } => {
+    for suffix in suffixes {
+        match suffix {
+            Suffix::Index(Index::Dot { name, .. }) => {
                 components.push(name.to_string().trim().to_string());
             }
-            Index::Brackets { expression, .. } => {
-                let Expression::String(name) = expression else {
+            Suffix::Index(Index::Brackets { expression, .. }) => {
+                let Some(name) = string_literal(expression) else {
                     bail!("require expression not supported: expression contains brackets component not of the form ['value']")
                 };
-                let TokenType::StringLiteral { literal, .. } = name.token_type() else {
-                    bail!("require expression not supported: expression contains brackets component not of the form ['value']")
-                };
-                components.push(literal.trim().to_string());
+                components.push(name);
             }
-            _ => unreachable!(),
+            Suffix::Call(Call::MethodCall(method_call)) => {
+                let component = method_call_to_component(method_call, &components)?;
+                components.push(component);
+            }
+            _ => bail!("require expression not supported: expression must contain components of form `.value` or `['value']`"),
         }
     }
 
     Ok(components)
 }
 
-pub fn match_require(expression: &Expression) -> Result<Vec<String>> {
-    let Expression::FunctionCall(call) = expression else {
-        bail!("'{}' is not a function call", expression.to_string().trim());
+/// The path a require expression points to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RequirePath {
+    /// An instance path, e.g. `require(script.Parent.Example)` becomes `["script", "Parent", "Example"]`
+    Instance(Vec<String>),
+    /// A string require, e.g. `require("./Example")` or `require("@Packages/Example")`
+    String(String),
+}
+
+/// Strips any parentheses and type assertions around an expression, e.g. `(require(x) :: any)` becomes `require(x)`
+pub fn unwrap_expression(expression: &Expression) -> &Expression {
+    match expression {
+        Expression::Parentheses { expression, .. }
+        | Expression::TypeAssertion { expression, .. } => unwrap_expression(expression),
+        _ => expression,
+    }
+}
+
+/// Replaces the expression wrapped by any parentheses and type assertions, the inverse of [`unwrap_expression`]
+pub fn rewrap_expression(expression: &Expression, inner: Expression) -> Expression {
+    match expression {
+        Expression::Parentheses {
+            contained,
+            expression,
+        } => Expression::Parentheses {
+            contained: contained.clone(),
+            expression: Box::new(rewrap_expression(expression, inner)),
+        },
+        Expression::TypeAssertion {
+            expression,
+            type_assertion,
+        } => Expression::TypeAssertion {
+            expression: Box::new(rewrap_expression(expression, inner)),
+            type_assertion: type_assertion.clone(),
+        },
+        _ => inner,
+    }
+}
+
+/// Finds the return statement of a link file, which consists solely of `return require(...)`, optionally wrapped
+/// in parentheses or type assertions, or followed by member accesses. Any other file is a module rather than a link
+pub fn link_return(ast: &Ast) -> Option<&Return> {
+    if ast.nodes().stmts().next().is_some() {
+        return None;
+    }
+
+    let Some(LastStmt::Return(r#return)) = ast.nodes().last_stmt() else {
+        return None;
     };
+    if r#return.returns().len() != 1 {
+        return None;
+    }
 
-    if call.prefix().to_string().trim() == "require" && call.suffixes().count() == 1 {
-        if let Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses { arguments, .. })) =
-            call.suffixes().next().unwrap()
-        {
-            if arguments.len() == 1 {
-                return expression_to_components(arguments.iter().next().unwrap());
+    is_require(r#return.returns().iter().next()?).then_some(r#return)
+}
+
+/// Whether an expression calls `require`, optionally wrapped in parentheses or type assertions, or followed by
+/// member accesses
+pub fn is_require(expression: &Expression) -> bool {
+    let prefix = match unwrap_expression(expression) {
+        Expression::FunctionCall(call) => call.prefix(),
+        Expression::Var(Var::Expression(var_expression)) => var_expression.prefix(),
+        _ => return false,
+    };
+    prefix.to_string().trim() == "require"
+}
+
+/// The require of a link file which assigns it to a local before returning it, e.g.
+/// `local module = require(script.Parent.Example) return module`
+#[derive(Debug)]
+pub struct LocalLinkRequire<'a> {
+    /// The local the require is assigned to
+    pub local_name: String,
+    /// The expression assigned to the local
+    pub expression: &'a Expression,
+}
+
+/// Finds the require of a link file which assigns it to a local, then returns it either directly or through other
+/// locals aliasing it. The link may declare other locals holding literals, such as a version header, but nothing
+/// else. Locals may not be redeclared, so that the local the require is assigned to always holds it
+pub fn local_link_require(ast: &Ast) -> Option<LocalLinkRequire<'_>> {
+    // The local each declared local aliases, if it aliases the require
+    let mut aliases: HashMap<String, Option<String>> = HashMap::new();
+    let mut require = None;
+    for stmt in ast.nodes().stmts() {
+        let Stmt::LocalAssignment(local) = stmt else {
+            return None;
+        };
+        if local.names().len() != 1 || local.expressions().len() != 1 {
+            return None;
+        }
+        let name = local.names().iter().next()?.token().to_string();
+        let expression = local.expressions().iter().next()?;
+        let aliased = match unwrap_expression(expression) {
+            Expression::Var(Var::Name(aliased)) => {
+                aliases.get(&aliased.token().to_string())?.clone()
             }
+            Expression::String(_) | Expression::Number(_) | Expression::Symbol(_) => None,
+            _ if is_require(expression) && require.is_none() => {
+                require = Some(LocalLinkRequire {
+                    local_name: name.clone(),
+                    expression,
+                });
+                Some(name.clone())
+            }
+            _ => return None,
+        };
+        if aliases.insert(name, aliased).is_some() {
+            return None;
         }
-    } else {
-        bail!("unknown require expression '{}'", call.to_string().trim());
     }
 
-    bail!(
-        "'{}' is not a require function call",
-        call.to_string().trim()
-    )
+    let Some(LastStmt::Return(r#return)) = ast.nodes().last_stmt() else {
+        return None;
+    };
+    if r#return.returns().len() != 1 {
+        return None;
+    }
+    let Expression::Var(Var::Name(returned)) = unwrap_expression(r#return.returns().iter().next()?)
+    else {
+        return None;
+    };
+    let require = require?;
+    (aliases.get(&returned.token().to_string())?.as_ref() == Some(&require.local_name))
+        .then_some(require)
+}
+
+/// Where the require of a link file is found
+#[derive(Debug)]
+pub enum LinkRequire<'a> {
+    /// A link of the form `return require(...)`
+    Returned(&'a Return),
+    /// A link which assigns its require to a local before returning it
+    Local(LocalLinkRequire<'a>),
+}
+
+impl LinkRequire<'_> {
+    /// The expression holding the require, e.g. `require(script.Parent.Example)`
+    pub fn expression(&self) -> &Expression {
+        match self {
+            LinkRequire::Returned(r#return) => r#return
+                .returns()
+                .iter()
+                .next()
+                .expect("link returns always return one expression"),
+            LinkRequire::Local(local) => local.expression,
+        }
+    }
+}
+
+/// Finds the require of a link file of either form. Any other file is a module rather than a link
+pub fn find_link_require(ast: &Ast) -> Option<LinkRequire<'_>> {
+    match link_return(ast) {
+        Some(r#return) => Some(LinkRequire::Returned(r#return)),
+        None => local_link_require(ast).map(LinkRequire::Local),
+    }
+}
+
+/// A require expression found in a link file
+#[derive(Debug, PartialEq)]
+pub struct MatchedRequire {
+    pub path: RequirePath,
+    /// The argument passed to `require`
+    pub argument: Expression,
+    /// Members indexed on the required module, e.g. `["SubModule"]` for `require(x).SubModule`
+    pub members: Vec<String>,
+}
+
+pub fn match_require(expression: &Expression) -> Result<MatchedRequire> {
+    let expression = unwrap_expression(expression);
+
+    // `require(x).SubModule` is a var expression rather than a function call
+    let (prefix, suffixes): (_, Vec<&Suffix>) = match expression {
+        Expression::FunctionCall(call) => (call.prefix(), call.suffixes().collect()),
+        Expression::Var(Var::Expression(var_expression)) => {
+            (var_expression.prefix(), var_expression.suffixes().collect())
+        }
+        _ => bail!("'{}' is not a function call", expression.to_string().trim()),
+    };
+
+    if prefix.to_string().trim() != "require" {
+        bail!(
+            "unknown require expression '{}'",
+            expression.to_string().trim()
+        );
+    }
+
+    let Some(Suffix::Call(Call::AnonymousCall(FunctionArgs::Parentheses { arguments, .. }))) =
+        suffixes.first()
+    else {
+        bail!(
+            "'{}' is not a require function call",
+            expression.to_string().trim()
+        )
+    };
+    if arguments.len() != 1 {
+        bail!(
+            "'{}' is not a require function call",
+            expression.to_string().trim()
+        )
+    }
+    let argument = arguments.iter().next().unwrap();
+
+    let members = suffixes[1..]
+        .iter()
+        .map(|suffix| match suffix {
+            Suffix::Index(Index::Dot { name, .. }) => Ok(name.token().to_string()),
+            Suffix::Index(Index::Brackets { expression, .. }) => string_literal(expression)
+                .context("require expression not supported: member accessed on required module is not of the form ['value']"),
+            _ => bail!("require expression not supported: only member accesses may follow the require call"),
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let path = match argument {
+        Expression::String(token) => match token.token_type() {
+            TokenType::StringLiteral { literal, .. } => RequirePath::String(literal.to_string()),
+            _ => unreachable!(),
+        },
+        _ => RequirePath::Instance(expression_to_components(argument)?),
+    };
+
+    Ok(MatchedRequire {
+        path,
+        argument: argument.clone(),
+        members,
+    })
+}
+
+/// Finds the require behind a field of the table a module returns, e.g. `Field = require(script.Field)`,
+/// either directly or through a local variable the field is set to
+pub fn find_table_field_require(code: &str, field: &str) -> Result<Option<RequirePath>> {
+    let parsed_code = full_moon::parse(code).map_err(|errors| {
+        anyhow::anyhow!(errors
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    })?;
+
+    let Some(LastStmt::Return(r#return)) = parsed_code.nodes().last_stmt() else {
+        return Ok(None);
+    };
+    let Some(Expression::TableConstructor(table)) = r#return.returns().iter().next() else {
+        return Ok(None);
+    };
+
+    let value = table
+        .fields()
+        .iter()
+        .find_map(|table_field| match table_field {
+            Field::NameKey { key, value, .. } if key.token().to_string() == field => Some(value),
+            Field::ExpressionKey { key, value, .. }
+                if string_literal(key).as_deref() == Some(field) =>
+            {
+                Some(value)
+            }
+            _ => None,
+        });
+    let Some(mut value) = value else {
+        return Ok(None);
+    };
+
+    if let Expression::Var(Var::Name(name)) = value {
+        let name = name.token().to_string();
+        let assigned = parsed_code.nodes().stmts().find_map(|stmt| {
+            let Stmt::LocalAssignment(local_assignment) = stmt else {
+                return None;
+            };
+            let index = local_assignment
+                .names()
+                .iter()
+                .position(|local| local.token().to_string() == name)?;
+            local_assignment.expressions().iter().nth(index)
+        });
+        match assigned {
+            Some(assigned) => value = assigned,
+            None => return Ok(None),
+        }
+    }
+
+    Ok(match match_require(value) {
+        Ok(require) if require.members.is_empty() => Some(require.path),
+        _ => None,
+    })
+}
+
+/// Finds the require behind a module whose exported types all re-export the same types of a single required
+/// module, e.g. `export type Signal<T> = Types.Signal<T>` with `local Types = require(...)`, as packages which share
+/// the types of a peer dependency do. Returns the require alongside the names of the re-exported types
+pub fn find_re_exported_require(code: &str) -> Result<Option<(RequirePath, Vec<String>)>> {
+    let parsed_code = full_moon::parse(code).map_err(|errors| {
+        anyhow::anyhow!(errors
+            .iter()
+            .map(|err| err.to_string())
+            .collect::<Vec<_>>()
+            .join("\n"))
+    })?;
+
+    let mut module = None;
+    let mut type_names = Vec::new();
+    for stmt in parsed_code.nodes().stmts() {
+        let Stmt::ExportedTypeDeclaration(stmt) = stmt else {
+            continue;
+        };
+        let type_declaration = stmt.type_declaration();
+        let TypeInfo::Module {
+            module: type_module,
+            type_info,
+            ..
+        } = type_declaration.type_definition()
+        else {
+            return Ok(None);
+        };
+
+        let type_name = type_declaration.type_name().token().to_string();
+        let generics: Vec<String> = type_declaration
+            .generics()
+            .map(|generics| {
+                generics
+                    .generics()
+                    .iter()
+                    .map(|generic| match generic.parameter() {
+                        GenericParameterInfo::Name(name) => name.token().to_string(),
+                        GenericParameterInfo::Variadic { name, .. } => {
+                            format!("{}...", name.token())
+                        }
+                        other => unreachable!("unknown node: {:?}", other),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        // The type must be passed through unchanged, under the same name and with the same generics
+        let (base, arguments) = match type_info.as_ref() {
+            IndexedTypeInfo::Basic(base) => (base, Vec::new()),
+            IndexedTypeInfo::Generic { base, generics, .. } => (
+                base,
+                generics
+                    .iter()
+                    .map(|argument| argument.to_string().trim().to_string())
+                    .collect(),
+            ),
+            _ => return Ok(None),
+        };
+        if base.token().to_string() != type_name || arguments != generics {
+            return Ok(None);
+        }
+
+        let type_module = type_module.token().to_string();
+        if module.get_or_insert_with(|| type_module.clone()) != &type_module {
+            return Ok(None);
+        }
+        type_names.push(type_name);
+    }
+    let Some(module) = module else {
+        return Ok(None);
+    };
+
+    let assigned = parsed_code.nodes().stmts().find_map(|stmt| {
+        let Stmt::LocalAssignment(local_assignment) = stmt else {
+            return None;
+        };
+        let index = local_assignment
+            .names()
+            .iter()
+            .position(|local| local.token().to_string() == module)?;
+        local_assignment.expressions().iter().nth(index)
+    });
+
+    Ok(match assigned.map(match_require) {
+        Some(Ok(require)) if require.members.is_empty() => Some((require.path, type_names)),
+        _ => None,
+    })
 }
 
 #[cfg(test)]
 mod tests {
-    use full_moon::ast::Stmt;
-
     use super::*;
 
     fn require_expression(code: &str) -> Expression {
@@ -78,7 +480,8 @@ mod tests {
     }
 
     fn expression_into_components(code: &str, components: Vec<&str>) -> bool {
-        match_require(&require_expression(code)).unwrap() == components
+        match_require(&require_expression(code)).unwrap().path
+            == RequirePath::Instance(components.into_iter().map(String::from).collect())
     }
 
     #[test]
@@ -97,8 +500,195 @@ mod tests {
         ))
     }
 
+    #[test]
+    fn require_with_get_service() {
+        assert!(expression_into_components(
+            "require(game:GetService('ReplicatedStorage').Packages.Example)",
+            vec!["game", "ReplicatedStorage", "Packages", "Example"]
+        ))
+    }
+
+    #[test]
+    fn require_with_wait_for_child_and_find_first_child() {
+        assert!(expression_into_components(
+            "require(script.Parent:WaitForChild('_Index'):FindFirstChild('Example'))",
+            vec!["script", "Parent", "_Index", "Example"]
+        ))
+    }
+
+    #[test]
+    fn require_with_unknown_method_call() {
+        assert!(match_require(&require_expression("require(script.Parent:GetChildren())")).is_err())
+    }
+
+    #[test]
+    fn string_require() {
+        assert_eq!(
+            match_require(&require_expression(
+                "require('./_Index/sleitnick_signal@1.5.0/signal')"
+            ))
+            .unwrap()
+            .path,
+            RequirePath::String("./_Index/sleitnick_signal@1.5.0/signal".to_string())
+        )
+    }
+
+    #[test]
+    fn require_with_type_assertion_and_parentheses() {
+        let parsed_ast =
+            full_moon::parse("return (require(script.Parent.Example) :: any)").unwrap();
+        let Some(LastStmt::Return(r#return)) = parsed_ast.nodes().last_stmt() else {
+            unreachable!()
+        };
+
+        assert_eq!(
+            match_require(r#return.returns().iter().next().unwrap())
+                .unwrap()
+                .path,
+            RequirePath::Instance(vec![
+                "script".to_string(),
+                "Parent".to_string(),
+                "Example".to_string()
+            ])
+        );
+    }
+
+    #[test]
+    fn require_with_trailing_member_access() {
+        let parsed_ast =
+            full_moon::parse("return require(script.Parent.Example).SubModule['Other']").unwrap();
+        let Some(LastStmt::Return(r#return)) = parsed_ast.nodes().last_stmt() else {
+            unreachable!()
+        };
+
+        let require = match_require(r#return.returns().iter().next().unwrap()).unwrap();
+        assert_eq!(
+            require.path,
+            RequirePath::Instance(vec![
+                "script".to_string(),
+                "Parent".to_string(),
+                "Example".to_string()
+            ])
+        );
+        assert_eq!(require.members, vec!["SubModule", "Other"]);
+    }
+
+    #[test]
+    fn finds_table_field_requires() {
+        let code = r"
+            local Other = require(script.Parent.Other)
+            return {
+                SubModule = require(script.SubModule),
+                Other = Other,
+            }
+        ";
+
+        assert_eq!(
+            find_table_field_require(code, "SubModule").unwrap(),
+            Some(RequirePath::Instance(vec![
+                "script".to_string(),
+                "SubModule".to_string()
+            ]))
+        );
+        assert_eq!(
+            find_table_field_require(code, "Other").unwrap(),
+            Some(RequirePath::Instance(vec![
+                "script".to_string(),
+                "Parent".to_string(),
+                "Other".to_string()
+            ]))
+        );
+        assert_eq!(find_table_field_require(code, "Missing").unwrap(), None);
+    }
+
+    #[test]
+    fn classifies_link_files() {
+        let is_link_file = |code: &str| link_return(&full_moon::parse(code).unwrap()).is_some();
+
+        assert!(is_link_file("return require(script.Parent._Index.pkg)\n"));
+        assert!(is_link_file("-- comment\nreturn (require('./pkg') :: any)"));
+        assert!(is_link_file("return require(script.Parent.pkg).SubModule"));
+        assert!(!is_link_file("local Module = {}\nreturn Module"));
+        assert!(!is_link_file("local pkg = require(script.pkg)\nreturn pkg"));
+        assert!(!is_link_file("return {}"));
+        assert!(!is_link_file("print('hello')"));
+    }
+
+    #[test]
+    fn finds_requires_assigned_to_locals() {
+        let local_name = |code: &str| {
+            local_link_require(&full_moon::parse(code).unwrap()).map(|require| require.local_name)
+        };
+
+        assert_eq!(
+            local_name("local pkg = require(script.Parent._Index.pkg)\nreturn pkg\n").as_deref(),
+            Some("pkg")
+        );
+        assert_eq!(
+            local_name(
+                "-- wally 0.3.2\nlocal _VERSION = \"1.5.0\"\nlocal module = require('./pkg')\nlocal alias = module\nreturn alias"
+            )
+            .as_deref(),
+            Some("module")
+        );
+        assert_eq!(
+            local_name("local pkg = require(script.pkg)\nreturn _VERSION"),
+            None
+        );
+        assert_eq!(
+            local_name("local pkg = require(script.pkg)\nlocal pkg = {}\nreturn pkg"),
+            None
+        );
+        assert_eq!(
+            local_name("local a = require(script.a)\nlocal b = require(script.b)\nreturn a"),
+            None
+        );
+        assert_eq!(
+            local_name("local pkg = require(script.pkg)\nprint(pkg)\nreturn pkg"),
+            None
+        );
+        assert!(matches!(
+            find_link_require(&full_moon::parse("return require(script.pkg)").unwrap()),
+            Some(LinkRequire::Returned(_))
+        ));
+    }
+
     #[test]
     fn unhandled_require() {
-        assert!(match_require(&require_expression("require('string')")).is_err())
+        assert!(match_require(&require_expression("require(5)")).is_err())
+    }
+
+    #[test]
+    fn finds_re_exported_require() {
+        assert_eq!(
+            find_re_exported_require(
+                r#"
+                local Types = require(script.Parent.Types)
+                export type Signal<T...> = Types.Signal<T...>
+                export type Connection = Types.Connection
+                return { new = function() end }
+                "#
+            )
+            .unwrap(),
+            Some((
+                RequirePath::Instance(vec![
+                    "script".to_string(),
+                    "Parent".to_string(),
+                    "Types".to_string()
+                ]),
+                vec!["Signal".to_string(), "Connection".to_string()]
+            ))
+        );
+
+        for code in [
+            "local Types = require(script.Types)\nexport type Signal<T> = Types.Signal<T, number>",
+            "local Types = require(script.Types)\nexport type Event = Types.Signal",
+            "local Types = require(script.Types)\nexport type Signal = Types.Signal\nexport type Value = number",
+            "local Types = require(script.Types)\nlocal Other = require(script.Other)\n\
+             export type Signal = Types.Signal\nexport type Value = Other.Value",
+            "local Types = require(script.Types)",
+        ] {
+            assert_eq!(find_re_exported_require(code).unwrap(), None, "{code}");
+        }
     }
 }
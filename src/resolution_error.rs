@@ -0,0 +1,162 @@
+use std::{
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use full_moon::{ast::Expression, node::Node};
+
+/// Identifies why the require of a link file could not be resolved, so that it can be looked up in the README
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorCode {
+    /// The require expression does not start with `script` or `game`
+    UnsupportedRoot,
+    /// The link file itself is not in the sourcemap
+    LinkNotInSourcemap,
+    /// The require goes above the root of the sourcemap, and could not be resolved on disk either
+    NoParent,
+    /// A component of the require path is not a child of the node before it
+    ChildNotFound,
+    /// The required node has no Luau file
+    NoModuleFile,
+}
+
+impl ErrorCode {
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorCode::UnsupportedRoot => "E001",
+            ErrorCode::LinkNotInSourcemap => "E002",
+            ErrorCode::NoParent => "E003",
+            ErrorCode::ChildNotFound => "E004",
+            ErrorCode::NoModuleFile => "E005",
+        }
+    }
+
+    fn help(self) -> &'static str {
+        match self {
+            ErrorCode::UnsupportedRoot => {
+                "link files must require through `script` or `game`. Run `wally install` to regenerate them"
+            }
+            ErrorCode::LinkNotInSourcemap => {
+                "the packages folder is not in the sourcemap. Mount it in default.project.json, then regenerate \
+                 the sourcemap"
+            }
+            ErrorCode::NoParent => {
+                "the require goes above the root of the sourcemap. Check default.project.json mounts the packages \
+                 folder where its link files expect, or pass --root-map"
+            }
+            ErrorCode::ChildNotFound => {
+                "the sourcemap may be out of date. Run `wally install`, then regenerate the sourcemap. If the \
+                 folder is not mounted in default.project.json, add it"
+            }
+            ErrorCode::NoModuleFile => {
+                "the linked instance is not backed by a Luau file. Regenerate the sourcemap, and check the package \
+                 was installed correctly"
+            }
+        }
+    }
+}
+
+/// The require expression being resolved, and where it is in its link file
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct RequireSpan {
+    path: PathBuf,
+    expression: String,
+    line: usize,
+    character: usize,
+}
+
+/// Describes why the require of a link file could not be resolved: which component failed, how much of the
+/// path was resolved before it, and what may fix it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolutionError {
+    pub code: ErrorCode,
+    pub message: String,
+    /// The instance path resolved before the failure, e.g. `Game/ReplicatedStorage/Packages`
+    pub resolved: Option<String>,
+    require: Option<RequireSpan>,
+}
+
+impl ResolutionError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            resolved: None,
+            require: None,
+        }
+    }
+
+    pub fn with_resolved(self, resolved: String) -> Self {
+        Self {
+            resolved: Some(resolved),
+            ..self
+        }
+    }
+
+    /// Attributes the error to a require expression of a link file
+    pub fn in_require(self, path: &Path, expression: &Expression) -> Self {
+        let position = expression.start_position().unwrap_or_default();
+        Self {
+            require: Some(RequireSpan {
+                path: path.to_path_buf(),
+                expression: expression.to_string().trim().to_string(),
+                line: position.line(),
+                character: position.character(),
+            }),
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for ResolutionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.code.code(), self.message)?;
+        if let Some(require) = &self.require {
+            write!(
+                f,
+                "\n  --> {}:{}:{} `{}`",
+                require.path.display(),
+                require.line,
+                require.character,
+                require.expression
+            )?;
+        }
+        if let Some(resolved) = &self.resolved {
+            write!(f, "\n  resolved as far as '{resolved}'")?;
+        }
+        write!(f, "\n  help: {}", self.code.help())
+    }
+}
+
+impl std::error::Error for ResolutionError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_where_resolution_failed() {
+        let ast = full_moon::parse("return require(script.Parent.signal)\n").unwrap();
+        let require = crate::require_parser::link_return(&ast)
+            .unwrap()
+            .returns()
+            .iter()
+            .next()
+            .unwrap();
+
+        let error = ResolutionError::new(
+            ErrorCode::ChildNotFound,
+            "Child 'signal' not found in 'Game/Packages'",
+        )
+        .with_resolved("Game/Packages".to_string())
+        .in_require(Path::new("Packages/Signal.lua"), require);
+        assert_eq!(
+            error.to_string(),
+            "[E004] Child 'signal' not found in 'Game/Packages'\n  \
+             --> Packages/Signal.lua:1:8 `require(script.Parent.signal)`\n  \
+             resolved as far as 'Game/Packages'\n  \
+             help: the sourcemap may be out of date. Run `wally install`, then regenerate the sourcemap. If the \
+             folder is not mounted in default.project.json, add it"
+        );
+    }
+}
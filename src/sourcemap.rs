@@ -1,8 +1,18 @@
 use anyhow::{Context, Result};
+use log::info;
+use rayon::prelude::*;
 use serde::Deserialize;
-use std::path::PathBuf;
 
-#[derive(Deserialize, Debug)]
+use crate::file_access::FileAccess;
+use crate::path_normalization::{normalize_lexically, path_key};
+use std::{
+    collections::{BTreeMap, HashMap},
+    io,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct SourcemapNode {
     pub name: String,
@@ -14,25 +24,652 @@ pub struct SourcemapNode {
 }
 
 impl SourcemapNode {
-    pub fn find_child(&self, name: String) -> Option<&SourcemapNode> {
+    pub fn find_child(&self, name: &str) -> Option<&SourcemapNode> {
         self.children.iter().find(|child| child.name == name)
     }
+
+    /// Finds a descendant by the names leading down to it from this node, joined by `/`,
+    /// e.g. `ReplicatedStorage/Packages`. An empty name path refers to this node
+    pub fn find_by_name_path(&self, name_path: &str) -> Option<&SourcemapNode> {
+        name_path
+            .split('/')
+            .filter(|name| !name.is_empty())
+            .try_fold(self, |node, name| node.find_child(name))
+    }
+
+    /// Iterates over every descendant of this node, depth first, with parents before their children
+    pub fn descendants(&self) -> Descendants<'_> {
+        Descendants {
+            stack: self.children.iter().rev().collect(),
+        }
+    }
+
+    /// Whether the given node is this node or one of its descendants. Nodes are compared by identity,
+    /// so the node must be borrowed from this tree
+    pub fn is_ancestor_of(&self, node: &SourcemapNode) -> bool {
+        std::ptr::eq(self, node)
+            || self
+                .descendants()
+                .any(|descendant| std::ptr::eq(descendant, node))
+    }
+
+    /// Extracts the nodes with a file inside of the given directory, along with the nodes leading down to them.
+    /// Returns `None` if no node has a file inside of the directory
+    pub fn subtree_under(&self, directory: &Path) -> Option<SourcemapNode> {
+        let children: Vec<SourcemapNode> = self
+            .children
+            .iter()
+            .filter_map(|child| child.subtree_under(directory))
+            .collect();
+        let inside = self
+            .file_paths
+            .iter()
+            .any(|path| path.starts_with(directory));
+        (inside || !children.is_empty()).then(|| SourcemapNode {
+            name: self.name.clone(),
+            class_name: self.class_name.clone(),
+            file_paths: self.file_paths.clone(),
+            children,
+        })
+    }
 }
 
-/// Updates all file paths in the sourcemap into canonical form, to allow matching later
-pub fn mutate_sourcemap(node: &mut SourcemapNode) -> Result<()> {
-    node.file_paths = node
-        .file_paths
-        .iter()
-        .map(|path| {
-            path.canonicalize()
-                .context(format!("Failed to canonicalize '{}'", path.display()))
+/// Iterator over the descendants of a [`SourcemapNode`], created by [`SourcemapNode::descendants`]
+pub struct Descendants<'a> {
+    stack: Vec<&'a SourcemapNode>,
+}
+
+impl<'a> Iterator for Descendants<'a> {
+    type Item = &'a SourcemapNode;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let node = self.stack.pop()?;
+        self.stack.extend(node.children.iter().rev());
+        Some(node)
+    }
+}
+
+/// Index of a node within the [`Sourcemap`] arena
+pub type NodeId = usize;
+
+#[derive(Debug)]
+pub struct Node {
+    pub name: String,
+    pub class_name: String,
+    pub file_paths: Vec<PathBuf>,
+    pub parent: Option<NodeId>,
+    pub children: Vec<NodeId>,
+}
+
+/// A flattened sourcemap, where nodes are stored in an arena with pointers to their parents,
+/// and an index from file paths to the nodes which own them
+#[derive(Debug)]
+pub struct Sourcemap {
+    nodes: Vec<Node>,
+    /// Keyed by both the canonical and lexically normalized form of each path, so that files reached through a
+    /// symlink are found either way
+    path_index: HashMap<PathBuf, NodeId>,
+}
+
+impl Sourcemap {
+    pub fn root(&self) -> NodeId {
+        0
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn find_child(&self, id: NodeId, name: &str) -> Option<NodeId> {
+        self.nodes[id]
+            .children
+            .iter()
+            .copied()
+            .find(|child| self.nodes[*child].name == name)
+    }
+
+    /// Finds the node which owns the given file path, which may be canonical or lexically normalized
+    pub fn find_by_path(&self, path: &Path) -> Option<NodeId> {
+        self.path_index.get(&path_key(path)).copied()
+    }
+
+    /// Returns the names of all nodes from the root down to the given node, joined by `/`
+    pub fn name_path(&self, id: NodeId) -> String {
+        let mut names = Vec::new();
+        let mut current = Some(id);
+        while let Some(id) = current {
+            names.push(self.nodes[id].name.as_str());
+            current = self.nodes[id].parent;
+        }
+        names.reverse();
+        names.join("/")
+    }
+
+    /// Returns the names of the nodes leading from an ancestor down to one of its descendants,
+    /// or `None` if the node is not a descendant of the ancestor
+    pub fn relative_names(&self, ancestor: NodeId, descendant: NodeId) -> Option<Vec<String>> {
+        let mut names = Vec::new();
+        let mut current = descendant;
+        while current != ancestor {
+            names.push(self.nodes[current].name.clone());
+            current = self.nodes[current].parent?;
+        }
+        names.reverse();
+        Some(names)
+    }
+
+    /// Returns the components of an instance path from one node to another, relative to the former,
+    /// e.g. `["script", "Parent", "_Index", "Example"]`
+    pub fn relative_instance_path(&self, from: NodeId, to: NodeId) -> Vec<String> {
+        let mut components = vec!["script".to_string()];
+        let mut ancestor = from;
+        loop {
+            if let Some(names) = self.relative_names(ancestor, to) {
+                components.extend(names);
+                return components;
+            }
+            components.push("Parent".to_string());
+            // Every node descends from the root, so the search always ends there
+            ancestor = self.nodes[ancestor].parent.unwrap_or(self.root());
+        }
+    }
+}
+
+/// Canonicalizes all of the given file paths in parallel, so that files which are symlinks resolve to their
+/// targets. Files which do not exist yet, e.g. as they have not been synced, are placed in their canonical
+/// directory instead, or are normalized lexically if their directory does not exist either
+fn canonicalize_file_paths<'a>(
+    paths: impl Iterator<Item = &'a PathBuf>,
+    files: &FileAccess,
+) -> Result<HashMap<PathBuf, PathBuf>> {
+    let mut by_directory: HashMap<&Path, Vec<&PathBuf>> = HashMap::new();
+    for path in paths {
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+        by_directory.entry(directory).or_default().push(path);
+    }
+
+    by_directory
+        .into_par_iter()
+        .map(|(directory, paths)| {
+            // Only canonicalized once a file in the directory is found to be missing
+            let mut canonical_directory = None;
+            paths
+                .into_iter()
+                .map(|path| {
+                    let canonical = match (files.canonicalize(path), path.file_name()) {
+                        (Ok(canonical), _) => canonical,
+                        (Err(err), Some(file_name)) if err.kind() == io::ErrorKind::NotFound => {
+                            let canonical_directory = match &canonical_directory {
+                                Some(canonical_directory) => canonical_directory,
+                                None => canonical_directory
+                                    .insert(canonicalize_directory(directory, files)?),
+                            };
+                            canonical_directory.join(file_name)
+                        }
+                        (Err(err), _) => {
+                            return Err(err).with_context(|| {
+                                format!("Failed to canonicalize '{}'", path.display())
+                            })
+                        }
+                    };
+                    Ok((path.clone(), canonical))
+                })
+                .collect::<Result<Vec<_>>>()
         })
-        .collect::<Result<Vec<_>, _>>()?;
+        .collect::<Result<Vec<_>>>()
+        .map(|batches| batches.into_iter().flatten().collect())
+}
+
+/// Canonicalizes the directory of a file which does not exist, normalizing it lexically if it does not exist either
+fn canonicalize_directory(directory: &Path, files: &FileAccess) -> Result<PathBuf> {
+    match files.canonicalize(directory) {
+        Ok(canonical_directory) => Ok(canonical_directory),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {
+            info!(
+                "'{}' does not exist, matching files in it by their normalized paths",
+                directory.display()
+            );
+            Ok(normalize_lexically(directory))
+        }
+        Err(err) => {
+            Err(err).with_context(|| format!("Failed to canonicalize '{}'", directory.display()))
+        }
+    }
+}
+
+/// Maps the start of `game`-rooted require paths to where the instances actually are in the sourcemap,
+/// for projects which place packages somewhere other than where their link files expect, e.g.
+/// `game/ReplicatedStorage/Packages=game/ReplicatedStorage/Shared/Packages`
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Hash)]
+#[serde(try_from = "String")]
+pub struct RootMap {
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+impl FromStr for RootMap {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (from, to) = value
+            .split_once('=')
+            .ok_or_else(|| format!("root map '{value}' is not of the form `from=to`"))?;
+        let components = |path: &str| -> Vec<String> {
+            path.split('/')
+                .filter(|component| !component.is_empty())
+                .map(str::to_string)
+                .collect()
+        };
+        let (from, to) = (components(from), components(to));
+        if from.first().map(String::as_str) != Some("game")
+            || to.first().map(String::as_str) != Some("game")
+        {
+            return Err(format!(
+                "root map '{value}' must map a path starting with `game` to another"
+            ));
+        }
+        Ok(Self { from, to })
+    }
+}
+
+impl TryFrom<String> for RootMap {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl RootMap {
+    /// Rewrites the components of a require path using the first root map whose `from` it starts with
+    pub fn apply(root_maps: &[RootMap], components: Vec<String>) -> Vec<String> {
+        match root_maps
+            .iter()
+            .find(|root_map| components.starts_with(&root_map.from))
+        {
+            Some(root_map) => root_map
+                .to
+                .iter()
+                .chain(&components[root_map.from.len()..])
+                .cloned()
+                .collect(),
+            None => components,
+        }
+    }
+}
+
+/// Updates all file paths in the sourcemap into canonical form, to allow matching later,
+/// and flattens it into an indexed [`Sourcemap`]
+pub fn mutate_sourcemap(root: SourcemapNode, files: &FileAccess) -> Result<Sourcemap> {
+    let mut sourcemap = Sourcemap {
+        nodes: Vec::new(),
+        path_index: HashMap::new(),
+    };
+
+    // Walk the tree with an explicit stack so deeply nested sourcemaps do not overflow
+    let mut stack: Vec<(SourcemapNode, Option<NodeId>)> = vec![(root, None)];
+    while let Some((mut node, parent)) = stack.pop() {
+        let id = sourcemap.nodes.len();
+
+        if let Some(parent) = parent {
+            sourcemap.nodes[parent].children.push(id);
+        }
+
+        // Push children in reverse so they are assigned ids in their original order
+        let children = std::mem::take(&mut node.children);
+        stack.extend(children.into_iter().rev().map(|child| (child, Some(id))));
+
+        sourcemap.nodes.push(Node {
+            name: node.name,
+            class_name: node.class_name,
+            file_paths: node.file_paths,
+            parent,
+            children: Vec::new(),
+        });
+    }
+
+    let canonical_paths = canonicalize_file_paths(
+        sourcemap.nodes.iter().flat_map(|node| &node.file_paths),
+        files,
+    )?;
+    for (id, node) in sourcemap.nodes.iter_mut().enumerate() {
+        for path in &mut node.file_paths {
+            let normalized = normalize_lexically(path);
+            *path = canonical_paths[path.as_path()].clone();
+            for key in [path_key(path), path_key(&normalized)] {
+                sourcemap.path_index.entry(key).or_insert(id);
+            }
+        }
+    }
+
+    Ok(sourcemap)
+}
+
+/// The nodes which differ between two sourcemaps, identified by their name paths
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SourcemapDiff {
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    /// Nodes whose files are found at a different name path, as `(old, new)`
+    pub moved: Vec<(String, String)>,
+}
+
+impl SourcemapDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.moved.is_empty()
+    }
+}
+
+/// Returns the name path of each node inside a subtree whose name path ends with the `under` names,
+/// alongside the files of the node. Every node is returned if `under` is empty
+fn subtree_nodes<'a>(root: &'a SourcemapNode, under: &[&str]) -> BTreeMap<String, &'a [PathBuf]> {
+    let mut nodes = BTreeMap::new();
+    let mut stack = vec![(root, vec![root.name.as_str()], under.is_empty())];
+    while let Some((node, names, inside)) = stack.pop() {
+        let inside = inside || names.ends_with(under);
+        if inside {
+            nodes.insert(names.join("/"), node.file_paths.as_slice());
+        }
+
+        for child in &node.children {
+            let mut child_names = names.clone();
+            child_names.push(child.name.as_str());
+            stack.push((child, child_names, inside));
+        }
+    }
+    nodes
+}
+
+/// Compares the nodes under the given name path (e.g. `Packages` or `ReplicatedStorage/Packages`) of two sourcemaps.
+/// A node which was removed and added elsewhere with the same files is reported as moved
+pub fn diff_sourcemaps(old: &SourcemapNode, new: &SourcemapNode, under: &str) -> SourcemapDiff {
+    let under: Vec<&str> = under.split('/').filter(|name| !name.is_empty()).collect();
+    let old_nodes = subtree_nodes(old, &under);
+    let new_nodes = subtree_nodes(new, &under);
+
+    let mut diff = SourcemapDiff::default();
+    let mut added: Vec<&String> = new_nodes
+        .keys()
+        .filter(|path| !old_nodes.contains_key(*path))
+        .collect();
+
+    for (path, file_paths) in &old_nodes {
+        if new_nodes.contains_key(path) {
+            continue;
+        }
 
-    for child in &mut node.children {
-        mutate_sourcemap(child)?;
+        let moved_to = (!file_paths.is_empty())
+            .then(|| {
+                added
+                    .iter()
+                    .position(|new_path| new_nodes[*new_path] == *file_paths)
+            })
+            .flatten();
+        match moved_to {
+            Some(index) => diff.moved.push((path.clone(), added.remove(index).clone())),
+            None => diff.removed.push(path.clone()),
+        }
     }
+    diff.added = added.into_iter().cloned().collect();
 
-    Ok(())
+    diff
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_root_maps() {
+        let root_maps: Vec<RootMap> = vec![
+            "game/ReplicatedStorage/Packages=game/ReplicatedStorage/Shared/Packages"
+                .parse()
+                .unwrap(),
+        ];
+        let components = |path: &str| path.split('/').map(str::to_string).collect::<Vec<_>>();
+
+        assert_eq!(
+            RootMap::apply(
+                &root_maps,
+                components("game/ReplicatedStorage/Packages/_Index/signal")
+            ),
+            components("game/ReplicatedStorage/Shared/Packages/_Index/signal")
+        );
+        assert_eq!(
+            RootMap::apply(&root_maps, components("game/ServerStorage/Packages")),
+            components("game/ServerStorage/Packages")
+        );
+        assert!("script/Parent=game/Packages".parse::<RootMap>().is_err());
+        assert!("game/Packages".parse::<RootMap>().is_err());
+    }
+
+    fn sourcemap(json: &str) -> Sourcemap {
+        mutate_sourcemap(serde_json::from_str(json).unwrap(), &FileAccess::new(1)).unwrap()
+    }
+
+    #[test]
+    fn links_children_to_parents() {
+        let sourcemap = sourcemap(
+            r#"{
+                "name": "Game",
+                "className": "DataModel",
+                "children": [
+                    {
+                        "name": "ReplicatedStorage",
+                        "className": "ReplicatedStorage",
+                        "children": [{ "name": "Packages", "className": "Folder" }]
+                    },
+                    { "name": "ServerStorage", "className": "ServerStorage" }
+                ]
+            }"#,
+        );
+
+        let replicated_storage = sourcemap
+            .find_child(sourcemap.root(), "ReplicatedStorage")
+            .unwrap();
+        let packages = sourcemap
+            .find_child(replicated_storage, "Packages")
+            .unwrap();
+
+        assert_eq!(sourcemap.node(packages).parent, Some(replicated_storage));
+        assert_eq!(
+            sourcemap.node(replicated_storage).parent,
+            Some(sourcemap.root())
+        );
+        assert_eq!(
+            sourcemap.name_path(packages),
+            "Game/ReplicatedStorage/Packages"
+        );
+        let server_storage = sourcemap
+            .find_child(sourcemap.root(), "ServerStorage")
+            .unwrap();
+        assert_eq!(
+            sourcemap.relative_instance_path(packages, server_storage),
+            vec!["script", "Parent", "Parent", "ServerStorage"]
+        );
+        assert_eq!(
+            sourcemap.relative_instance_path(replicated_storage, packages),
+            vec!["script", "Packages"]
+        );
+    }
+
+    #[test]
+    fn matches_files_which_do_not_exist_yet() {
+        let sourcemap = sourcemap(
+            r#"{
+                "name": "Packages",
+                "className": "Folder",
+                "children": [
+                    { "name": "Later", "className": "ModuleScript", "filePaths": ["NotSynced/Later.lua"] }
+                ]
+            }"#,
+        );
+
+        let later = sourcemap.find_child(sourcemap.root(), "Later").unwrap();
+        assert_eq!(
+            sourcemap.find_by_path(&normalize_lexically(Path::new(
+                "./NotSynced/../NotSynced/Later.lua"
+            ))),
+            Some(later)
+        );
+        assert_eq!(
+            sourcemap.node(later).file_paths,
+            vec![std::env::current_dir().unwrap().join("NotSynced/Later.lua")]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_symlinked_files() {
+        let directory = std::env::temp_dir().join(format!(
+            "wally-package-types-symlinked-files-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(directory.join("Vendor")).unwrap();
+        std::fs::write(directory.join("Vendor/Signal.lua"), "return {}\n").unwrap();
+        let link = directory.join("Signal.lua");
+        let _ = std::fs::remove_file(&link);
+        std::os::unix::fs::symlink(directory.join("Vendor/Signal.lua"), &link).unwrap();
+
+        let sourcemap = sourcemap(
+            &serde_json::json!({
+                "name": "Packages",
+                "className": "Folder",
+                "children": [{ "name": "Signal", "className": "ModuleScript", "filePaths": [link] }]
+            })
+            .to_string(),
+        );
+
+        let signal = sourcemap.find_child(sourcemap.root(), "Signal").unwrap();
+        assert_eq!(
+            sourcemap.node(signal).file_paths,
+            vec![directory.join("Vendor/Signal.lua").canonicalize().unwrap()]
+        );
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn queries_sourcemap_nodes() {
+        let root: SourcemapNode = serde_json::from_str(
+            r#"{
+                "name": "Game",
+                "className": "DataModel",
+                "children": [
+                    {
+                        "name": "ReplicatedStorage",
+                        "className": "ReplicatedStorage",
+                        "children": [
+                            {
+                                "name": "Packages",
+                                "className": "Folder",
+                                "filePaths": ["Packages"],
+                                "children": [
+                                    { "name": "Signal", "className": "ModuleScript", "filePaths": ["Packages/Signal.lua"] }
+                                ]
+                            },
+                            { "name": "Shared", "className": "Folder", "filePaths": ["src/shared"] }
+                        ]
+                    },
+                    { "name": "ServerStorage", "className": "ServerStorage" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        let packages = root
+            .find_by_name_path("ReplicatedStorage/Packages")
+            .unwrap();
+        let signal = packages.find_child("Signal").unwrap();
+        assert!(std::ptr::eq(root.find_by_name_path("").unwrap(), &root));
+        assert!(root
+            .find_by_name_path("ReplicatedStorage/Missing")
+            .is_none());
+
+        assert_eq!(
+            root.descendants()
+                .map(|node| node.name.as_str())
+                .collect::<Vec<_>>(),
+            vec![
+                "ReplicatedStorage",
+                "Packages",
+                "Signal",
+                "Shared",
+                "ServerStorage"
+            ]
+        );
+        assert!(root.is_ancestor_of(signal));
+        assert!(packages.is_ancestor_of(signal));
+        assert!(!signal.is_ancestor_of(packages));
+
+        let subtree = root.subtree_under(Path::new("Packages")).unwrap();
+        assert_eq!(
+            subtree
+                .descendants()
+                .map(|node| node.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["ReplicatedStorage", "Packages", "Signal"]
+        );
+        assert!(root.subtree_under(Path::new("assets")).is_none());
+    }
+
+    #[test]
+    fn diffs_sourcemap_subtrees() {
+        let old: SourcemapNode = serde_json::from_str(
+            r#"{
+                "name": "Game",
+                "className": "DataModel",
+                "children": [
+                    {
+                        "name": "Packages",
+                        "className": "Folder",
+                        "children": [
+                            { "name": "Signal", "className": "ModuleScript", "filePaths": ["Packages/Signal.lua"] },
+                            { "name": "Promise", "className": "ModuleScript", "filePaths": ["Packages/Promise.lua"] },
+                            { "name": "Roact", "className": "ModuleScript", "filePaths": ["Packages/Roact.lua"] }
+                        ]
+                    },
+                    { "name": "Shared", "className": "Folder" }
+                ]
+            }"#,
+        )
+        .unwrap();
+        let new: SourcemapNode = serde_json::from_str(
+            r#"{
+                "name": "Game",
+                "className": "DataModel",
+                "children": [
+                    {
+                        "name": "Packages",
+                        "className": "Folder",
+                        "children": [
+                            { "name": "Signal", "className": "ModuleScript", "filePaths": ["Packages/Signal.lua"] },
+                            { "name": "React", "className": "ModuleScript", "filePaths": ["Packages/React.lua"] },
+                            { "name": "Future", "className": "ModuleScript", "filePaths": ["Packages/Promise.lua"] }
+                        ]
+                    },
+                    { "name": "Server", "className": "Folder" }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            diff_sourcemaps(&old, &new, "Packages/"),
+            SourcemapDiff {
+                added: vec!["Game/Packages/React".to_string()],
+                removed: vec!["Game/Packages/Roact".to_string()],
+                moved: vec![(
+                    "Game/Packages/Promise".to_string(),
+                    "Game/Packages/Future".to_string()
+                )],
+            }
+        );
+        assert_eq!(
+            diff_sourcemaps(&old, &new, "").removed,
+            vec!["Game/Packages/Roact".to_string(), "Game/Shared".to_string()]
+        );
+    }
 }
@@ -0,0 +1,92 @@
+use std::{
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::{bail, Context, Result};
+use log::{info, warn};
+
+/// The command a fresh sourcemap is generated with by `--generate-sourcemap`, where `{output}` is replaced with the
+/// path the sourcemap is written to
+pub const DEFAULT_SOURCEMAP_COMMAND: &str = "rojo sourcemap default.project.json -o {output}";
+
+const OUTPUT_PLACEHOLDER: &str = "{output}";
+
+/// A sourcemap generated for a single run, which is removed again once dropped
+pub struct GeneratedSourcemap {
+    path: PathBuf,
+}
+
+impl GeneratedSourcemap {
+    /// Runs the command to generate a sourcemap into a temporary file. Commands without an `{output}` placeholder
+    /// are expected to print the sourcemap to stdout instead. Arguments are split on whitespace, without quoting
+    pub fn generate(command: &str) -> Result<Self> {
+        let sourcemap = Self {
+            path: std::env::temp_dir().join(format!(
+                "wally-package-types-sourcemap-{}.json",
+                process::id()
+            )),
+        };
+        let output_path = sourcemap.path.to_string_lossy();
+
+        let mut arguments = command
+            .split_whitespace()
+            .map(|argument| argument.replace(OUTPUT_PLACEHOLDER, &output_path));
+        let program = arguments.next().context("Sourcemap command is empty")?;
+        info!("Generating sourcemap with `{command}`");
+
+        let output = process::Command::new(&program)
+            .args(arguments)
+            .output()
+            .with_context(|| {
+                format!("Failed to run sourcemap command '{program}'. Is it installed?")
+            })?;
+        if !output.status.success() {
+            bail!(
+                "Sourcemap command `{command}` failed with {}:\n{}",
+                output.status,
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+        if !command.contains(OUTPUT_PLACEHOLDER) {
+            std::fs::write(&sourcemap.path, &output.stdout)
+                .context("Failed to write generated sourcemap")?;
+        }
+
+        Ok(sourcemap)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for GeneratedSourcemap {
+    fn drop(&mut self) {
+        if let Err(err) = std::fs::remove_file(&self.path) {
+            if err.kind() != std::io::ErrorKind::NotFound {
+                warn!(
+                    "Failed to remove generated sourcemap '{}': {err}",
+                    self.path.display()
+                );
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_and_removes_sourcemaps() {
+        let sourcemap = GeneratedSourcemap::generate("echo {}").unwrap();
+        let path = sourcemap.path().to_path_buf();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "{}\n");
+
+        drop(sourcemap);
+        assert!(!path.exists());
+
+        assert!(GeneratedSourcemap::generate("false").is_err());
+    }
+}
@@ -0,0 +1,329 @@
+use std::{collections::BTreeSet, fmt::Write, path::Path, sync::Arc};
+
+use anyhow::Result;
+use full_moon::{
+    ast::{
+        luau::{ExportedTypeDeclaration, GenericParameterInfo, IndexedTypeInfo, TypeInfo},
+        punctuated::Punctuated,
+    },
+    tokenizer::TokenReference,
+    visitors::{Visit, Visitor},
+};
+
+use crate::lockfile::containing_index_folder;
+use crate::report::{Report, ThunkStatus};
+
+/// A way in which an exported type undermines strict type checking for its users
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StrictnessIssue {
+    /// The type is defined as `any`
+    DefinedAsAny,
+    /// `any` is passed as a generic argument, e.g. `Promise<any>`
+    AnyArgument { generic_type: String },
+    /// A generic parameter defaults to a type using `any`, e.g. `T = any`
+    AnyDefault { generic: String },
+}
+
+impl StrictnessIssue {
+    /// How badly the issue undermines strictness. A default only applies when the generic is left out,
+    /// whereas an `any` argument or definition cannot be avoided by users of the type
+    pub fn severity(&self) -> usize {
+        match self {
+            StrictnessIssue::DefinedAsAny => 3,
+            StrictnessIssue::AnyArgument { .. } => 2,
+            StrictnessIssue::AnyDefault { .. } => 1,
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            StrictnessIssue::DefinedAsAny => "defined as `any`".to_string(),
+            StrictnessIssue::AnyArgument { generic_type } => {
+                format!("passes `any` to `{generic_type}`")
+            }
+            StrictnessIssue::AnyDefault { generic } => {
+                format!("generic `{generic}` defaults to `any`")
+            }
+        }
+    }
+}
+
+/// The strictness issues of a single exported type
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeStrictness {
+    pub name: String,
+    pub issues: Vec<StrictnessIssue>,
+}
+
+/// The exported types of a package which undermine strictness
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageStrictness {
+    /// The package's folder in `_Index`, e.g. `sleitnick_signal@1.5.0`, or the link's name if it is not in one
+    pub package: String,
+    pub total_types: usize,
+    pub types: Vec<TypeStrictness>,
+}
+
+impl PackageStrictness {
+    pub fn score(&self) -> usize {
+        self.types
+            .iter()
+            .flat_map(|exported_type| &exported_type.issues)
+            .map(StrictnessIssue::severity)
+            .sum()
+    }
+}
+
+fn is_any(type_info: &TypeInfo) -> bool {
+    match type_info {
+        TypeInfo::Basic(token) => token.token().to_string() == "any",
+        TypeInfo::Variadic { type_info, .. } => is_any(type_info),
+        _ => false,
+    }
+}
+
+/// Finds where `any` is used within a type
+#[derive(Default)]
+struct AnyUsage {
+    contains_any: bool,
+    /// The generic types `any` is passed to
+    generic_types: Vec<String>,
+}
+
+impl AnyUsage {
+    fn of(type_info: &TypeInfo) -> Self {
+        let mut usage = Self::default();
+        type_info.visit(&mut usage);
+        usage
+    }
+
+    fn check_arguments(&mut self, base: &TokenReference, generics: &Punctuated<TypeInfo>) {
+        if generics.iter().any(is_any) {
+            self.generic_types.push(base.token().to_string());
+        }
+    }
+}
+
+impl Visitor for AnyUsage {
+    fn visit_type_info(&mut self, type_info: &TypeInfo) {
+        match type_info {
+            TypeInfo::Basic(_) if is_any(type_info) => self.contains_any = true,
+            TypeInfo::Generic { base, generics, .. } => self.check_arguments(base, generics),
+            _ => {}
+        }
+    }
+
+    fn visit_indexed_type_info(&mut self, type_info: &IndexedTypeInfo) {
+        if let IndexedTypeInfo::Generic { base, generics, .. } = type_info {
+            self.check_arguments(base, generics);
+        }
+    }
+}
+
+/// Finds the ways in which an exported type undermines strict type checking
+pub fn analyse_type(stmt: &ExportedTypeDeclaration) -> Vec<StrictnessIssue> {
+    let type_declaration = stmt.type_declaration();
+    let mut issues = Vec::new();
+
+    if let Some(generics) = type_declaration.generics() {
+        for generic in generics.generics() {
+            let name = match generic.parameter() {
+                GenericParameterInfo::Name(name) => name.token().to_string(),
+                GenericParameterInfo::Variadic { name, .. } => format!("{}...", name.token()),
+                other => unreachable!("unknown node: {:?}", other),
+            };
+            if generic
+                .default_type()
+                .is_some_and(|default| AnyUsage::of(default).contains_any)
+            {
+                issues.push(StrictnessIssue::AnyDefault { generic: name });
+            }
+        }
+    }
+
+    if is_any(type_declaration.type_definition()) {
+        issues.push(StrictnessIssue::DefinedAsAny);
+    } else {
+        for generic_type in AnyUsage::of(type_declaration.type_definition()).generic_types {
+            issues.push(StrictnessIssue::AnyArgument { generic_type });
+        }
+    }
+
+    issues
+}
+
+/// Analyses the exported types of each package linked to during the run, most severe first.
+/// Packages whose types do not undermine strictness are left out
+pub fn analyse_packages(
+    report: &Report,
+    type_declarations: impl Fn(&Path) -> Result<Arc<Vec<ExportedTypeDeclaration>>>,
+) -> Result<Vec<PackageStrictness>> {
+    let mut seen = BTreeSet::new();
+    let mut packages = Vec::new();
+
+    for thunk in &report.thunks {
+        let (ThunkStatus::Mutated { .. } | ThunkStatus::UpToDate { .. }, Some(target)) =
+            (&thunk.status, &thunk.target)
+        else {
+            continue;
+        };
+        let package = containing_index_folder(target)
+            .or_else(|| Some(thunk.path.file_stem()?.to_string_lossy().to_string()))
+            .unwrap_or_default();
+        if !seen.insert(package.clone()) {
+            continue;
+        }
+
+        let type_declarations = type_declarations(target)?;
+        let types: Vec<TypeStrictness> = type_declarations
+            .iter()
+            .map(|stmt| TypeStrictness {
+                name: stmt.type_declaration().type_name().token().to_string(),
+                issues: analyse_type(stmt),
+            })
+            .filter(|exported_type| !exported_type.issues.is_empty())
+            .collect();
+        if !types.is_empty() {
+            packages.push(PackageStrictness {
+                package,
+                total_types: type_declarations.len(),
+                types,
+            });
+        }
+    }
+
+    packages.sort_by(|a, b| b.score().cmp(&a.score()).then(a.package.cmp(&b.package)));
+    Ok(packages)
+}
+
+/// Renders an annotated report of the packages undermining strictness, for prioritising which to replace or fix
+pub fn render(packages: &[PackageStrictness]) -> String {
+    let mut text = String::new();
+    if packages.is_empty() {
+        text.push_str("No re-exported types use `any`\n");
+        return text;
+    }
+
+    for package in packages {
+        let _ = writeln!(
+            text,
+            "{} (score {}, {} of {} types)",
+            package.package,
+            package.score(),
+            package.types.len(),
+            package.total_types
+        );
+        for exported_type in &package.types {
+            let issues = exported_type
+                .issues
+                .iter()
+                .map(|issue| format!("{} [{}]", issue.describe(), issue.severity()))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(text, "  {}: {issues}", exported_type.name);
+        }
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::link_mutator::type_declarations_from_source;
+    use crate::report::ThunkReport;
+
+    fn analyse(code: &str) -> Vec<Vec<StrictnessIssue>> {
+        type_declarations_from_source(code)
+            .unwrap()
+            .iter()
+            .map(analyse_type)
+            .collect()
+    }
+
+    #[test]
+    fn finds_any_defaults_and_arguments() {
+        assert_eq!(
+            analyse(
+                r"
+                export type Signal<T... = ...any> = { Fire: (self: Signal<T...>, T...) -> () }
+                export type Store<S = { [string]: any }> = Types.Store<S, any>
+                export type Value = any
+                export type Promise<T> = { andThen: (self: Promise<T>, (T) -> ()) -> Promise<any> }
+                export type Strict<T> = { value: T }
+                "
+            ),
+            vec![
+                vec![StrictnessIssue::AnyDefault {
+                    generic: "T...".to_string()
+                }],
+                vec![
+                    StrictnessIssue::AnyDefault {
+                        generic: "S".to_string()
+                    },
+                    StrictnessIssue::AnyArgument {
+                        generic_type: "Store".to_string()
+                    }
+                ],
+                vec![StrictnessIssue::DefinedAsAny],
+                vec![StrictnessIssue::AnyArgument {
+                    generic_type: "Promise".to_string()
+                }],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn scores_packages() {
+        let thunk = |path: &str, target: &str| ThunkReport {
+            path: PathBuf::from(path),
+            target: Some(PathBuf::from(target)),
+            status: ThunkStatus::Mutated {
+                exported_types: vec![],
+            },
+            diff: None,
+        };
+        let report = Report {
+            thunks: vec![
+                thunk(
+                    "Packages/Signal.lua",
+                    "Packages/_Index/a_signal@1.0.0/signal.lua",
+                ),
+                thunk(
+                    "Packages/Value.lua",
+                    "Packages/_Index/a_value@1.0.0/value.lua",
+                ),
+                thunk(
+                    "Packages/_Index/b_other@1.0.0/Value.lua",
+                    "Packages/_Index/a_value@1.0.0/value.lua",
+                ),
+            ],
+            ..Default::default()
+        };
+
+        let packages = analyse_packages(&report, |target| {
+            let code = if target.ends_with("signal.lua") {
+                "export type Signal<T = any> = {}\nexport type Connection = {}"
+            } else {
+                "export type Value = any"
+            };
+            Ok(Arc::new(type_declarations_from_source(code)?))
+        })
+        .unwrap();
+
+        assert_eq!(
+            packages
+                .iter()
+                .map(|package| (package.package.as_str(), package.score()))
+                .collect::<Vec<_>>(),
+            vec![("a_value@1.0.0", 3), ("a_signal@1.0.0", 1)]
+        );
+        assert_eq!(
+            render(&packages),
+            "a_value@1.0.0 (score 3, 1 of 1 types)\n  Value: defined as `any` [3]\n\
+             a_signal@1.0.0 (score 1, 1 of 2 types)\n  Signal: generic `T` defaults to `any` [1]\n"
+        );
+    }
+}
@@ -0,0 +1,241 @@
+use std::{
+    collections::HashMap,
+    ffi::OsString,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+
+use crate::file_access::FileAccess;
+use crate::trace::PathKind;
+
+#[derive(Deserialize, Debug, Default)]
+struct LuauRc {
+    #[serde(default)]
+    aliases: HashMap<String, String>,
+}
+
+/// The parts of a Rojo project file needed to find the module its root is mapped to
+#[derive(Deserialize, Debug)]
+struct Project {
+    tree: ProjectNode,
+}
+
+#[derive(Deserialize, Debug)]
+struct ProjectNode {
+    #[serde(rename = "$path")]
+    path: Option<ProjectPath>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+enum ProjectPath {
+    Required(PathBuf),
+    Optional { optional: PathBuf },
+}
+
+/// Appends a suffix to a path, without treating any existing dots in the file name as an extension
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut path: OsString = path.as_os_str().to_owned();
+    path.push(suffix);
+    path.into()
+}
+
+/// Finds the directory an alias points to, searching `.luaurc` files from the requiring file upwards
+fn find_alias(requirer: &Path, alias: &str, files: &FileAccess) -> Result<PathBuf> {
+    for directory in requirer.ancestors().skip(1) {
+        let luaurc_path = directory.join(".luaurc");
+        if files.path_kind(&luaurc_path) != PathKind::File {
+            continue;
+        }
+
+        let contents = files
+            .read_to_string(&luaurc_path)
+            .with_context(|| format!("Failed to read '{}'", luaurc_path.display()))?;
+        let luaurc: LuauRc = serde_json::from_str(&contents)
+            .with_context(|| format!("Failed to parse '{}'", luaurc_path.display()))?;
+
+        // Alias names are case-insensitive
+        if let Some((_, path)) = luaurc
+            .aliases
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(alias))
+        {
+            return Ok(directory.join(path));
+        }
+    }
+
+    bail!("alias '@{alias}' is not defined in any .luaurc")
+}
+
+/// Resolves a module path without an extension to the Luau file it refers to
+pub fn resolve_module_file(path: &Path, files: &FileAccess) -> Result<PathBuf> {
+    let candidates = [
+        with_suffix(path, ".luau"),
+        with_suffix(path, ".lua"),
+        path.join("init.luau"),
+        path.join("init.lua"),
+    ];
+
+    candidates
+        .into_iter()
+        .find(|candidate| files.path_kind(candidate) == PathKind::File)
+        .with_context(|| format!("No .lua/.luau file found for '{}'", path.display()))
+}
+
+/// Whether a file is a Rojo project file, such as the `default.project.json` some packages ship with
+pub fn is_project_file(path: &Path) -> bool {
+    path.file_name()
+        .is_some_and(|name| name.to_string_lossy().ends_with(".project.json"))
+}
+
+/// Finds the canonical path of the Luau module a Rojo project file maps its root to, e.g. `src/init.luau` for a
+/// root `$path` of `src`. Returns `None` if the root is not mapped to a path
+pub fn project_module_file(project_path: &Path, files: &FileAccess) -> Result<Option<PathBuf>> {
+    let contents = files
+        .read_to_string(project_path)
+        .with_context(|| format!("Failed to read '{}'", project_path.display()))?;
+    let project: Project = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse '{}'", project_path.display()))?;
+    let Some(ProjectPath::Required(path) | ProjectPath::Optional { optional: path }) =
+        project.tree.path
+    else {
+        return Ok(None);
+    };
+
+    let module_path = project_path
+        .parent()
+        .context("Project file has no parent directory")?
+        .join(path);
+    let is_luau_file = module_path
+        .extension()
+        .is_some_and(|extension| extension == "lua" || extension == "luau");
+    let file_path = if is_luau_file && files.path_kind(&module_path) == PathKind::File {
+        module_path
+    } else {
+        resolve_module_file(&module_path, files)?
+    };
+    files
+        .canonicalize(&file_path)
+        .map(Some)
+        .with_context(|| format!("Failed to canonicalize '{}'", file_path.display()))
+}
+
+/// Resolves a string require (e.g. `./Example`, `../Example` or `@Packages/Example`), relative to the file
+/// containing it, to the canonical path of the file it points to
+pub fn resolve_string_require(
+    requirer: &Path,
+    require: &str,
+    files: &FileAccess,
+) -> Result<PathBuf> {
+    let module_path = if let Some(aliased) = require.strip_prefix('@') {
+        let (alias, rest) = aliased.split_once('/').unwrap_or((aliased, ""));
+        find_alias(requirer, alias, files)?.join(rest)
+    } else if require.starts_with("./") || require.starts_with("../") {
+        requirer
+            .parent()
+            .context("Link file has no parent directory")?
+            .join(require)
+    } else {
+        bail!("string require '{require}' must start with './', '../' or an '@' alias");
+    };
+
+    let file_path = resolve_module_file(&module_path, files)?;
+    files
+        .canonicalize(&file_path)
+        .with_context(|| format!("Failed to canonicalize '{}'", file_path.display()))
+}
+
+/// The path a string require should point to for a module file, which is its directory for `init` files, and
+/// otherwise the file without its extension, the inverse of [`resolve_module_file`]
+pub fn module_require_path(file_path: &Path) -> PathBuf {
+    match file_path.parent() {
+        Some(parent) if file_path.file_stem().is_some_and(|stem| stem == "init") => {
+            parent.to_path_buf()
+        }
+        _ => file_path.with_extension(""),
+    }
+}
+
+/// Creates a relative string require (e.g. `../Packages/Example`) from a file in the given directory to a module
+/// file. Both paths should be canonical
+pub fn relative_string_require(directory: &Path, file_path: &Path) -> Option<String> {
+    let module_path = module_require_path(file_path);
+    let common = directory
+        .components()
+        .zip(module_path.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    if common == 0 {
+        return None;
+    }
+
+    let mut require = match directory.components().count() - common {
+        0 => vec![".".to_string()],
+        parents => vec!["..".to_string(); parents],
+    };
+    require.extend(
+        module_path
+            .components()
+            .skip(common)
+            .map(|component| component.as_os_str().to_string_lossy().to_string()),
+    );
+    Some(require.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn suffix_does_not_replace_version_dots() {
+        assert_eq!(
+            with_suffix(Path::new("_Index/sleitnick_signal@1.5.0"), ".luau"),
+            PathBuf::from("_Index/sleitnick_signal@1.5.0.luau")
+        );
+    }
+
+    #[test]
+    fn resolves_project_file_root_module() {
+        let directory = std::env::temp_dir().join("wally-package-types-project-file");
+        std::fs::create_dir_all(directory.join("src")).unwrap();
+        std::fs::write(directory.join("src/init.luau"), "return {}\n").unwrap();
+        let project_path = directory.join("default.project.json");
+        let files = FileAccess::new(1);
+
+        std::fs::write(
+            &project_path,
+            r#"{ "name": "signal", "tree": { "$path": "src" } }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            project_module_file(&project_path, &files).unwrap(),
+            Some(directory.join("src/init.luau").canonicalize().unwrap())
+        );
+
+        std::fs::write(
+            &project_path,
+            r#"{ "name": "signal", "tree": { "$className": "Folder" } }"#,
+        )
+        .unwrap();
+        assert_eq!(project_module_file(&project_path, &files).unwrap(), None);
+    }
+
+    #[test]
+    fn creates_relative_string_requires() {
+        assert_eq!(
+            relative_string_require(
+                Path::new("/project/types"),
+                Path::new("/project/Packages/_Index/sleitnick_signal@1.5.0/signal/init.lua")
+            )
+            .as_deref(),
+            Some("../Packages/_Index/sleitnick_signal@1.5.0/signal")
+        );
+        assert_eq!(
+            relative_string_require(Path::new("/project"), Path::new("/project/Signal.luau"))
+                .as_deref(),
+            Some("./Signal")
+        );
+    }
+}
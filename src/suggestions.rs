@@ -0,0 +1,197 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use console::style;
+
+use crate::diagnostics::WarningCategory;
+use crate::lockfile::is_index_folder;
+use crate::report::{Report, ThunkStatus};
+
+/// A known cause of failures, which problems are matched against to suggest how to fix them
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Cause {
+    /// Nodes were missing from the sourcemap under the given packages folder
+    MissingFromSourcemap(String),
+    /// Linked modules could not be found or read
+    MissingModules,
+    /// Modules were too large or took too long to parse
+    CorruptModules,
+    /// Files were held open or locked by another process
+    LockedFiles,
+    /// Link files were of a form the tool does not understand
+    UnsupportedRequires,
+    /// Root links point to versions which are not locked
+    VersionMismatches,
+}
+
+impl Cause {
+    fn suggestion(&self, count: usize) -> String {
+        match self {
+            Cause::MissingFromSourcemap(folder) => format!(
+                "{count} failure(s) are nodes not found under {folder}. Did you include {folder} in \
+                 default.project.json and regenerate the sourcemap after running `wally install`?"
+            ),
+            Cause::MissingModules => format!(
+                "{count} failure(s) could not find or read the linked module. Run `wally install` to reinstall \
+                 your packages, then regenerate the sourcemap"
+            ),
+            Cause::CorruptModules => format!(
+                "{count} failure(s) are modules which are too large or too slow to parse, which usually means a \
+                 corrupt download. Delete the packages folder and run `wally install` again"
+            ),
+            Cause::LockedFiles => format!(
+                "{count} failure(s) are files held by another process. Close any editor or sync client using \
+                 the packages folder, or lower --max-open-files"
+            ),
+            Cause::UnsupportedRequires => format!(
+                "{count} failure(s) are link files with unsupported require expressions. Run `wally install` to \
+                 regenerate them, and report an issue if they persist"
+            ),
+            Cause::VersionMismatches => format!(
+                "{count} link file(s) point to versions which are not locked in wally.lock. Run `wally install`, \
+                 or run with --repair-links"
+            ),
+        }
+    }
+}
+
+/// The name of the packages folder a link file is inside of, e.g. `ServerPackages`
+fn packages_folder_name(path: &Path) -> Option<String> {
+    let components: Vec<_> = path.components().map(|c| c.as_os_str()).collect();
+    let folder = match components.iter().position(|c| is_index_folder(c)) {
+        Some(index) => components.get(index.checked_sub(1)?)?,
+        None => components.get(components.len().checked_sub(2)?)?,
+    };
+    Some(folder.to_string_lossy().to_string())
+}
+
+fn failure_cause(path: &Path, error: &str) -> Option<Cause> {
+    if error.contains("not found in sourcemap") || error.contains("not found in '") {
+        return Some(Cause::MissingFromSourcemap(
+            packages_folder_name(path).unwrap_or_else(|| "the packages folder".to_string()),
+        ));
+    }
+    if error.contains("larger than the maximum") || error.contains("took longer than") {
+        return Some(Cause::CorruptModules);
+    }
+    if error.contains("locked by another process") || error.contains("open files") {
+        return Some(Cause::LockedFiles);
+    }
+    if error.contains("No .lua/.luau file found") || error.contains("Failed to read linked file") {
+        return Some(Cause::MissingModules);
+    }
+    if error.contains("require expression not supported")
+        || error.contains("cannot determine starting point")
+    {
+        return Some(Cause::UnsupportedRequires);
+    }
+    None
+}
+
+/// Suggests next steps for the problems of a run, most common cause first
+fn suggestions(report: &Report) -> Vec<String> {
+    let mut causes: BTreeMap<Cause, usize> = BTreeMap::new();
+    for thunk in &report.thunks {
+        if let ThunkStatus::Failed { error, .. } = &thunk.status {
+            if let Some(cause) = failure_cause(&thunk.path, error) {
+                *causes.entry(cause).or_default() += 1;
+            }
+        }
+    }
+    for warning in &report.warnings {
+        if warning.category == WarningCategory::VersionMismatches {
+            *causes.entry(Cause::VersionMismatches).or_default() += 1;
+        }
+    }
+
+    let mut causes: Vec<_> = causes.into_iter().collect();
+    // Stable, so causes with the same count keep their order of importance
+    causes.sort_by(|(_, a), (_, b)| b.cmp(a));
+    causes
+        .iter()
+        .map(|(cause, count)| cause.suggestion(*count))
+        .collect()
+}
+
+/// Renders ranked next steps for a run which had failures, or nothing if it had none
+pub fn render(report: &Report) -> String {
+    let failed = report
+        .thunks
+        .iter()
+        .any(|thunk| matches!(thunk.status, ThunkStatus::Failed { .. }));
+    let suggestions = suggestions(report);
+    if !failed || suggestions.is_empty() {
+        return String::new();
+    }
+
+    let mut text = format!(
+        "{}{} suggested next steps\n",
+        style("help").cyan().bold().for_stderr(),
+        style(":").bold().for_stderr()
+    );
+    for (rank, suggestion) in suggestions.iter().enumerate() {
+        let _ = writeln!(text, "  {}. {suggestion}", rank + 1);
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::diagnostics::EmittedWarning;
+    use crate::failure::FailureClass;
+    use crate::report::ThunkReport;
+
+    fn failed(path: &str, error: &str) -> ThunkReport {
+        ThunkReport {
+            path: PathBuf::from(path),
+            target: None,
+            status: ThunkStatus::Failed {
+                error: error.to_string(),
+                class: FailureClass::Resolution,
+            },
+            diff: None,
+        }
+    }
+
+    #[test]
+    fn ranks_suggestions_by_number_of_failures() {
+        console::set_colors_enabled_stderr(false);
+        let mut report = Report {
+            thunks: vec![
+                failed(
+                    "ServerPackages/_Index/a_b@1.0.0/Promise.lua",
+                    "Child '_Index' not found in 'ServerPackages'",
+                ),
+                failed(
+                    "ServerPackages/Signal.lua",
+                    "Linker node 'ServerPackages/Signal.lua' not found in sourcemap",
+                ),
+                failed(
+                    "Packages/Promise.lua",
+                    "Failed to read linked file: '/Packages/_Index/promise/init.lua' is larger than the maximum of 1 bytes",
+                ),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render(&report),
+            "help: suggested next steps\n  \
+             1. 2 failure(s) are nodes not found under ServerPackages. Did you include ServerPackages in \
+             default.project.json and regenerate the sourcemap after running `wally install`?\n  \
+             2. 1 failure(s) are modules which are too large or too slow to parse, which usually means a \
+             corrupt download. Delete the packages folder and run `wally install` again\n"
+        );
+
+        report.thunks.clear();
+        report.warnings.push(EmittedWarning {
+            path: PathBuf::from("Packages/Signal.lua"),
+            category: WarningCategory::VersionMismatches,
+            message: "message".to_string(),
+            denied: false,
+        });
+        assert_eq!(render(&report), "");
+    }
+}
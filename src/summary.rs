@@ -0,0 +1,204 @@
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+use console::style;
+
+use crate::lockfile::containing_index_folder;
+use crate::report::{Report, ThunkStatus};
+
+/// How severe a problem in the end of run summary is
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    Error,
+    Warning,
+}
+
+/// A single problem with a link file, shown in the end of run summary
+struct Problem<'a> {
+    path: &'a Path,
+    /// The warning category, or whether the link file failed or was skipped
+    kind: String,
+    message: &'a str,
+}
+
+/// The package a link file belongs to, which is its folder in `_Index`, or its own name for a root link
+fn package_name(path: &Path) -> String {
+    containing_index_folder(path)
+        .or_else(|| Some(path.file_stem()?.to_string_lossy().to_string()))
+        .unwrap_or_default()
+}
+
+fn collect_problems(report: &Report) -> BTreeMap<Severity, BTreeMap<String, Vec<Problem<'_>>>> {
+    let failures = report.thunks.iter().filter_map(|thunk| {
+        let (kind, message) = match &thunk.status {
+            ThunkStatus::Failed { error, .. } => ("failed", error),
+            ThunkStatus::Skipped { reason } => ("skipped", reason),
+            _ => return None,
+        };
+        let problem = Problem {
+            path: &thunk.path,
+            kind: kind.to_string(),
+            message,
+        };
+        Some((Severity::Error, problem))
+    });
+    let warnings = report.warnings.iter().map(|warning| {
+        let severity = match warning.denied {
+            true => Severity::Error,
+            false => Severity::Warning,
+        };
+        let problem = Problem {
+            path: &warning.path,
+            kind: warning.category.to_string(),
+            message: &warning.message,
+        };
+        (severity, problem)
+    });
+
+    let mut problems: BTreeMap<Severity, BTreeMap<String, Vec<Problem>>> = BTreeMap::new();
+    for (severity, problem) in failures.chain(warnings) {
+        problems
+            .entry(severity)
+            .or_default()
+            .entry(package_name(problem.path))
+            .or_default()
+            .push(problem);
+    }
+    for packages in problems.values_mut() {
+        for package_problems in packages.values_mut() {
+            package_problems.sort_by(|a, b| a.path.cmp(b.path).then(a.kind.cmp(&b.kind)));
+        }
+    }
+    problems
+}
+
+/// Renders the problems of a run grouped by severity and then by package, with the count of each kind of problem.
+/// Errors are always listed individually, whereas warnings are only listed if `verbose` is set. Empty if there
+/// were no problems
+pub fn render(report: &Report, verbose: bool) -> String {
+    let problems = collect_problems(report);
+    let mut text = String::new();
+
+    for (severity, packages) in &problems {
+        let total: usize = packages.values().map(Vec::len).sum();
+        let (tag, noun) = match severity {
+            Severity::Error => (style("error").red(), "error"),
+            Severity::Warning => (style("warn").yellow(), "warning"),
+        };
+        let _ = writeln!(
+            text,
+            "{}{} {total} {noun}(s) in {} package(s)",
+            tag.bold().for_stderr(),
+            style(":").bold().for_stderr(),
+            packages.len()
+        );
+
+        for (package, package_problems) in packages {
+            let mut kinds: BTreeMap<&str, usize> = BTreeMap::new();
+            for problem in package_problems {
+                *kinds.entry(&problem.kind).or_default() += 1;
+            }
+            let kinds = kinds
+                .iter()
+                .map(|(kind, count)| format!("{kind}: {count}"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            let _ = writeln!(text, "  {} ({kinds})", style(package).bold().for_stderr());
+
+            if verbose || *severity == Severity::Error {
+                for problem in package_problems {
+                    // Details following the first line of a message, such as where a require failed, are indented under it
+                    let mut lines = problem.message.lines();
+                    let _ = writeln!(
+                        text,
+                        "    {}: {} [{}]",
+                        problem.path.display(),
+                        lines.next().unwrap_or_default(),
+                        problem.kind
+                    );
+                    for line in lines {
+                        let _ = writeln!(text, "    {line}");
+                    }
+                }
+            }
+        }
+    }
+
+    if problems.contains_key(&Severity::Warning) && !verbose {
+        text.push_str("Run with --verbose to list every problem\n");
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+    use crate::diagnostics::{EmittedWarning, WarningCategory};
+    use crate::failure::FailureClass;
+    use crate::report::ThunkReport;
+
+    #[test]
+    fn groups_problems_by_severity_and_package() {
+        console::set_colors_enabled_stderr(false);
+        let warning = |path: &str, category, denied| EmittedWarning {
+            path: PathBuf::from(path),
+            category,
+            message: "message".to_string(),
+            denied,
+        };
+        let report = Report {
+            thunks: vec![ThunkReport {
+                path: PathBuf::from("Packages/Promise.lua"),
+                target: None,
+                status: ThunkStatus::Failed {
+                    error: "Child 'promise' not found".to_string(),
+                    class: FailureClass::Resolution,
+                },
+                diff: None,
+            }],
+            warnings: vec![
+                warning(
+                    "Packages/_Index/a_signal@1.0.0/Promise.lua",
+                    WarningCategory::StrippedDefaults,
+                    false,
+                ),
+                warning(
+                    "Packages/_Index/a_signal@1.0.0/Janitor.lua",
+                    WarningCategory::StrippedDefaults,
+                    false,
+                ),
+                warning(
+                    "Packages/Signal.lua",
+                    WarningCategory::ShadowedBuiltins,
+                    false,
+                ),
+                warning(
+                    "Packages/Signal.lua",
+                    WarningCategory::VersionMismatches,
+                    true,
+                ),
+            ],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            render(&report, false),
+            "error: 2 error(s) in 2 package(s)\n  \
+             Promise (failed: 1)\n    \
+             Packages/Promise.lua: Child 'promise' not found [failed]\n  \
+             Signal (version-mismatches: 1)\n    \
+             Packages/Signal.lua: message [version-mismatches]\n\
+             warn: 3 warning(s) in 2 package(s)\n  \
+             Signal (shadowed-builtins: 1)\n  \
+             a_signal@1.0.0 (stripped-defaults: 2)\n\
+             Run with --verbose to list every problem\n"
+        );
+        assert!(render(&report, true).ends_with(
+            "  a_signal@1.0.0 (stripped-defaults: 2)\n    \
+             Packages/_Index/a_signal@1.0.0/Janitor.lua: message [stripped-defaults]\n    \
+             Packages/_Index/a_signal@1.0.0/Promise.lua: message [stripped-defaults]\n"
+        ));
+        assert_eq!(render(&Report::default(), false), "");
+    }
+}
@@ -0,0 +1,49 @@
+use std::time::{Duration, Instant};
+
+/// Records how long each phase of a run takes, for `--timings`
+pub struct Timings {
+    enabled: bool,
+    phase_start: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            phase_start: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    /// Marks the end of a phase, which started when the previous phase ended
+    pub fn phase(&mut self, name: &'static str) {
+        if self.enabled {
+            self.phases.push((name, self.phase_start.elapsed()));
+            self.phase_start = Instant::now();
+        }
+    }
+
+    /// Prints the duration of each phase to stderr, so it does not interfere with any report on stdout
+    pub fn print(&self) {
+        if !self.enabled {
+            return;
+        }
+
+        let width = self
+            .phases
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or_default();
+        for (name, duration) in &self.phases {
+            eprintln!("{name:<width$}  {:>8.1}ms", duration.as_secs_f64() * 1000.0);
+        }
+        let total: Duration = self.phases.iter().map(|(_, duration)| *duration).sum();
+        eprintln!(
+            "{:<width$}  {:>8.1}ms",
+            "total",
+            total.as_secs_f64() * 1000.0
+        );
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::build_info::BuildInfo;
+
+/// A file read during a recorded run, embedded so the run can be replayed elsewhere
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct RecordedFile {
+    pub hash: String,
+    pub contents: String,
+}
+
+/// An entry found whilst reading a directory during a recorded run
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RecordedDirEntry {
+    pub path: PathBuf,
+    pub is_file: bool,
+}
+
+/// The kind of item found at a path during a recorded run
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum PathKind {
+    Missing,
+    File,
+    Directory,
+}
+
+/// Reads the packages folders of a trace, which were recorded as a single folder by older versions
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<PathBuf>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(PathBuf),
+        Many(Vec<PathBuf>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(path) => vec![path],
+        OneOrMany::Many(paths) => paths,
+    })
+}
+
+/// Everything the tool observed of the filesystem during a run, alongside the resolution decisions it made.
+/// Replaying a trace re-runs the pipeline purely against these observations
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Trace {
+    pub sourcemap: PathBuf,
+    #[serde(alias = "packagesFolder", deserialize_with = "one_or_many")]
+    pub packages_folders: Vec<PathBuf>,
+    pub files: BTreeMap<PathBuf, RecordedFile>,
+    pub canonicalized: BTreeMap<PathBuf, Option<PathBuf>>,
+    pub directories: BTreeMap<PathBuf, Vec<RecordedDirEntry>>,
+    pub path_kinds: BTreeMap<PathBuf, PathKind>,
+    /// Maps each link file to the file its require expression resolved to
+    pub resolutions: BTreeMap<PathBuf, PathBuf>,
+    /// The binary which recorded the trace, missing from traces recorded by older versions
+    #[serde(default)]
+    pub build_info: Option<BuildInfo>,
+}
+
+impl Trace {
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path).context("Failed to read trace file")?;
+        serde_json::from_str(&contents).context("Failed to parse trace file")
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_json::to_string_pretty(self).context("Failed to serialize trace")?;
+        std::fs::write(path, contents).context("Failed to write trace file")
+    }
+}
+
+/// Hashes file contents using 64-bit FNV-1a, which is stable across platforms and releases
+pub fn hash_contents(contents: &str) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in contents.as_bytes() {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    format!("{hash:016x}")
+}
@@ -0,0 +1,262 @@
+use std::{
+    collections::BTreeMap,
+    io,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use log::{error, info, warn};
+use serde::{Deserialize, Serialize};
+
+use crate::file_access::FileAccess;
+use crate::trace::PathKind;
+
+/// The name of the journal of the last run which wrote any files, which is kept next to the packages folder
+pub const JOURNAL_FILE_NAME: &str = ".wally-package-types-journal.json";
+
+/// The contents a file had before it was written
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct JournalEntry {
+    path: PathBuf,
+    /// `None` if the file did not exist
+    previous_contents: Option<String>,
+}
+
+/// Records the previous contents of every file a run writes, so that they can be rolled back
+#[derive(Serialize, Deserialize, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+struct Journal {
+    version: String,
+    entries: Vec<JournalEntry>,
+}
+
+/// Writes a file by writing a temporary file next to it and renaming it over the original, so that the file
+/// is never left with partial contents
+fn write_atomic(files: &FileAccess, path: &Path, contents: &str) -> io::Result<()> {
+    let mut temporary = path.as_os_str().to_owned();
+    temporary.push(".tmp");
+    let temporary = PathBuf::from(temporary);
+    files.write(&temporary, contents)?;
+    files.rename(&temporary, path).inspect_err(|_| {
+        let _ = files.remove_file(&temporary);
+    })
+}
+
+fn restore(files: &FileAccess, entry: &JournalEntry) -> io::Result<()> {
+    match &entry.previous_contents {
+        Some(contents) => write_atomic(files, &entry.path, contents),
+        None => match files.remove_file(&entry.path) {
+            Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        },
+    }
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = path.as_os_str().to_owned();
+    backup.push(".bak");
+    PathBuf::from(backup)
+}
+
+/// Writes the files staged during a run. The previous contents of every file are written to the journal first,
+/// so that the run can be rolled back, and files written before a failure are restored. With `backup`,
+/// a `.bak` copy of the previous contents of each file is kept next to it too, unless one already exists. The
+/// backups are journaled as well, so rolling back removes them
+pub fn commit(
+    files: &FileAccess,
+    staged: BTreeMap<PathBuf, String>,
+    journal_path: &Path,
+    backup: bool,
+) -> Result<()> {
+    if staged.is_empty() {
+        return Ok(());
+    }
+
+    let mut journal = Journal {
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        entries: Vec::new(),
+    };
+    let written = staged.len();
+    // The contents written for each journal entry, in the same order
+    let mut writes = Vec::new();
+    for (path, contents) in staged {
+        let path = std::path::absolute(&path)
+            .with_context(|| format!("Failed to resolve '{}'", path.display()))?;
+        let previous_contents = match files.read_to_string(&path) {
+            Ok(contents) => Some(contents),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => {
+                return Err(err).with_context(|| format!("Failed to read '{}'", path.display()))
+            }
+        };
+        if let (Some(previous_contents), true) = (&previous_contents, backup) {
+            let backup_path = backup_path(&path);
+            // An existing backup holds the contents from before the first run, rather than a later run's output
+            if files.path_kind(&backup_path) != PathKind::Missing {
+                info!("Keeping existing backup '{}'", backup_path.display());
+            } else {
+                journal.entries.push(JournalEntry {
+                    path: backup_path,
+                    previous_contents: None,
+                });
+                writes.push(previous_contents.clone());
+            }
+        }
+        journal.entries.push(JournalEntry {
+            path,
+            previous_contents,
+        });
+        writes.push(contents);
+    }
+    let contents = serde_json::to_string_pretty(&journal).context("Failed to serialize journal")?;
+    write_atomic(files, journal_path, &contents)
+        .with_context(|| format!("Failed to write journal '{}'", journal_path.display()))?;
+
+    for (index, (entry, contents)) in journal.entries.iter().zip(&writes).enumerate() {
+        if let Err(err) = write_atomic(files, &entry.path, contents) {
+            error!(
+                "Failed to write '{}', restoring the files written before it",
+                entry.path.display()
+            );
+            for written in &journal.entries[..index] {
+                if let Err(err) = restore(files, written) {
+                    warn!("Failed to restore '{}': {err}", written.path.display());
+                }
+            }
+            return Err(err).with_context(|| format!("Failed to write '{}'", entry.path.display()));
+        }
+    }
+
+    let backups = journal.entries.len() - written;
+    match backups {
+        0 => info!("Wrote {written} file(s), run `wally-package-types rollback` to undo"),
+        _ => info!(
+            "Wrote {written} file(s) and {backups} backup(s), run `wally-package-types rollback` to undo"
+        ),
+    }
+    Ok(())
+}
+
+/// Restores every file written by the run which wrote the journal to its previous contents, then removes the
+/// journal
+pub fn rollback(files: &FileAccess, journal_path: &Path) -> Result<()> {
+    let contents = files.read_to_string(journal_path).with_context(|| {
+        format!(
+            "Failed to read journal '{}'. Has a run written any files?",
+            journal_path.display()
+        )
+    })?;
+    let journal: Journal = serde_json::from_str(&contents)
+        .with_context(|| format!("Failed to parse journal '{}'", journal_path.display()))?;
+
+    for entry in &journal.entries {
+        restore(files, entry)
+            .with_context(|| format!("Failed to restore '{}'", entry.path.display()))?;
+        info!("Restored '{}'", entry.path.display());
+    }
+    files
+        .remove_file(journal_path)
+        .with_context(|| format!("Failed to remove journal '{}'", journal_path.display()))?;
+
+    info!("Rolled back {} file(s)", journal.entries.len());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::memory_fs::MemoryFileSystem;
+
+    #[test]
+    fn rolls_back_committed_files() {
+        let directory = std::env::temp_dir().join(format!(
+            "wally-package-types-transaction-{}",
+            std::process::id()
+        ));
+        std::fs::create_dir_all(&directory).unwrap();
+        let link = directory.join("Signal.lua");
+        let stub = directory.join("Signal.d.luau");
+        let journal = directory.join(JOURNAL_FILE_NAME);
+        std::fs::write(&link, "return require(script.Parent.signal)\n").unwrap();
+        let files = FileAccess::new(1);
+
+        commit(
+            &files,
+            BTreeMap::from([
+                (link.clone(), "-- generated\n".to_string()),
+                (stub.clone(), "-- stub\n".to_string()),
+            ]),
+            &journal,
+            true,
+        )
+        .unwrap();
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "-- generated\n");
+        assert_eq!(std::fs::read_to_string(&stub).unwrap(), "-- stub\n");
+        assert_eq!(
+            std::fs::read_to_string(backup_path(&link)).unwrap(),
+            "return require(script.Parent.signal)\n"
+        );
+
+        rollback(&files, &journal).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(&link).unwrap(),
+            "return require(script.Parent.signal)\n"
+        );
+        assert!(!stub.exists());
+        assert!(!backup_path(&link).exists());
+        assert!(!journal.exists());
+
+        // Backups from before an earlier run are kept, rather than replaced with that run's output
+        std::fs::write(backup_path(&link), "-- original\n").unwrap();
+        commit(
+            &files,
+            BTreeMap::from([(link.clone(), "-- generated\n".to_string())]),
+            &journal,
+            true,
+        )
+        .unwrap();
+        rollback(&files, &journal).unwrap();
+        assert_eq!(
+            std::fs::read_to_string(backup_path(&link)).unwrap(),
+            "-- original\n"
+        );
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn commits_through_the_file_system() {
+        let file_system = Arc::new(MemoryFileSystem::new());
+        file_system.insert(
+            "Packages/Signal.lua",
+            "return require(script.Parent.signal)\n",
+        );
+        let files = FileAccess::new(1).with_file_system(file_system.clone());
+        let journal = Path::new("/.wally-package-types-journal.json");
+
+        commit(
+            &files,
+            BTreeMap::from([(
+                PathBuf::from("/Packages/Signal.lua"),
+                "-- generated\n".to_string(),
+            )]),
+            journal,
+            true,
+        )
+        .unwrap();
+        assert_eq!(
+            file_system.get("Packages/Signal.lua").unwrap(),
+            "-- generated\n"
+        );
+        assert!(file_system.get("Packages/Signal.lua.bak").is_some());
+
+        rollback(&files, journal).unwrap();
+        assert_eq!(
+            file_system.files().into_keys().collect::<Vec<_>>(),
+            vec![PathBuf::from("/Packages/Signal.lua")]
+        );
+    }
+}
@@ -0,0 +1,56 @@
+use std::{sync::mpsc, thread, time::Duration};
+
+use anyhow::{bail, Result};
+
+/// Runs an operation on its own thread, giving up on it if it has not finished within the timeout.
+/// An abandoned operation keeps running in the background until it finishes or the process exits,
+/// but no longer holds up the caller. Runs the operation directly if there is no timeout
+pub fn run_with_timeout<T: Send + 'static>(
+    timeout: Option<Duration>,
+    description: &str,
+    operation: impl FnOnce() -> T + Send + 'static,
+) -> Result<T> {
+    let Some(timeout) = timeout else {
+        return Ok(operation());
+    };
+
+    let (sender, receiver) = mpsc::channel();
+    thread::Builder::new()
+        .name("wally-package-types-watchdog".to_string())
+        .spawn(move || {
+            // The receiver is gone if the operation was abandoned, in which case the result is dropped
+            let _ = sender.send(operation());
+        })?;
+
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => Ok(result),
+        Err(mpsc::RecvTimeoutError::Timeout) => bail!(
+            "{description} took longer than {}s, so was abandoned. The file may be corrupt, \
+             try reinstalling the package, or raise --parse-timeout",
+            timeout.as_secs_f64()
+        ),
+        Err(mpsc::RecvTimeoutError::Disconnected) => bail!("{description} panicked"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn abandons_operations_which_time_out() {
+        let finished = run_with_timeout(Some(Duration::from_secs(5)), "Adding", || 1 + 1);
+        assert_eq!(finished.unwrap(), 2);
+
+        let abandoned = run_with_timeout(Some(Duration::from_millis(10)), "Sleeping", || {
+            thread::sleep(Duration::from_secs(1))
+        });
+        assert_eq!(
+            abandoned.unwrap_err().to_string(),
+            "Sleeping took longer than 0.01s, so was abandoned. The file may be corrupt, \
+             try reinstalling the package, or raise --parse-timeout"
+        );
+
+        assert_eq!(run_with_timeout(None, "Adding", || 1 + 1).unwrap(), 2);
+    }
+}
@@ -0,0 +1,166 @@
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+/// A position in a text document, where `character` is measured in UTF-16 code units as LSP expects by default
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub character: usize,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Range {
+    pub start: Position,
+    pub end: Position,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct TextEdit {
+    pub range: Range,
+    pub new_text: String,
+}
+
+/// An LSP `WorkspaceEdit`, mapping document URIs to the edits to apply to them
+#[derive(Serialize, Debug, Default)]
+pub struct WorkspaceEdit {
+    pub changes: BTreeMap<String, Vec<TextEdit>>,
+}
+
+/// Creates the edits which turn the old contents of a file into the new contents, one per changed group of lines
+pub fn text_edits(old: &str, new: &str) -> Vec<TextEdit> {
+    let diff = similar::TextDiff::from_lines(old, new);
+    let old_lines = diff.old_slices();
+    let new_lines = diff.new_slices();
+
+    // The position at the start of the given line, or the end of the document if there is no such line
+    let line_start = |line: usize| match old_lines.last() {
+        Some(last) if line == old_lines.len() && !last.ends_with('\n') => Position {
+            line: line - 1,
+            character: last.encode_utf16().count(),
+        },
+        _ => Position { line, character: 0 },
+    };
+
+    diff.ops()
+        .iter()
+        .filter(|op| op.tag() != similar::DiffTag::Equal)
+        .map(|op| TextEdit {
+            range: Range {
+                start: line_start(op.old_range().start),
+                end: line_start(op.old_range().end),
+            },
+            new_text: new_lines[op.new_range()].concat(),
+        })
+        .collect()
+}
+
+/// Converts a file path into a `file://` URI
+fn file_uri(path: &Path) -> Result<String> {
+    let path = std::path::absolute(path)
+        .with_context(|| format!("Failed to make '{}' absolute", path.display()))?;
+    let path = path.to_string_lossy().replace('\\', "/");
+
+    let mut uri = String::from("file://");
+    if !path.starts_with('/') {
+        uri.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{byte:02X}")),
+        }
+    }
+    Ok(uri)
+}
+
+/// Collects the changes made to link files during a run, so they can be applied by an editor instead of
+/// being written directly
+#[derive(Default)]
+pub struct WorkspaceEdits {
+    changes: Mutex<Vec<(PathBuf, Vec<TextEdit>)>>,
+}
+
+impl WorkspaceEdits {
+    pub fn record(&self, path: &Path, old: &str, new: &str) {
+        self.changes
+            .lock()
+            .unwrap()
+            .push((path.to_path_buf(), text_edits(old, new)));
+    }
+
+    pub fn workspace_edit(&self) -> Result<WorkspaceEdit> {
+        let mut workspace_edit = WorkspaceEdit::default();
+        for (path, edits) in self.changes.lock().unwrap().iter() {
+            workspace_edit
+                .changes
+                .insert(file_uri(path)?, edits.clone());
+        }
+        Ok(workspace_edit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn creates_edits_for_changed_lines() {
+        let edits = text_edits(
+            "return require(script.Parent.Module)\n",
+            "local REQUIRED_MODULE = require(script.Parent.Module)\nexport type Value = REQUIRED_MODULE.Value \nreturn REQUIRED_MODULE\n",
+        );
+
+        assert_eq!(
+            edits,
+            vec![TextEdit {
+                range: Range {
+                    start: Position {
+                        line: 0,
+                        character: 0
+                    },
+                    end: Position {
+                        line: 1,
+                        character: 0
+                    },
+                },
+                new_text: "local REQUIRED_MODULE = require(script.Parent.Module)\nexport type Value = REQUIRED_MODULE.Value \nreturn REQUIRED_MODULE\n".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn ends_edits_at_end_of_document_without_trailing_newline() {
+        let edits = text_edits("-- ñ\nreturn require(x)", "-- ñ\nreturn y\n");
+
+        assert_eq!(edits.len(), 1);
+        assert_eq!(
+            edits[0].range.start,
+            Position {
+                line: 1,
+                character: 0
+            }
+        );
+        assert_eq!(
+            edits[0].range.end,
+            Position {
+                line: 1,
+                character: 17
+            }
+        );
+    }
+
+    #[test]
+    fn percent_encodes_file_uris() {
+        let uri = file_uri(Path::new("/my packages/Signal.lua")).unwrap();
+        assert_eq!(uri, "file:///my%20packages/Signal.lua");
+    }
+}